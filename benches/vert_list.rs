@@ -0,0 +1,63 @@
+//! Throughput of VertList/PrimList parsing on a large synthetic path
+//!
+//! Run with `cargo bench --bench vert_list`.
+
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use laser_tools::lbrn2::{parse_lbrn2, parse_prim_list, parse_vert_list};
+use std::fmt::Write;
+
+const VERTEX_COUNT: usize = 50_000;
+
+/// A closed path of alternating line and Bezier vertices, in the style of
+/// LightBurn's vectorized output
+fn synthetic_lists(count: usize) -> (String, String) {
+    let mut verts = String::new();
+    let mut prims = String::new();
+    for i in 0..count {
+        let t = i as f64 / count as f64 * std::f64::consts::TAU;
+        let (x, y) = (100.0 * t.cos(), 60.0 * t.sin());
+        if i % 2 == 0 {
+            let _ = write!(verts, "V{:.6} {:.6}c0x1c1x1", x, y);
+            let _ = write!(prims, "L{} {}", i, (i + 1) % count);
+        } else {
+            let _ = write!(
+                verts,
+                "V{:.6} {:.6}c0x{:.6}c0y{:.6}c1x{:.6}c1y{:.6}",
+                x,
+                y,
+                x + 0.1,
+                y - 0.1,
+                x - 0.1,
+                y + 0.1
+            );
+            let _ = write!(prims, "B{} {}", i, (i + 1) % count);
+        }
+    }
+    (verts, prims)
+}
+
+fn bench_lists(c: &mut Criterion) {
+    let (verts, prims) = synthetic_lists(VERTEX_COUNT);
+
+    let mut group = c.benchmark_group("lists");
+    group.throughput(Throughput::Bytes(verts.len() as u64));
+    group.bench_function("parse_vert_list", |b| {
+        b.iter(|| parse_vert_list(black_box(&verts)))
+    });
+    group.throughput(Throughput::Bytes(prims.len() as u64));
+    group.bench_function("parse_prim_list", |b| {
+        b.iter(|| parse_prim_list(black_box(&prims)))
+    });
+    group.finish();
+
+    let document = format!(
+        "<LightBurnProject FormatVersion=\"1\">\n  <Shape Type=\"Path\" CutIndex=\"0\">\n    <XForm>1 0 0 1 0 0</XForm>\n    <VertList>{}</VertList>\n    <PrimList>{}</PrimList>\n  </Shape>\n</LightBurnProject>\n",
+        verts, prims
+    );
+    c.bench_function("parse_lbrn2", |b| {
+        b.iter(|| parse_lbrn2(black_box(&document)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_lists);
+criterion_main!(benches);
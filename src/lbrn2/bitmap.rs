@@ -0,0 +1,114 @@
+//! Helpers for the image data LightBurn embeds in Bitmap shapes
+//!
+//! The `Data` attribute holds a base64 encoded image file. LightBurn writes
+//! PNG most of the time but also keeps JPEG and BMP data as imported.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::io::Cursor;
+
+/// Decode the base64 `Data` attribute of a Bitmap shape
+///
+/// Whitespace is ignored, since long attribute values are sometimes wrapped.
+pub fn decode_bitmap_data(data: &str) -> Result<Vec<u8>, String> {
+    let compact: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    BASE64
+        .decode(compact.as_bytes())
+        .map_err(|e| format!("Invalid base64 bitmap data: {}", e))
+}
+
+/// Encode raw image bytes for the `Data` attribute of a Bitmap shape
+pub fn encode_bitmap_data(bytes: &[u8]) -> String {
+    BASE64.encode(bytes)
+}
+
+/// Detect the MIME type of an image from its magic bytes
+pub fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"BM") {
+        Some("image/bmp")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// File extension conventionally used for a MIME type
+pub fn mime_extension(mime: &str) -> &'static str {
+    match mime {
+        "image/jpeg" => "jpg",
+        "image/bmp" => "bmp",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => "png",
+    }
+}
+
+/// Whether browsers and SVG renderers generally display this MIME type
+pub fn is_web_image(mime: &str) -> bool {
+    matches!(
+        mime,
+        "image/png" | "image/jpeg" | "image/gif" | "image/webp"
+    )
+}
+
+/// Re-encode any supported image as PNG
+pub fn reencode_as_png(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let img =
+        image::load_from_memory(bytes).map_err(|e| format!("Failed to decode bitmap: {}", e))?;
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bmp_bytes() -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0]));
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Bmp).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_sniff_image_mime() {
+        assert_eq!(
+            sniff_image_mime(b"\x89PNG\r\n\x1a\n...."),
+            Some("image/png")
+        );
+        assert_eq!(
+            sniff_image_mime(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some("image/jpeg")
+        );
+        assert_eq!(sniff_image_mime(&bmp_bytes()), Some("image/bmp"));
+        assert_eq!(sniff_image_mime(b"GIF89a"), Some("image/gif"));
+        assert_eq!(
+            sniff_image_mime(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(sniff_image_mime(b"hello"), None);
+    }
+
+    #[test]
+    fn test_reencode_bmp_as_png() {
+        let png = reencode_as_png(&bmp_bytes()).unwrap();
+        assert_eq!(sniff_image_mime(&png), Some("image/png"));
+    }
+
+    #[test]
+    fn test_decode_ignores_whitespace() {
+        let encoded = encode_bitmap_data(b"\x89PNG\r\n\x1a\n");
+        let wrapped = format!("{}\n  {}", &encoded[..4], &encoded[4..]);
+        assert_eq!(decode_bitmap_data(&wrapped).unwrap(), b"\x89PNG\r\n\x1a\n");
+        assert!(decode_bitmap_data("not base64!").is_err());
+    }
+}
@@ -0,0 +1,600 @@
+//! Boolean operations on closed shapes
+//!
+//! Union, difference, intersection and exclusive or, and welding of layers.
+
+use super::geometry::{
+    BezPath, Point, bezpaths_to_path, cross, ring_area, segment_distance, shape_bezpaths, simplify,
+    sub,
+};
+use super::svg::flatten_groups;
+use super::types::{LightBurnProject, Shape, XForm};
+use std::collections::HashMap;
+
+/// Spacing in mm of the grid that split points are snapped to; points
+/// closer than this are treated as one
+const GRID: f64 = 1e-6;
+
+/// How the subject and clip areas are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOp {
+    /// Area covered by either
+    Union,
+    /// Area of the subject not covered by the clip
+    Difference,
+    /// Area covered by both
+    Intersection,
+    /// Area covered by exactly one
+    Xor,
+}
+
+impl BooleanOp {
+    fn apply(self, subject: bool, clip: bool) -> bool {
+        match self {
+            BooleanOp::Union => subject || clip,
+            BooleanOp::Difference => subject && !clip,
+            BooleanOp::Intersection => subject && clip,
+            BooleanOp::Xor => subject != clip,
+        }
+    }
+}
+
+/// Closed rings filling one area by the even-odd rule, so a ring inside
+/// another is a hole
+pub type Region = Vec<Vec<Point>>;
+
+/// Input edge and the region it bounds
+struct Edge {
+    a: Point,
+    b: Point,
+    region: usize,
+}
+
+/// Points where the edges `e` and `f` touch, cross or overlap, pushed onto
+/// the split lists of each
+fn intersect(e: &Edge, f: &Edge, on_e: &mut Vec<Point>, on_f: &mut Vec<Point>) {
+    // Endpoints on the other edge cover T-junctions and collinear overlaps
+    for p in [f.a, f.b] {
+        if segment_distance(p, e.a, e.b) <= GRID {
+            on_e.push(p);
+        }
+    }
+    for p in [e.a, e.b] {
+        if segment_distance(p, f.a, f.b) <= GRID {
+            on_f.push(p);
+        }
+    }
+
+    let (d, g) = (sub(e.b, e.a), sub(f.b, f.a));
+    let denom = cross(d, g);
+    if denom.abs() <= 1e-12 * d.0.hypot(d.1) * g.0.hypot(g.1) {
+        return;
+    }
+    let w = sub(f.a, e.a);
+    let t = cross(w, g) / denom;
+    let u = cross(w, d) / denom;
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        let p = (e.a.0 + t * d.0, e.a.1 + t * d.1);
+        on_e.push(p);
+        on_f.push(p);
+    }
+}
+
+/// Split points of every edge, found by sweeping the edges in order of
+/// their left end
+fn split_points(edges: &[Edge]) -> Vec<Vec<Point>> {
+    let min_x = |e: &Edge| e.a.0.min(e.b.0);
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by(|&i, &j| min_x(&edges[i]).total_cmp(&min_x(&edges[j])));
+
+    let mut splits = vec![Vec::new(); edges.len()];
+    let (mut on_e, mut on_f) = (Vec::new(), Vec::new());
+    let mut active: Vec<usize> = Vec::new();
+    for &i in &order {
+        let e = &edges[i];
+        active.retain(|&j| edges[j].a.0.max(edges[j].b.0) >= min_x(e) - GRID);
+        for &j in &active {
+            let f = &edges[j];
+            if e.a.1.min(e.b.1) > f.a.1.max(f.b.1) + GRID
+                || f.a.1.min(f.b.1) > e.a.1.max(e.b.1) + GRID
+            {
+                continue;
+            }
+            intersect(e, f, &mut on_e, &mut on_f);
+            splits[i].append(&mut on_e);
+            splits[j].append(&mut on_f);
+        }
+        active.push(i);
+    }
+    splits
+}
+
+/// Planar graph of the split edges, with coincident edges merged
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Point>,
+    node_ids: HashMap<(i64, i64), usize>,
+    edges: Vec<(usize, usize)>,
+    /// Regions bounded by each edge, once per input edge lying on it
+    regions: Vec<Vec<usize>>,
+    edge_ids: HashMap<(usize, usize), usize>,
+}
+
+impl Graph {
+    fn node(&mut self, p: Point) -> usize {
+        let key = ((p.0 / GRID).round() as i64, (p.1 / GRID).round() as i64);
+        *self.node_ids.entry(key).or_insert_with(|| {
+            self.nodes.push(p);
+            self.nodes.len() - 1
+        })
+    }
+
+    fn add_edge(&mut self, a: usize, b: usize, region: usize) {
+        let key = (a.min(b), a.max(b));
+        let id = *self.edge_ids.entry(key).or_insert_with(|| {
+            self.edges.push(key);
+            self.regions.push(Vec::new());
+            self.edges.len() - 1
+        });
+        self.regions[id].push(region);
+    }
+}
+
+fn build_graph(edges: &[Edge]) -> Graph {
+    let splits = split_points(edges);
+    let mut graph = Graph::default();
+    for (edge, mut points) in edges.iter().zip(splits) {
+        let d = sub(edge.b, edge.a);
+        let along = |p: &Point| (p.0 - edge.a.0) * d.0 + (p.1 - edge.a.1) * d.1;
+        points.push(edge.a);
+        points.push(edge.b);
+        points.sort_by(|p, q| along(p).total_cmp(&along(q)));
+
+        let mut prev = graph.node(points[0]);
+        for &p in &points[1..] {
+            let next = graph.node(p);
+            if next != prev {
+                graph.add_edge(prev, next, edge.region);
+                prev = next;
+            }
+        }
+    }
+    graph
+}
+
+/// Graph edges bucketed by the horizontal bands of the bounds they span, so
+/// a ray only tests the edges of one band
+struct Bands {
+    min_y: f64,
+    height: f64,
+    bands: Vec<Vec<usize>>,
+}
+
+impl Bands {
+    fn new(graph: &Graph) -> Self {
+        let ys = || graph.nodes.iter().map(|p| p.1);
+        let min_y = ys().fold(f64::INFINITY, f64::min);
+        let max_y = ys().fold(f64::NEG_INFINITY, f64::max);
+        let count = (graph.edges.len() as f64).sqrt().ceil().max(1.0) as usize;
+        let height = ((max_y - min_y) / count as f64).max(GRID);
+
+        let mut bands = Self {
+            min_y,
+            height,
+            bands: vec![Vec::new(); count],
+        };
+        for (id, &(a, b)) in graph.edges.iter().enumerate() {
+            let (y0, y1) = (graph.nodes[a].1, graph.nodes[b].1);
+            for band in bands.band(y0.min(y1))..=bands.band(y0.max(y1)) {
+                bands.bands[band].push(id);
+            }
+        }
+        bands
+    }
+
+    fn band(&self, y: f64) -> usize {
+        (((y - self.min_y) / self.height).max(0.0) as usize).min(self.bands.len() - 1)
+    }
+}
+
+/// Which of the subject and clip sets cover `p`, by counting how often a
+/// ray to the right crosses the edges of each region
+fn covered(
+    graph: &Graph,
+    bands: &Bands,
+    subjects: usize,
+    p: Point,
+    parity: &mut [bool],
+    touched: &mut Vec<usize>,
+) -> (bool, bool) {
+    for &id in &bands.bands[bands.band(p.1)] {
+        let (a, b) = graph.edges[id];
+        let (a, b) = (graph.nodes[a], graph.nodes[b]);
+        if (a.1 > p.1) != (b.1 > p.1) && a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0) > p.0 {
+            for &region in &graph.regions[id] {
+                parity[region] = !parity[region];
+                touched.push(region);
+            }
+        }
+    }
+
+    let (mut subject, mut clip) = (false, false);
+    for region in touched.drain(..) {
+        if parity[region] {
+            if region < subjects {
+                subject = true;
+            } else {
+                clip = true;
+            }
+        }
+        parity[region] = false;
+    }
+    (subject, clip)
+}
+
+/// Combine the areas of two sets of regions
+///
+/// A point is in a set when it is inside any of its regions. Returns the
+/// boundary of the result as closed rings, without a repeated closing
+/// point: outer boundaries counter-clockwise and holes clockwise.
+pub fn boolean_polygons(op: BooleanOp, subject: &[Region], clip: &[Region]) -> Vec<Vec<Point>> {
+    let mut edges = Vec::new();
+    for (region, rings) in subject.iter().chain(clip).enumerate() {
+        for ring in rings {
+            for (&a, &b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+                if a != b {
+                    edges.push(Edge { a, b, region });
+                }
+            }
+        }
+    }
+    if edges.is_empty() {
+        return Vec::new();
+    }
+
+    let graph = build_graph(&edges);
+    let bands = Bands::new(&graph);
+    let mut parity = vec![false; subject.len() + clip.len()];
+    let mut touched = Vec::new();
+
+    // Keep the edges with the result on exactly one side, directed to have
+    // it on their left
+    let mut kept: Vec<(usize, usize)> = Vec::new();
+    for &(a, b) in &graph.edges {
+        let (pa, pb) = (graph.nodes[a], graph.nodes[b]);
+        let d = sub(pb, pa);
+        let len = d.0.hypot(d.1);
+        let delta = (len * 1e-3).min(GRID / 4.0);
+        let mid = ((pa.0 + pb.0) / 2.0, (pa.1 + pb.1) / 2.0);
+        let normal = (-d.1 / len * delta, d.0 / len * delta);
+
+        let mut side = |sign: f64| {
+            let p = (mid.0 + sign * normal.0, mid.1 + sign * normal.1);
+            let (s, c) = covered(&graph, &bands, subject.len(), p, &mut parity, &mut touched);
+            op.apply(s, c)
+        };
+        match (side(1.0), side(-1.0)) {
+            (true, false) => kept.push((a, b)),
+            (false, true) => kept.push((b, a)),
+            _ => {}
+        }
+    }
+
+    link_rings(&graph.nodes, &kept)
+}
+
+/// Chain directed edges into closed rings, turning as far left as possible
+/// wherever several leave a node so loops touching at a point come apart
+fn link_rings(nodes: &[Point], kept: &[(usize, usize)]) -> Vec<Vec<Point>> {
+    let mut outgoing = vec![Vec::new(); nodes.len()];
+    for (id, &(a, _)) in kept.iter().enumerate() {
+        outgoing[a].push(id);
+    }
+
+    let mut used = vec![false; kept.len()];
+    let mut rings = Vec::new();
+    for start in 0..kept.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let (origin, mut at) = kept[start];
+        let mut prev = origin;
+        let mut ring = vec![nodes[origin]];
+        let closed = loop {
+            if at == origin {
+                break true;
+            }
+            ring.push(nodes[at]);
+            let incoming = sub(nodes[at], nodes[prev]);
+            let turn = |id: usize| {
+                let out = sub(nodes[kept[id].1], nodes[at]);
+                cross(incoming, out).atan2(incoming.0 * out.0 + incoming.1 * out.1)
+            };
+            let next = outgoing[at]
+                .iter()
+                .copied()
+                .filter(|&id| !used[id])
+                .max_by(|&i, &j| turn(i).total_cmp(&turn(j)));
+            let Some(id) = next else {
+                break false;
+            };
+            used[id] = true;
+            prev = at;
+            at = kept[id].1;
+        };
+
+        if closed {
+            let ring = simplify(&ring, GRID);
+            if ring.len() >= 3 && 2.0 * ring_area(&ring).abs() > GRID * GRID {
+                rings.push(ring);
+            }
+        }
+    }
+    rings
+}
+
+/// Flattened closed contours of a leaf shape, and whether it also has open
+/// ones
+fn leaf_region(shape: &Shape, tolerance: f64, log: &mut Vec<String>) -> (Region, bool) {
+    let paths = shape_bezpaths(shape, &XForm::identity(), log);
+    let open = paths.iter().any(|p| !p.closed);
+    let region = paths
+        .iter()
+        .filter(|p| p.closed)
+        .map(|p| p.flatten(tolerance).points)
+        .collect();
+    (region, open)
+}
+
+/// World-space path through result rings
+fn rings_shape(cut_index: i32, rings: &[Vec<Point>]) -> Option<Shape> {
+    let paths: Vec<BezPath> = rings.iter().map(|ring| BezPath::polygon(ring)).collect();
+    let identity = XForm::identity();
+    bezpaths_to_path(cut_index, identity, &identity, &paths).map(Shape::Path)
+}
+
+/// Combine the closed contours of two sets of shapes
+///
+/// Each shape, or each shape inside a group, fills its closed contours by
+/// the even-odd rule; open contours are ignored and curves are flattened to
+/// within `tolerance` mm. The result is a path in world coordinates on the
+/// cut index of the first shape, or `None` when it is empty.
+pub fn boolean_shapes(
+    op: BooleanOp,
+    subject: &[Shape],
+    clip: &[Shape],
+    tolerance: f64,
+) -> Option<Shape> {
+    let mut log = Vec::new();
+    let mut regions = |shapes: &[Shape]| {
+        let mut leaves = Vec::new();
+        flatten_groups(shapes, None, &mut leaves);
+        leaves
+            .iter()
+            .map(|leaf| leaf_region(leaf, tolerance, &mut log).0)
+            .collect::<Vec<_>>()
+    };
+    let (subject_regions, clip_regions) = (regions(subject), regions(clip));
+
+    let cut_index = subject.iter().chain(clip).next()?.cut_index();
+    let rings = boolean_polygons(op, &subject_regions, &clip_regions);
+    if rings.is_empty() {
+        return None;
+    }
+    rings_shape(cut_index, &rings)
+}
+
+/// Place in the welded project of a leaf shape
+enum Slot {
+    Keep(Box<Shape>),
+    /// Index into the welded layers
+    Layer(usize),
+}
+
+/// Weld the closed shapes of each layer into one path
+///
+/// Overlapping shapes merge into a single outline and shapes inside
+/// others become holes or islands by the even-odd rule within each shape.
+/// Groups are dissolved; each layer's path takes the place of its first
+/// welded shape, in world coordinates with curves flattened to within
+/// `tolerance` mm. Bitmaps, text without glyph outlines and shapes with
+/// open contours are kept as they are.
+pub fn weld_layers(project: &LightBurnProject, tolerance: f64) -> LightBurnProject {
+    weld_layers_with_warnings(project, tolerance).0
+}
+
+/// [`weld_layers`], also returning warnings about shapes left as they are
+pub fn weld_layers_with_warnings(
+    project: &LightBurnProject,
+    tolerance: f64,
+) -> (LightBurnProject, Vec<String>) {
+    let mut log = Vec::new();
+    let mut leaves = Vec::new();
+    flatten_groups(&project.shapes, None, &mut leaves);
+
+    let mut slots = Vec::with_capacity(leaves.len());
+    let mut layers: Vec<(i32, Vec<Region>)> = Vec::new();
+    for leaf in leaves {
+        let weldable = match &leaf {
+            Shape::Bitmap(_) => None,
+            Shape::Text(text) if text.backup_path.is_none() => {
+                log.push(format!(
+                    "Text \"{}\" left as is: no BackupPath outline to weld",
+                    text.text
+                ));
+                None
+            }
+            _ => match leaf_region(&leaf, tolerance, &mut log) {
+                (region, false) if !region.is_empty() => Some(region),
+                (_, true) => {
+                    log.push(format!(
+                        "Shape on layer {} left as is: welding needs closed contours",
+                        leaf.cut_index()
+                    ));
+                    None
+                }
+                _ => None,
+            },
+        };
+
+        let Some(region) = weldable else {
+            slots.push(Slot::Keep(Box::new(leaf)));
+            continue;
+        };
+        let cut_index = leaf.cut_index();
+        let layer = match layers.iter().position(|(index, _)| *index == cut_index) {
+            Some(layer) => layer,
+            None => {
+                slots.push(Slot::Layer(layers.len()));
+                layers.push((cut_index, Vec::new()));
+                layers.len() - 1
+            }
+        };
+        layers[layer].1.push(region);
+    }
+
+    let mut project = project.clone();
+    project.shapes = slots
+        .into_iter()
+        .filter_map(|slot| match slot {
+            Slot::Keep(shape) => Some(*shape),
+            Slot::Layer(layer) => {
+                let (cut_index, regions) = &layers[layer];
+                let rings = boolean_polygons(BooleanOp::Union, regions, &[]);
+                rings_shape(*cut_index, &rings)
+            }
+        })
+        .collect();
+    log.dedup();
+    (project, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::{square, square_ring};
+    use crate::lbrn2::types::{Path, PathPrimitive, Vec2};
+
+    fn area(rings: &[Vec<Point>]) -> f64 {
+        rings.iter().map(|ring| ring_area(ring)).sum()
+    }
+
+    #[test]
+    fn test_operations_on_overlapping_squares() {
+        let a = [vec![square_ring(0.0, 0.0, 10.0)]];
+        let b = [vec![square_ring(5.0, 5.0, 10.0)]];
+
+        let union = boolean_polygons(BooleanOp::Union, &a, &b);
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].len(), 8);
+        assert!((area(&union) - 175.0).abs() < 1e-9);
+
+        let difference = boolean_polygons(BooleanOp::Difference, &a, &b);
+        assert_eq!(difference.len(), 1);
+        assert!((area(&difference) - 75.0).abs() < 1e-9);
+
+        let intersection = boolean_polygons(BooleanOp::Intersection, &a, &b);
+        assert_eq!(intersection.len(), 1);
+        assert_eq!(intersection[0].len(), 4);
+        assert!((area(&intersection) - 25.0).abs() < 1e-9);
+
+        // The two L shapes touch at two corners but stay separate loops
+        let xor = boolean_polygons(BooleanOp::Xor, &a, &b);
+        assert_eq!(xor.len(), 2);
+        assert!(xor.iter().all(|ring| ring_area(ring) > 0.0));
+        assert!((area(&xor) - 150.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_holes_shared_edges_and_self_intersections() {
+        // Cutting out the middle leaves a clockwise hole
+        let holed = boolean_polygons(
+            BooleanOp::Difference,
+            &[vec![square_ring(0.0, 0.0, 10.0)]],
+            &[vec![square_ring(2.0, 2.0, 6.0)]],
+        );
+        assert_eq!(holed.len(), 2);
+        assert_eq!(holed.iter().filter(|r| ring_area(r) < 0.0).count(), 1);
+        assert!((area(&holed) - 64.0).abs() < 1e-9);
+
+        // A ring inside another in one region is already a hole
+        let framed = vec![square_ring(0.0, 0.0, 10.0), square_ring(2.0, 2.0, 6.0)];
+        let filled = boolean_polygons(
+            BooleanOp::Union,
+            &[framed],
+            &[vec![square_ring(4.0, 4.0, 2.0)]],
+        );
+        assert_eq!(filled.len(), 3);
+        assert!((area(&filled) - 68.0).abs() < 1e-9);
+
+        // Squares sharing an edge become one rectangle
+        let joined = boolean_polygons(
+            BooleanOp::Union,
+            &[
+                vec![square_ring(0.0, 0.0, 10.0)],
+                vec![square_ring(10.0, 0.0, 10.0)],
+            ],
+            &[],
+        );
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0].len(), 4);
+        assert!((area(&joined) - 200.0).abs() < 1e-9);
+
+        // A bowtie splits into its two triangles, both counter-clockwise
+        let bowtie = vec![vec![(0.0, 0.0), (10.0, 10.0), (10.0, 0.0), (0.0, 10.0)]];
+        let triangles = boolean_polygons(BooleanOp::Union, &[bowtie], &[]);
+        assert_eq!(triangles.len(), 2);
+        assert!(triangles.iter().all(|ring| ring.len() == 3));
+        assert!((area(&triangles) - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_weld_layers() {
+        let open = Shape::Path(Path::from_parts(
+            0,
+            XForm::identity(),
+            vec![Vec2::new(0.0, 20.0), Vec2::new(10.0, 20.0)],
+            vec![PathPrimitive::Line {
+                start_idx: 0,
+                end_idx: 1,
+            }],
+        ));
+        let project = LightBurnProject {
+            shapes: vec![
+                square(0.0, 0.0, 10.0, 0),
+                square(40.0, 0.0, 10.0, 1),
+                open,
+                square(5.0, 0.0, 10.0, 0),
+            ],
+            ..Default::default()
+        };
+
+        let (welded, log) = weld_layers_with_warnings(&project, 0.01);
+        assert_eq!(log.len(), 1);
+        assert_eq!(welded.shapes.len(), 3);
+        assert_eq!(welded.shapes[2], project.shapes[2]);
+
+        let Shape::Path(layer0) = &welded.shapes[0] else {
+            panic!("expected a path");
+        };
+        assert_eq!(layer0.cut_index, 0);
+        assert_eq!(layer0.parsed_verts.len(), 4);
+        let xs = layer0.parsed_verts.iter().map(|v| v.x);
+        assert_eq!(xs.clone().fold(f64::MAX, f64::min), -5.0);
+        assert_eq!(xs.fold(f64::MIN, f64::max), 10.0);
+        assert_eq!(welded.shapes[1].cut_index(), 1);
+
+        let cut = boolean_shapes(
+            BooleanOp::Difference,
+            &project.shapes[..1],
+            &project.shapes[3..],
+            0.01,
+        )
+        .unwrap();
+        let Shape::Path(cut) = cut else {
+            panic!("expected a path");
+        };
+        assert_eq!(cut.parsed_verts.len(), 4);
+        assert!(boolean_shapes(BooleanOp::Intersection, &project.shapes[..1], &[], 0.01).is_none());
+    }
+}
@@ -1,8 +1,8 @@
-use super::types::{PathPrimitive, Shape};
+use super::types::{PathPrimitive, Shape, Text, XForm};
 use std::f64::consts::PI;
 
 /// Bounding box
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Bounds {
     pub min_x: f64,
     pub min_y: f64,
@@ -83,7 +83,7 @@ fn bezier_extrema(p0: (f64, f64), c0: (f64, f64), c1: (f64, f64), p1: (f64, f64)
 }
 
 /// Evaluate a cubic Bezier curve at parameter t
-fn bezier_point(
+pub(crate) fn bezier_point(
     t: f64,
     p0: (f64, f64),
     c0: (f64, f64),
@@ -102,135 +102,199 @@ fn bezier_point(
     (x, y)
 }
 
+/// Append the points of a cubic Bezier after `p0`, using enough uniform
+/// steps to stay within `tolerance` of the curve
+pub(crate) fn flatten_cubic(
+    p0: (f64, f64),
+    c0: (f64, f64),
+    c1: (f64, f64),
+    p1: (f64, f64),
+    tolerance: f64,
+    out: &mut Vec<(f64, f64)>,
+) {
+    // The chord error of n uniform steps is at most max|B''| / (8 n^2), and
+    // max|B''| is bounded by six times the largest second difference of the
+    // control polygon
+    let second_diff = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        (a.0 - 2.0 * b.0 + c.0).hypot(a.1 - 2.0 * b.1 + c.1)
+    };
+    let m = second_diff(p0, c0, c1).max(second_diff(c0, c1, p1));
+    let steps = (0.75 * m / tolerance.max(1e-6)).sqrt().ceil();
+    let steps = if steps.is_finite() {
+        (steps as usize).clamp(1, 1000)
+    } else {
+        1
+    };
+
+    for i in 1..=steps {
+        out.push(bezier_point(i as f64 / steps as f64, p0, c0, c1, p1));
+    }
+}
+
+/// Rough local box `(min_x, min_y, max_x, max_y)` of a text without glyph
+/// outlines, assuming glyphs 0.6 times as wide as they are tall
+fn estimated_text_box(text: &Text) -> Option<(f64, f64, f64, f64)> {
+    let lines: Vec<&str> = text.text.lines().collect();
+    let longest = lines.iter().map(|l| l.chars().count()).max()?;
+    if longest == 0 || text.h <= 0.0 {
+        return None;
+    }
+
+    let w = longest as f64 * text.h * 0.6;
+    let h = lines.len() as f64 * text.h;
+    let (x0, x1) = match text.align_h {
+        1 => (-w / 2.0, w / 2.0),
+        2 => (-w, 0.0),
+        _ => (0.0, w),
+    };
+    // Y up: the block hangs below its top line
+    let (y0, y1) = match text.align_v {
+        1 => (-h / 2.0, h / 2.0),
+        2 => (-h, 0.0),
+        3 => (0.0, h),
+        _ => (text.h - h, text.h),
+    };
+    Some((x0, y0, x1, y1))
+}
+
+/// Bounds of a set of points, or `None` if there are none
+fn bounds_of(points: impl IntoIterator<Item = (f64, f64)>) -> Option<Bounds> {
+    points.into_iter().fold(None, |acc, (x, y)| {
+        let point = Bounds::new(x, y, x, y);
+        Some(match acc {
+            None => point,
+            Some(mut b) => {
+                b.expand(&point);
+                b
+            }
+        })
+    })
+}
+
+/// Append the points of the arc `center + r (cos θ, sin θ)`, θ in
+/// `[start, end]`, at which its image under `m` reaches its extent: the two
+/// ends and any angle where the transformed x or y turns around
+fn push_arc_extremes(
+    center: (f64, f64),
+    r: f64,
+    (start, end): (f64, f64),
+    m: &XForm,
+    out: &mut Vec<(f64, f64)>,
+) {
+    let at = |theta: f64| m.transform_point(center.0 + r * theta.cos(), center.1 + r * theta.sin());
+    out.push(at(start));
+    out.push(at(end));
+
+    // x' = a r cos θ + c r sin θ turns at tan θ = c / a; likewise for y'
+    for base in [m.c.atan2(m.a), m.d.atan2(m.b)] {
+        for theta in [base, base + PI] {
+            let theta = theta.rem_euclid(2.0 * PI);
+            if theta >= start && theta <= end {
+                out.push(at(theta));
+            }
+        }
+    }
+}
+
 /// Get transformed bounds for a shape
+///
+/// Bounds are in SVG's Y-down space and exact: ellipses and rounded corners
+/// use their closed form under the transform, and Bezier segments are
+/// bounded by their ends and extrema, never by their control points.
 pub fn get_transformed_bounds(shape: &Shape) -> Option<Bounds> {
     let xform = shape.xform();
 
-    // Transform a point with the shape's transform and flip Y for SVG
-    let tx = |x: f64, y: f64| -> (f64, f64) {
-        (
-            xform.a * x + xform.c * y + xform.e,
-            -(xform.b * x + xform.d * y + xform.f),
-        )
+    // The shape's transform followed by the flip to SVG's Y-down space
+    let m = XForm {
+        b: -xform.b,
+        d: -xform.d,
+        f: -xform.f,
+        ..*xform
+    };
+    let tx = |x: f64, y: f64| m.transform_point(x, y);
+    let corners = |w: f64, h: f64| {
+        let (w, h) = (w / 2.0, h / 2.0);
+        [tx(-w, -h), tx(w, -h), tx(w, h), tx(-w, h)]
     };
 
-    let mut points_to_bound: Vec<(f64, f64)> = Vec::new();
+    let mut points: Vec<(f64, f64)> = Vec::new();
 
     match shape {
         Shape::Rect(rect) => {
-            let w = rect.w / 2.0;
-            let h = rect.h / 2.0;
-            points_to_bound.push((-w, -h));
-            points_to_bound.push((w, -h));
-            points_to_bound.push((w, h));
-            points_to_bound.push((-w, h));
+            let r = rect.cr.min(rect.w / 2.0).min(rect.h / 2.0).max(0.0);
+            if r == 0.0 {
+                return bounds_of(corners(rect.w, rect.h));
+            }
+
+            // Straight sides end on the arcs, so the arcs alone bound the rect
+            let (cx, cy) = (rect.w / 2.0 - r, rect.h / 2.0 - r);
+            let quarter = PI / 2.0;
+            for (i, center) in [(cx, cy), (-cx, cy), (-cx, -cy), (cx, -cy)]
+                .into_iter()
+                .enumerate()
+            {
+                let start = i as f64 * quarter;
+                push_arc_extremes(center, r, (start, start + quarter), &m, &mut points);
+            }
         }
         Shape::Ellipse(ellipse) => {
-            // Add center
-            points_to_bound.push((0.0, 0.0));
-
-            // Add cardinal points
-            points_to_bound.push((ellipse.rx, 0.0));
-            points_to_bound.push((-ellipse.rx, 0.0));
-            points_to_bound.push((0.0, ellipse.ry));
-            points_to_bound.push((0.0, -ellipse.ry));
-
-            // Sample 32 points around the ellipse
-            let steps = 32;
-            for i in 0..steps {
-                let theta = 2.0 * PI * (i as f64) / (steps as f64);
-                let x = ellipse.rx * theta.cos();
-                let y = ellipse.ry * theta.sin();
-                points_to_bound.push((x, y));
-            }
+            // Half extents of the image of (rx cos θ, ry sin θ) under m
+            let hx = (m.a * ellipse.rx).hypot(m.c * ellipse.ry);
+            let hy = (m.b * ellipse.rx).hypot(m.d * ellipse.ry);
+            return Some(Bounds::new(m.e - hx, m.f - hy, m.e + hx, m.f + hy));
         }
         Shape::Path(path) => {
-            if path.parsed_verts.is_empty() {
+            let verts = &path.parsed_verts;
+            if verts.is_empty() {
                 return None;
             }
 
-            if path.prim_list == "LineClosed" {
-                // Use all vertices
-                for v in &path.parsed_verts {
-                    points_to_bound.push((v.x, v.y));
-                }
-            } else if !path.parsed_primitives.is_empty() {
-                for prim in &path.parsed_primitives {
-                    match prim {
-                        PathPrimitive::Line { start_idx, end_idx } => {
-                            if *start_idx < path.parsed_verts.len() {
-                                let p0 = &path.parsed_verts[*start_idx];
-                                points_to_bound.push((p0.x, p0.y));
-                            }
-                            if *end_idx < path.parsed_verts.len() {
-                                let p1 = &path.parsed_verts[*end_idx];
-                                points_to_bound.push((p1.x, p1.y));
-                            }
-                        }
-                        PathPrimitive::Bezier { start_idx, end_idx } => {
-                            if *start_idx >= path.parsed_verts.len()
-                                || *end_idx >= path.parsed_verts.len()
-                            {
-                                continue;
-                            }
-
-                            let p0 = &path.parsed_verts[*start_idx];
-                            let p1 = &path.parsed_verts[*end_idx];
-
-                            points_to_bound.push((p0.x, p0.y));
-                            points_to_bound.push((p1.x, p1.y));
-
-                            // Add control points
-                            if let (Some(c0x), Some(c0y)) = (p0.c0x, p0.c0y) {
-                                points_to_bound.push((c0x, c0y));
-                            }
-                            if let (Some(c1x), Some(c1y)) = (p1.c1x, p1.c1y) {
-                                points_to_bound.push((c1x, c1y));
-                            }
-
-                            // Calculate Bezier extrema points
-                            if let (Some(c0x), Some(c0y), Some(c1x), Some(c1y)) =
-                                (p0.c0x, p0.c0y, p1.c1x, p1.c1y)
-                            {
-                                let c0 = (c0x, c0y);
-                                let c1 = (c1x, c1y);
-                                let ts = bezier_extrema((p0.x, p0.y), c0, c1, (p1.x, p1.y));
-
-                                for t in ts {
-                                    let pt = bezier_point(t, (p0.x, p0.y), c0, c1, (p1.x, p1.y));
-                                    points_to_bound.push(pt);
-                                }
-                            }
+            if path.is_line_closed() || path.parsed_primitives.is_empty() {
+                return bounds_of(verts.iter().map(|v| tx(v.x, v.y)));
+            }
+
+            for prim in path.parsed_primitives.iter() {
+                let (PathPrimitive::Line { start_idx, end_idx }
+                | PathPrimitive::Bezier { start_idx, end_idx }) = prim;
+                let (Some(p0), Some(p1)) = (verts.get(*start_idx), verts.get(*end_idx)) else {
+                    continue;
+                };
+                let (q0, q1) = (tx(p0.x, p0.y), tx(p1.x, p1.y));
+
+                // A Bezier missing control points is drawn as a line
+                let controls = match prim {
+                    PathPrimitive::Bezier { .. } => p0.c0x.zip(p0.c0y).zip(p1.c1x.zip(p1.c1y)),
+                    PathPrimitive::Line { .. } => None,
+                };
+                match controls {
+                    // An affine image of a Bezier is the Bezier of the
+                    // transformed control points, so find extrema after
+                    // transforming
+                    Some(((c0x, c0y), (c1x, c1y))) => {
+                        let (c0, c1) = (tx(c0x, c0y), tx(c1x, c1y));
+                        for t in bezier_extrema(q0, c0, c1, q1) {
+                            points.push(bezier_point(t, q0, c0, c1, q1));
                         }
                     }
-                }
-            } else {
-                // Fallback: use all vertices
-                for v in &path.parsed_verts {
-                    points_to_bound.push((v.x, v.y));
+                    None => points.extend([q0, q1]),
                 }
             }
         }
-        Shape::Bitmap(bitmap) => {
-            let w = bitmap.w / 2.0;
-            let h = bitmap.h / 2.0;
-            points_to_bound.push((-w, -h));
-            points_to_bound.push((w, -h));
-            points_to_bound.push((w, h));
-            points_to_bound.push((-w, h));
+        Shape::Bitmap(bitmap) => return bounds_of(corners(bitmap.w, bitmap.h)),
+        Shape::Text(text) => {
+            if let Some(outline) = text.outline() {
+                return get_transformed_bounds(&Shape::Path(outline));
+            }
+            let (x0, y0, x1, y1) = estimated_text_box(text)?;
+            return bounds_of([tx(x0, y0), tx(x1, y0), tx(x1, y1), tx(x0, y1)]);
         }
         Shape::Group(group) => {
-            if group.children.is_empty() {
-                return None;
-            }
-
             let mut combined_bounds: Option<Bounds> = None;
 
             for child in &group.children {
                 // Compose transforms
-                let effective_child_xform = xform.compose(child.xform());
                 let mut temp_child = child.clone();
-                *temp_child.xform_mut() = effective_child_xform;
+                *temp_child.xform_mut() = xform.compose(child.xform());
 
                 if let Some(child_bounds) = get_transformed_bounds(&temp_child) {
                     match &mut combined_bounds {
@@ -244,23 +308,145 @@ pub fn get_transformed_bounds(shape: &Shape) -> Option<Bounds> {
         }
     }
 
-    if points_to_bound.is_empty() {
-        return None;
+    bounds_of(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::types::{Ellipse, Path, Rect, Vec2};
+
+    const SAMPLES: usize = 20_000;
+
+    /// A rotated, sheared and translated placement
+    fn skewed() -> XForm {
+        XForm {
+            a: 0.8,
+            b: 0.6,
+            c: -0.3,
+            d: 1.1,
+            e: 5.0,
+            f: -2.0,
+        }
     }
 
-    let transformed: Vec<(f64, f64)> = points_to_bound.into_iter().map(|(x, y)| tx(x, y)).collect();
+    /// Bounds of local points placed with `xform` in SVG's Y-down space
+    fn sampled(xform: &XForm, points: impl Iterator<Item = (f64, f64)>) -> Bounds {
+        bounds_of(points.map(|(x, y)| {
+            let (x, y) = xform.transform_point(x, y);
+            (x, -y)
+        }))
+        .unwrap()
+    }
 
-    if transformed.is_empty() {
-        return None;
+    fn assert_tight(exact: Bounds, truth: Bounds) {
+        for (e, t) in [
+            (exact.min_x, truth.min_x),
+            (exact.min_y, truth.min_y),
+            (exact.max_x, truth.max_x),
+            (exact.max_y, truth.max_y),
+        ] {
+            assert!((e - t).abs() < 1e-6, "{:?} vs sampled {:?}", exact, truth);
+        }
     }
 
-    let xs: Vec<f64> = transformed.iter().map(|(x, _)| *x).collect();
-    let ys: Vec<f64> = transformed.iter().map(|(_, y)| *y).collect();
+    fn angles() -> impl Iterator<Item = f64> {
+        (0..=SAMPLES).map(|i| 2.0 * PI * i as f64 / SAMPLES as f64)
+    }
+
+    #[test]
+    fn test_ellipse_bounds_match_sampling() {
+        let ellipse = Ellipse {
+            cut_index: 0,
+            xform: skewed(),
+            rx: 10.0,
+            ry: 4.0,
+        };
+        let truth = sampled(
+            &ellipse.xform,
+            angles().map(|t| (10.0 * t.cos(), 4.0 * t.sin())),
+        );
+        assert_tight(
+            get_transformed_bounds(&Shape::Ellipse(ellipse)).unwrap(),
+            truth,
+        );
+    }
 
-    Some(Bounds::new(
-        xs.iter().cloned().fold(f64::INFINITY, f64::min),
-        ys.iter().cloned().fold(f64::INFINITY, f64::min),
-        xs.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
-        ys.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
-    ))
+    #[test]
+    fn test_rounded_rect_bounds_match_sampling() {
+        let rect = Rect {
+            cut_index: 0,
+            xform: skewed(),
+            w: 20.0,
+            h: 8.0,
+            cr: 3.0,
+        };
+        // Convex, so the corner arcs trace the whole outline's extent
+        let (cx, cy) = (7.0, 1.0);
+        let truth = sampled(
+            &rect.xform,
+            angles().map(|t| {
+                let (sx, sy) = (t.cos().signum(), t.sin().signum());
+                (sx * cx + 3.0 * t.cos(), sy * cy + 3.0 * t.sin())
+            }),
+        );
+        let exact = get_transformed_bounds(&Shape::Rect(rect.clone())).unwrap();
+        assert_tight(exact, truth);
+
+        // Square corners are still the plain corner box
+        let square = Rect { cr: 0.0, ..rect };
+        let truth = sampled(
+            &square.xform,
+            [(-10.0, -4.0), (10.0, -4.0), (10.0, 4.0), (-10.0, 4.0)].into_iter(),
+        );
+        assert_tight(get_transformed_bounds(&Shape::Rect(square)).unwrap(), truth);
+    }
+
+    #[test]
+    fn test_bezier_bounds_exclude_control_points() {
+        let (p0, c0, c1, p1) = ((0.0, 0.0), (2.0, 30.0), (8.0, -30.0), (10.0, 0.0));
+        let path = Path::from_parts(
+            0,
+            skewed(),
+            vec![
+                Vec2::with_control_points(p0.0, p0.1, Some(c0.0), Some(c0.1), None, None),
+                Vec2::with_control_points(p1.0, p1.1, None, None, Some(c1.0), Some(c1.1)),
+            ],
+            vec![PathPrimitive::Bezier {
+                start_idx: 0,
+                end_idx: 1,
+            }],
+        );
+        let truth = sampled(
+            &path.xform,
+            (0..=SAMPLES).map(|i| bezier_point(i as f64 / SAMPLES as f64, p0, c0, c1, p1)),
+        );
+        let exact = get_transformed_bounds(&Shape::Path(path)).unwrap();
+        assert_tight(exact, truth);
+
+        // The control polygon reaches 30 units out; the curve only ~8.7
+        assert!(exact.height() < 30.0, "{:?}", exact);
+    }
+
+    #[test]
+    fn test_flatten_cubic_tolerance() {
+        let (p0, c0, c1, p1) = ((0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+        let mut points = vec![p0];
+        flatten_cubic(p0, c0, c1, p1, 0.01, &mut points);
+        assert_eq!(points.last(), Some(&p1));
+
+        for pair in points.windows(2) {
+            let mid = ((pair[0].0 + pair[1].0) / 2.0, (pair[0].1 + pair[1].1) / 2.0);
+            let nearest = (0..=2000)
+                .map(|i| bezier_point(i as f64 / 2000.0, p0, c0, c1, p1))
+                .map(|p| (p.0 - mid.0).hypot(p.1 - mid.1))
+                .fold(f64::INFINITY, f64::min);
+            assert!(
+                nearest < 0.011,
+                "chord midpoint {:?} off by {}",
+                mid,
+                nearest
+            );
+        }
+    }
 }
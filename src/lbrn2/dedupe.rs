@@ -0,0 +1,605 @@
+//! Duplicate and overlapping line removal
+//!
+//! Drops repeated shapes and bitmaps and trims edges an earlier shape already cuts.
+
+use super::geometry::{BezPath, Leaf, Point, Segment, collect_leaves, distance, splice_leaves};
+use super::path::NumberFormat;
+use super::types::{Bitmap, LightBurnProject, Shape, XForm};
+use std::fmt;
+
+/// Something [`remove_duplicates_with_report`] took out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Removal {
+    /// A shape drawing the same contours as an earlier one on its layer
+    Shape {
+        cut_index: i32,
+        /// Shape type, such as `Rect` or `Path`
+        kind: &'static str,
+        /// Start of the shape's first contour in workspace coordinates
+        at: Point,
+    },
+    /// Part of a segment that an earlier one on its layer already cuts
+    Overlap {
+        cut_index: i32,
+        from: Point,
+        to: Point,
+        /// Length removed in mm
+        length: f64,
+    },
+}
+
+impl fmt::Display for Removal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fmt = NumberFormat {
+            precision: 3,
+            trim_zeros: true,
+        };
+        let point = |p: &Point| format!("({}, {})", fmt.format(p.0), fmt.format(p.1));
+        match self {
+            Removal::Shape {
+                cut_index,
+                kind,
+                at,
+            } => {
+                write!(
+                    f,
+                    "Duplicate {} on layer {} at {}",
+                    kind,
+                    cut_index,
+                    point(at)
+                )
+            }
+            Removal::Overlap {
+                cut_index,
+                from,
+                to,
+                length,
+            } => write!(
+                f,
+                "Overlap on layer {} from {} to {} ({} mm)",
+                cut_index,
+                point(from),
+                point(to),
+                fmt.format(*length)
+            ),
+        }
+    }
+}
+
+fn lerp(a: Point, b: Point, t: f64) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn shape_kind(shape: &Shape) -> &'static str {
+    match shape {
+        Shape::Rect(_) => "Rect",
+        Shape::Ellipse(_) => "Ellipse",
+        Shape::Path(_) => "Path",
+        Shape::Bitmap(_) => "Bitmap",
+        Shape::Text(_) => "Text",
+        Shape::Group(_) => "Group",
+    }
+}
+
+/// Whether two segments run between the same points in the same direction
+fn same_segment(a: &Segment, b: &Segment, tolerance: f64) -> bool {
+    let same = |p: Point, q: Point| distance(p, q) <= tolerance;
+    match (a, b) {
+        (Segment::Line(a0, a1), Segment::Line(b0, b1)) => same(*a0, *b0) && same(*a1, *b1),
+        (Segment::Cubic(a0, a1, a2, a3), Segment::Cubic(b0, b1, b2, b3)) => {
+            same(*a0, *b0) && same(*a1, *b1) && same(*a2, *b2) && same(*a3, *b3)
+        }
+        _ => false,
+    }
+}
+
+/// Whether two contours trace the same curve, in either direction and, when
+/// closed, from any starting segment
+fn same_contour(a: &BezPath, b: &BezPath, tolerance: f64) -> bool {
+    let n = a.segments.len();
+    if a.closed != b.closed || n != b.segments.len() {
+        return false;
+    }
+    let offsets = if a.closed { n } else { 1 };
+    [b.clone(), b.reversed()].iter().any(|b| {
+        (0..offsets).any(|k| {
+            (0..n).all(|i| same_segment(&a.segments[i], &b.segments[(i + k) % n], tolerance))
+        })
+    })
+}
+
+/// Whether two shapes draw the same set of contours
+fn same_contours(a: &[BezPath], b: &[BezPath], tolerance: f64) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut matched = vec![false; b.len()];
+    a.iter().all(|a| {
+        let found = (0..b.len()).find(|&j| !matched[j] && same_contour(a, &b[j], tolerance));
+        if let Some(j) = found {
+            matched[j] = true;
+        }
+        found.is_some()
+    })
+}
+
+/// Bounds of a set of contours, through their control points
+fn contour_bounds<'a>(contours: impl IntoIterator<Item = &'a Segment>) -> (Point, Point) {
+    let mut min = (f64::INFINITY, f64::INFINITY);
+    let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for segment in contours {
+        let points = match *segment {
+            Segment::Line(p0, p1) => vec![p0, p1],
+            Segment::Cubic(p0, c0, c1, p1) => vec![p0, c0, c1, p1],
+        };
+        for (x, y) in points {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+    }
+    (min, max)
+}
+
+/// Whether two leaves are the same image on the same layer, with corners
+/// within `tolerance` mm of each other
+fn same_bitmap(a: &Leaf, b: &Leaf, tolerance: f64) -> bool {
+    let (Shape::Bitmap(p), Shape::Bitmap(q)) = (a.shape, b.shape) else {
+        return false;
+    };
+    let corners = |leaf: &Leaf, bitmap: &Bitmap| {
+        let (x, y) = (bitmap.w / 2.0, bitmap.h / 2.0);
+        [(-x, -y), (x, -y), (-x, y)].map(|(u, v)| leaf.world.transform_point(u, v))
+    };
+    p.cut_index == q.cut_index
+        && p.data == q.data
+        && corners(a, p)
+            .into_iter()
+            .zip(corners(b, q))
+            .all(|(c, d)| distance(c, d) <= tolerance)
+}
+
+/// Indices of leaves drawing the same contours, or the same bitmap, as an
+/// earlier leaf on their layer
+fn duplicate_leaves(leaves: &[Leaf], tolerance: f64) -> Vec<bool> {
+    let bounds: Vec<(Point, Point)> = leaves
+        .iter()
+        .map(|leaf| contour_bounds(leaf.contours.iter().flat_map(|c| &c.segments)))
+        .collect();
+    let mut order: Vec<usize> = (0..leaves.len())
+        .filter(|&i| !leaves[i].contours.is_empty())
+        .collect();
+    order.sort_by(|&i, &j| bounds[i].0.0.total_cmp(&bounds[j].0.0));
+
+    let mut duplicate = vec![false; leaves.len()];
+    for (k, &i) in order.iter().enumerate() {
+        for &j in order[..k].iter().rev() {
+            if bounds[i].0.0 - bounds[j].0.0 > tolerance {
+                break;
+            }
+            let (a, b) = (bounds[i], bounds[j]);
+            let close = distance(a.0, b.0) <= tolerance && distance(a.1, b.1) <= tolerance;
+            if !close || leaves[i].shape.cut_index() != leaves[j].shape.cut_index() {
+                continue;
+            }
+            if same_contours(&leaves[i].contours, &leaves[j].contours, tolerance) {
+                // Of two copies the later one goes
+                duplicate[i.max(j)] = true;
+            }
+        }
+    }
+
+    // Bitmaps have no contours; they are few, so compare every pair
+    let bitmaps: Vec<usize> = (0..leaves.len())
+        .filter(|&i| matches!(leaves[i].shape, Shape::Bitmap(_)))
+        .collect();
+    for (k, &i) in bitmaps.iter().enumerate() {
+        duplicate[i] = bitmaps[..k]
+            .iter()
+            .any(|&j| same_bitmap(&leaves[i], &leaves[j], tolerance));
+    }
+    duplicate
+}
+
+/// Position of a segment: leaf, contour and segment index
+type SegmentRef = (usize, usize, usize);
+
+/// Parameter ranges along a segment
+type Ranges = Vec<(f64, f64)>;
+
+/// Stretches of `s` that the earlier segment `e` already cuts, as
+/// parameters along `s`
+fn covered_by(s: &Segment, e: &Segment, tolerance: f64) -> Option<(f64, f64)> {
+    match (s, e) {
+        (Segment::Line(a, b), Segment::Line(p, q)) => {
+            let d = (b.0 - a.0, b.1 - a.1);
+            let len = d.0.hypot(d.1);
+            if len <= tolerance || distance(*p, *q) <= tolerance {
+                return None;
+            }
+            // Both ends of `e` must lie on the line through `s`
+            let off_line = |r: &Point| ((r.0 - a.0) * d.1 - (r.1 - a.1) * d.0).abs() / len;
+            if off_line(p) > tolerance || off_line(q) > tolerance {
+                return None;
+            }
+            let along = |r: &Point| ((r.0 - a.0) * d.0 + (r.1 - a.1) * d.1) / (len * len);
+            let (t0, t1) = (along(p), along(q));
+            let (t0, t1) = (t0.min(t1).max(0.0), t0.max(t1).min(1.0));
+            ((t1 - t0) * len > tolerance).then_some((t0, t1))
+        }
+        (Segment::Cubic(..), Segment::Cubic(..)) => {
+            let reversed = BezPath {
+                segments: vec![*e],
+                closed: false,
+            }
+            .reversed();
+            (same_segment(s, e, tolerance) || same_segment(s, &reversed.segments[0], tolerance))
+                .then_some((0.0, 1.0))
+        }
+        _ => None,
+    }
+}
+
+/// Parameter ranges of `[0, 1]` outside the union of `covered`, dropping
+/// pieces shorter than `min_piece`
+fn uncovered(mut covered: Ranges, min_piece: f64) -> Ranges {
+    covered.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut kept = Vec::new();
+    let mut at = 0.0;
+    for (t0, t1) in covered {
+        if t0 - at > min_piece {
+            kept.push((at, t0));
+        }
+        at = f64::max(at, t1);
+    }
+    if 1.0 - at > min_piece {
+        kept.push((at, 1.0));
+    }
+    kept
+}
+
+/// Part of a segment between two parameters; Bezier segments are only ever
+/// kept whole
+fn piece(segment: &Segment, t0: f64, t1: f64) -> Segment {
+    match *segment {
+        Segment::Line(a, b) => Segment::Line(
+            if t0 == 0.0 { a } else { lerp(a, b, t0) },
+            if t1 == 1.0 { b } else { lerp(a, b, t1) },
+        ),
+        cubic => cubic,
+    }
+}
+
+/// Open runs left of a contour once only the `kept` parameter ranges of each
+/// segment remain
+fn rebuild(contour: &BezPath, kept: &[Ranges]) -> Vec<BezPath> {
+    let mut runs: Vec<Vec<Segment>> = Vec::new();
+    let mut run = Vec::new();
+    let mut connected = false;
+    for (segment, kept) in contour.segments.iter().zip(kept) {
+        if kept.is_empty() {
+            connected = false;
+        }
+        for &(t0, t1) in kept {
+            let continues = connected && t0 == 0.0;
+            if !continues && !run.is_empty() {
+                runs.push(std::mem::take(&mut run));
+            }
+            run.push(piece(segment, t0, t1));
+            connected = t1 == 1.0;
+        }
+    }
+
+    // A closed contour's last run carries on into its first across the seam
+    let starts_at_seam = kept
+        .first()
+        .and_then(|k| k.first())
+        .is_some_and(|k| k.0 == 0.0);
+    if contour.closed && connected && starts_at_seam && !runs.is_empty() {
+        run.append(&mut runs[0]);
+        runs[0] = run;
+    } else if !run.is_empty() {
+        runs.push(run);
+    }
+    runs.into_iter()
+        .map(|segments| BezPath {
+            segments,
+            closed: false,
+        })
+        .collect()
+}
+
+/// Remove shapes and segments that repeat earlier ones on the same layer
+///
+/// Whole shapes are duplicates when their contours match within `tolerance`
+/// mm, in any direction and from any start. Straight segments are then
+/// trimmed wherever an earlier collinear segment on the layer overlaps them,
+/// and Bezier segments repeating an earlier one are dropped. The first copy
+/// in drawing order is kept. Shapes that lose segments become paths in
+/// their own coordinates; text is only removed as a whole duplicate, and a
+/// bitmap only when the same image is placed over an earlier one.
+pub fn remove_duplicates(project: &LightBurnProject, tolerance: f64) -> LightBurnProject {
+    remove_duplicates_with_report(project, tolerance).0
+}
+
+/// [`remove_duplicates`], also returning what was removed and warnings
+/// about primitives that refer to missing vertices
+pub fn remove_duplicates_with_report(
+    project: &LightBurnProject,
+    tolerance: f64,
+) -> (LightBurnProject, Vec<Removal>, Vec<String>) {
+    let mut log = Vec::new();
+    let mut leaves = Vec::new();
+    collect_leaves(&project.shapes, &XForm::identity(), &mut leaves, &mut log);
+    let mut removals = Vec::new();
+    let mut replacements: Vec<Option<Vec<Shape>>> = vec![None; leaves.len()];
+
+    let duplicate = duplicate_leaves(&leaves, tolerance);
+    for (i, leaf) in leaves.iter().enumerate() {
+        if duplicate[i] {
+            removals.push(Removal::Shape {
+                cut_index: leaf.shape.cut_index(),
+                kind: shape_kind(leaf.shape),
+                at: match leaf.contours.first() {
+                    Some(contour) => contour.start().unwrap_or_default(),
+                    None => leaf.world.transform_point(0.0, 0.0),
+                },
+            });
+            replacements[i] = Some(Vec::new());
+        }
+    }
+
+    // Every segment that may be trimmed, in drawing order
+    let mut segments: Vec<SegmentRef> = Vec::new();
+    for (l, leaf) in leaves.iter().enumerate() {
+        if duplicate[l] || matches!(leaf.shape, Shape::Text(_)) {
+            continue;
+        }
+        for (c, contour) in leaf.contours.iter().enumerate() {
+            segments.extend((0..contour.segments.len()).map(|k| (l, c, k)));
+        }
+    }
+    let segment = |&(l, c, k): &SegmentRef| &leaves[l].contours[c].segments[k];
+    let bounds: Vec<(Point, Point)> = segments
+        .iter()
+        .map(|r| contour_bounds([segment(r)]))
+        .collect();
+
+    // Sweep in order of the left edge for earlier segments overlapping each
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_by(|&i, &j| bounds[i].0.0.total_cmp(&bounds[j].0.0));
+    let mut covers: Vec<Ranges> = vec![Vec::new(); segments.len()];
+    let mut active: Vec<usize> = Vec::new();
+    for &i in &order {
+        active.retain(|&j| bounds[j].1.0 >= bounds[i].0.0 - tolerance);
+        for &j in &active {
+            let (a, b) = (bounds[i], bounds[j]);
+            let cut_index = |r: &SegmentRef| leaves[r.0].shape.cut_index();
+            if a.0.1 > b.1.1 + tolerance
+                || b.0.1 > a.1.1 + tolerance
+                || cut_index(&segments[i]) != cut_index(&segments[j])
+            {
+                continue;
+            }
+            // The later segment in drawing order gives way
+            let (later, earlier) = (i.max(j), i.min(j));
+            let (s, e) = (segment(&segments[later]), segment(&segments[earlier]));
+            if let Some(range) = covered_by(s, e, tolerance) {
+                covers[later].push(range);
+            }
+        }
+        active.push(i);
+    }
+
+    // Parameter ranges kept of every segment, by leaf and contour
+    let mut kept: Vec<Vec<Vec<Ranges>>> = leaves
+        .iter()
+        .map(|leaf| {
+            leaf.contours
+                .iter()
+                .map(|c| vec![vec![(0.0, 1.0)]; c.segments.len()])
+                .collect()
+        })
+        .collect();
+    let mut trimmed = vec![false; leaves.len()];
+    for (i, covered) in covers.into_iter().enumerate() {
+        if covered.is_empty() {
+            continue;
+        }
+        let r = &segments[i];
+        let s = segment(r);
+        let min_piece = match *s {
+            Segment::Line(a, b) => tolerance / distance(a, b).max(tolerance),
+            Segment::Cubic(..) => 1.0,
+        };
+        let left = uncovered(covered, min_piece);
+
+        // Report the stretches between the pieces that are left
+        let mut at = 0.0;
+        for &(t0, t1) in left.iter().chain([&(1.0, 1.0)]) {
+            if t0 > at {
+                let removed = piece(s, at, t0);
+                removals.push(Removal::Overlap {
+                    cut_index: leaves[r.0].shape.cut_index(),
+                    from: removed.start(),
+                    to: removed.end(),
+                    length: removed.length(),
+                });
+            }
+            at = t1;
+        }
+        kept[r.0][r.1][r.2] = left;
+        trimmed[r.0] = true;
+    }
+
+    for (l, leaf) in leaves.iter().enumerate() {
+        if !trimmed[l] {
+            continue;
+        }
+        let contours: Vec<BezPath> = leaf
+            .contours
+            .iter()
+            .zip(&kept[l])
+            .flat_map(|(contour, kept)| {
+                if kept.iter().all(|k| k == &[(0.0, 1.0)]) {
+                    vec![contour.clone()]
+                } else {
+                    rebuild(contour, kept)
+                }
+            })
+            .collect();
+        replacements[l] = if contours.is_empty() {
+            Some(Vec::new())
+        } else {
+            leaf.path(&contours).map(|shape| vec![shape])
+        };
+    }
+
+    let mut project = project.clone();
+    splice_leaves(&mut project.shapes, &mut replacements.into_iter());
+    (project, removals, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::{contours, square};
+    use crate::lbrn2::types::Ellipse;
+
+    #[test]
+    fn test_duplicate_shapes_are_removed() {
+        let circle = Shape::Ellipse(Ellipse {
+            cut_index: 0,
+            xform: XForm::identity(),
+            rx: 5.0,
+            ry: 5.0,
+        });
+        let project = LightBurnProject {
+            shapes: vec![
+                circle.clone(),
+                square(20.0, 0.0, 4.0, 0),
+                circle,
+                // The same square on another layer stays
+                square(20.0, 0.0, 4.0, 1),
+                square(20.0, 0.0, 4.0, 0),
+            ],
+            ..Default::default()
+        };
+
+        let (deduped, removals, _) = remove_duplicates_with_report(&project, 0.01);
+        let kept = [0, 1, 3].map(|i| project.shapes[i].clone());
+        assert_eq!(deduped.shapes, kept);
+        assert_eq!(removals.len(), 2);
+        assert!(matches!(
+            removals[0],
+            Removal::Shape {
+                kind: "Ellipse",
+                ..
+            }
+        ));
+        assert_eq!(
+            removals[1].to_string(),
+            "Duplicate Rect on layer 0 at (18, -2)"
+        );
+    }
+
+    #[test]
+    fn test_repeated_bitmaps_are_removed() {
+        let bitmap = |e: f64, data: &str| {
+            Shape::Bitmap(Bitmap {
+                cut_index: 0,
+                xform: XForm {
+                    e,
+                    f: 3.0,
+                    ..XForm::identity()
+                },
+                w: 10.0,
+                h: 8.0,
+                data: data.to_string(),
+            })
+        };
+        let project = LightBurnProject {
+            shapes: vec![
+                bitmap(0.0, "AAAA"),
+                bitmap(0.001, "AAAA"),
+                // Moved, or a different image, stays
+                bitmap(5.0, "AAAA"),
+                bitmap(0.0, "BBBB"),
+            ],
+            ..Default::default()
+        };
+
+        let (deduped, removals, _) = remove_duplicates_with_report(&project, 0.01);
+        let kept = [0, 2, 3].map(|i| project.shapes[i].clone());
+        assert_eq!(deduped.shapes, kept);
+        assert_eq!(
+            removals.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["Duplicate Bitmap on layer 0 at (0.001, 3)"]
+        );
+    }
+
+    #[test]
+    fn test_shared_edges_are_cut_once() {
+        // Two squares nested edge to edge, and a third whose edge only
+        // partly overlaps the second
+        let project = LightBurnProject {
+            shapes: vec![
+                square(0.0, 0.0, 10.0, 0),
+                square(10.0, 0.0, 10.0, 0),
+                square(20.0, 5.0, 10.0, 0),
+            ],
+            ..Default::default()
+        };
+
+        let (deduped, removals, _) = remove_duplicates_with_report(&project, 0.01);
+        assert_eq!(deduped.shapes[0], project.shapes[0]);
+        assert_eq!(removals.len(), 2);
+        let Removal::Overlap {
+            from, to, length, ..
+        } = removals[0]
+        else {
+            panic!("expected an overlap");
+        };
+        assert_eq!((from, to), ((5.0, 5.0), (5.0, -5.0)));
+        assert!((length - 10.0).abs() < 1e-9);
+        assert!(
+            (contours(&deduped.shapes)
+                .iter()
+                .map(BezPath::length)
+                .sum::<f64>()
+                - (120.0 - 10.0 - 5.0))
+                .abs()
+                < 1e-9
+        );
+
+        // The second square opens into a single run around its other sides
+        let second = deduped.shapes[1].to_bezpath(&XForm::identity());
+        assert_eq!(second.len(), 1);
+        assert!(!second[0].closed);
+        assert_eq!(second[0].segments.len(), 3);
+        assert_eq!(second[0].start(), Some((5.0, -5.0)));
+        assert_eq!(second[0].end(), Some((5.0, 5.0)));
+
+        // The third keeps the part of its shared edge past the second square
+        let third = deduped.shapes[2].to_bezpath(&XForm::identity());
+        assert_eq!(third.len(), 1);
+        assert_eq!(third[0].start(), Some((15.0, 0.0)));
+        assert!((third[0].length() - 35.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pieces_join_across_the_seam() {
+        // The shared edge is the second side of the left square, so what is
+        // left of it runs on from its last side into its first
+        let project = LightBurnProject {
+            shapes: vec![square(0.0, 0.0, 10.0, 0), square(-10.0, 0.0, 10.0, 0)],
+            ..Default::default()
+        };
+
+        let deduped = remove_duplicates(&project, 0.01);
+        let left = deduped.shapes[1].to_bezpath(&XForm::identity());
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].segments.len(), 3);
+        assert_eq!(left[0].start(), Some((-5.0, 5.0)));
+        assert_eq!(left[0].end(), Some((-5.0, -5.0)));
+    }
+}
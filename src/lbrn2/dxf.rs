@@ -0,0 +1,963 @@
+//! DXF export
+//!
+//! Writes an AutoCAD 2000 ASCII DXF in millimetres with one layer per cut setting.
+
+use super::bounds::get_transformed_bounds;
+use super::geometry::{BezPath, Segment, shape_bezpaths};
+use super::path::NumberFormat;
+use super::style::get_cut_setting_color;
+use super::svg::flatten_groups;
+use super::types::{Ellipse, LightBurnProject, Shape, XForm};
+use std::collections::BTreeMap;
+
+/// Standard AutoCAD Color Index entries 1..=9 as RGB
+const ACI_COLORS: [(u8, (u8, u8, u8)); 9] = [
+    (1, (255, 0, 0)),
+    (2, (255, 255, 0)),
+    (3, (0, 255, 0)),
+    (4, (0, 255, 255)),
+    (5, (0, 0, 255)),
+    (6, (255, 0, 255)),
+    (7, (255, 255, 255)),
+    (8, (128, 128, 128)),
+    (9, (192, 192, 192)),
+];
+
+/// Options for DXF export
+#[derive(Debug, Clone, PartialEq)]
+pub struct DxfExportOptions {
+    /// Write Bezier curves as SPLINE entities; when false they are flattened
+    /// into LWPOLYLINEs (default: true)
+    pub splines: bool,
+    /// Maximum distance between a curve and its flattened polyline in mm,
+    /// used when `splines` is false (default: 0.01)
+    pub tolerance: f64,
+}
+
+impl Default for DxfExportOptions {
+    fn default() -> Self {
+        Self {
+            splines: true,
+            tolerance: 0.01,
+        }
+    }
+}
+
+/// Parse `#RRGGBB` into its components
+fn parse_hex_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Nearest standard ACI color; black maps to 7, which CAD programs draw in
+/// the foreground color
+pub(crate) fn rgb_to_aci(rgb: (u8, u8, u8)) -> u8 {
+    if rgb == (0, 0, 0) {
+        return 7;
+    }
+    let dist = |c: (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(rgb.0, c.0) + d(rgb.1, c.1) + d(rgb.2, c.2)
+    };
+    ACI_COLORS
+        .iter()
+        .min_by_key(|(_, c)| dist(*c))
+        .map(|(aci, _)| *aci)
+        .unwrap_or(7)
+}
+
+/// RGB value of a standard ACI color (1..=9); other indices are not known
+pub(crate) fn aci_to_rgb(aci: i32) -> Option<(u8, u8, u8)> {
+    ACI_COLORS
+        .iter()
+        .find(|(i, _)| *i as i32 == aci)
+        .map(|(_, c)| *c)
+}
+
+/// Principal axes of a transformed ellipse: center, semi-major axis vector
+/// and minor/major ratio
+fn ellipse_axes(ellipse: &Ellipse, xform: &XForm) -> ((f64, f64), (f64, f64), f64) {
+    let center = xform.transform_point(0.0, 0.0);
+    // Points are center + u cos t + v sin t; u and v are conjugate
+    // semi-diameters, rotate the parameter to make them perpendicular
+    let u = (xform.a * ellipse.rx, xform.b * ellipse.rx);
+    let v = (xform.c * ellipse.ry, xform.d * ellipse.ry);
+    let uv = u.0 * v.0 + u.1 * v.1;
+    let uu = u.0 * u.0 + u.1 * u.1;
+    let vv = v.0 * v.0 + v.1 * v.1;
+    let t = 0.5 * (2.0 * uv).atan2(uu - vv);
+    let (cos, sin) = (t.cos(), t.sin());
+    let p = (u.0 * cos + v.0 * sin, u.1 * cos + v.1 * sin);
+    let q = (v.0 * cos - u.0 * sin, v.1 * cos - u.1 * sin);
+
+    let (lp, lq) = (p.0.hypot(p.1), q.0.hypot(q.1));
+    let (major, lmajor, lminor) = if lp >= lq { (p, lp, lq) } else { (q, lq, lp) };
+    let ratio = if lmajor > 0.0 { lminor / lmajor } else { 1.0 };
+    (center, major, ratio)
+}
+
+struct DxfWriter<'a> {
+    options: &'a DxfExportOptions,
+    fmt: NumberFormat,
+    out: String,
+    next_handle: u32,
+    /// Handle of the *Model_Space block record, the owner of every entity
+    model_space: String,
+}
+
+impl DxfWriter<'_> {
+    fn pair(&mut self, code: i32, value: &str) {
+        self.out.push_str(&format!("{:>3}\n{}\n", code, value));
+    }
+
+    fn num(&mut self, code: i32, value: f64) {
+        let value = self.fmt.format(value);
+        self.pair(code, &value);
+    }
+
+    fn point(&mut self, code: i32, (x, y): (f64, f64)) {
+        self.num(code, x);
+        self.num(code + 10, y);
+        self.num(code + 20, 0.0);
+    }
+
+    /// Allocate a handle without writing it, for objects referenced before
+    /// they are written
+    fn reserve(&mut self) -> String {
+        let handle = format!("{:X}", self.next_handle);
+        self.next_handle += 1;
+        handle
+    }
+
+    fn handle(&mut self) -> String {
+        let handle = self.reserve();
+        self.pair(5, &handle);
+        handle
+    }
+
+    /// Symbol table header; returns the table handle, which owns its records
+    fn table(&mut self, name: &str, count: usize) -> String {
+        self.pair(0, "TABLE");
+        self.pair(2, name);
+        let handle = self.handle();
+        self.pair(330, "0");
+        self.pair(100, "AcDbSymbolTable");
+        self.pair(70, &count.to_string());
+        handle
+    }
+
+    /// Symbol table record up to and including its name and flags; returns
+    /// the record handle
+    fn table_record(&mut self, kind: &str, owner: &str, subclass: &str, name: &str) -> String {
+        self.pair(0, kind);
+        let handle = self.handle();
+        self.pair(330, owner);
+        self.pair(100, "AcDbSymbolTableRecord");
+        self.pair(100, subclass);
+        self.pair(2, name);
+        self.pair(70, "0");
+        handle
+    }
+
+    /// Dictionary header owned by `owner` with its `(name, handle)` entries
+    fn dictionary(&mut self, handle: &str, owner: &str, entries: &[(&str, &str)]) {
+        self.pair(0, "DICTIONARY");
+        self.pair(5, handle);
+        self.pair(330, owner);
+        self.pair(100, "AcDbDictionary");
+        self.pair(281, "1");
+        for (name, entry) in entries {
+            self.pair(3, name);
+            self.pair(350, entry);
+        }
+    }
+
+    /// Entity type, handle, owner, layer and subclass marker
+    fn entity(&mut self, kind: &str, layer: &str, subclass: &str) {
+        self.pair(0, kind);
+        self.handle();
+        let owner = self.model_space.clone();
+        self.pair(330, &owner);
+        self.pair(100, "AcDbEntity");
+        self.pair(8, layer);
+        self.pair(100, subclass);
+    }
+
+    fn polyline(&mut self, layer: &str, points: &[(f64, f64)], closed: bool) {
+        let mut points = points.to_vec();
+        points.dedup();
+        if closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() < 2 {
+            return;
+        }
+
+        if points.len() == 2 && !closed {
+            self.entity("LINE", layer, "AcDbLine");
+            self.point(10, points[0]);
+            self.point(11, points[1]);
+            return;
+        }
+
+        self.entity("LWPOLYLINE", layer, "AcDbPolyline");
+        self.pair(90, &points.len().to_string());
+        self.pair(70, if closed { "1" } else { "0" });
+        for (x, y) in points {
+            self.num(10, x);
+            self.num(20, y);
+        }
+    }
+
+    /// Cubic B-spline with clamped knots that reproduces the segments
+    /// exactly; lines become degenerate cubics
+    fn spline(&mut self, layer: &str, segments: &[Segment], closed: bool) {
+        let mut controls = Vec::new();
+        for (i, segment) in segments.iter().enumerate() {
+            let (p0, c0, c1, p1) = match *segment {
+                Segment::Cubic(p0, c0, c1, p1) => (p0, c0, c1, p1),
+                Segment::Line(p0, p1) => {
+                    let lerp = |t: f64| (p0.0 + (p1.0 - p0.0) * t, p0.1 + (p1.1 - p0.1) * t);
+                    (p0, lerp(1.0 / 3.0), lerp(2.0 / 3.0), p1)
+                }
+            };
+            if i == 0 {
+                controls.push(p0);
+            }
+            controls.extend([c0, c1, p1]);
+        }
+
+        let n = segments.len();
+        let mut knots = vec![0.0; 4];
+        for k in 1..n {
+            knots.extend([k as f64; 3]);
+        }
+        knots.extend([n as f64; 4]);
+
+        self.entity("SPLINE", layer, "AcDbSpline");
+        // 8 = planar, 1 = closed
+        self.pair(70, if closed { "9" } else { "8" });
+        self.pair(71, "3");
+        self.pair(72, &knots.len().to_string());
+        self.pair(73, &controls.len().to_string());
+        self.pair(74, "0");
+        for k in knots {
+            self.num(40, k);
+        }
+        for p in controls {
+            self.point(10, p);
+        }
+    }
+
+    fn chain(&mut self, layer: &str, chain: &BezPath) {
+        let has_curves = chain
+            .segments
+            .iter()
+            .any(|s| matches!(s, Segment::Cubic(..)));
+
+        if has_curves && self.options.splines {
+            self.spline(layer, &chain.segments, chain.closed);
+            return;
+        }
+
+        let polyline = chain.flatten(self.options.tolerance);
+        self.polyline(layer, &polyline.points, chain.closed);
+    }
+
+    fn ellipse(&mut self, layer: &str, ellipse: &Ellipse) {
+        let (center, major, ratio) = ellipse_axes(ellipse, &ellipse.xform);
+        if (ratio - 1.0).abs() < 1e-9 {
+            self.entity("CIRCLE", layer, "AcDbCircle");
+            self.point(10, center);
+            self.num(40, major.0.hypot(major.1));
+            return;
+        }
+
+        self.entity("ELLIPSE", layer, "AcDbEllipse");
+        self.point(10, center);
+        self.point(11, major);
+        self.num(40, ratio);
+        self.num(41, 0.0);
+        self.num(42, std::f64::consts::TAU);
+    }
+
+    fn shape(&mut self, layer: &str, shape: &Shape, log: &mut Vec<String>) {
+        match shape {
+            Shape::Ellipse(ellipse) => self.ellipse(layer, ellipse),
+            Shape::Bitmap(_) => {
+                log.push("Bitmap skipped: DXF export only writes vector shapes".to_string());
+            }
+            Shape::Text(text) if text.backup_path.is_none() => log.push(format!(
+                "Text \"{}\" skipped: no BackupPath outline to export",
+                text.text
+            )),
+            _ => {
+                for chain in shape_bezpaths(shape, &XForm::identity(), log) {
+                    self.chain(layer, &chain);
+                }
+            }
+        }
+    }
+}
+
+/// Characters AutoCAD does not allow in layer names
+fn sanitize_layer_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | '/' | '\\' | '"' | ':' | ';' | '?' | '*' | '|' | '=' | '`' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Layer name for every cut index in use: the cut setting name when it is
+/// set and unique, `C00` style otherwise
+fn layer_names(project: &LightBurnProject, leaves: &[Shape]) -> BTreeMap<i32, String> {
+    let mut names: BTreeMap<i32, String> = BTreeMap::new();
+    let mut taken = vec!["0".to_string()];
+
+    let indices = project
+        .cut_settings
+        .iter()
+        .map(|cs| cs.index)
+        .chain(leaves.iter().map(Shape::cut_index));
+    for index in indices {
+        if names.contains_key(&index) {
+            continue;
+        }
+        let name = project
+            .cut_settings
+            .iter()
+            .find(|cs| cs.index == index)
+            .map(|cs| sanitize_layer_name(&cs.name))
+            .filter(|n| !n.is_empty() && !taken.iter().any(|t| t.eq_ignore_ascii_case(n)))
+            .unwrap_or_else(|| format!("C{:02}", index));
+        taken.push(name.clone());
+        names.insert(index, name);
+    }
+    names
+}
+
+/// Convert a LightBurn project to DXF with default options
+pub fn lbrn2_to_dxf(project: &LightBurnProject) -> String {
+    lbrn2_to_dxf_with_options(project, &DxfExportOptions::default()).0
+}
+
+/// Convert a LightBurn project to DXF, also returning warnings about content
+/// that could not be converted
+pub fn lbrn2_to_dxf_with_options(
+    project: &LightBurnProject,
+    options: &DxfExportOptions,
+) -> (String, Vec<String>) {
+    let mirrored = project.with_mirror_applied();
+    let project = mirrored.as_ref();
+    let mut log = Vec::new();
+    let mut leaves = Vec::new();
+    flatten_groups(&project.shapes, None, &mut leaves);
+    let names = layer_names(project, &leaves);
+
+    let mut w = DxfWriter {
+        options,
+        fmt: NumberFormat {
+            precision: 6,
+            trim_zeros: true,
+        },
+        out: String::new(),
+        next_handle: 0x100,
+        model_space: String::new(),
+    };
+
+    // Bounds come back in SVG space (Y down)
+    let extents = project
+        .shapes
+        .iter()
+        .filter_map(get_transformed_bounds)
+        .reduce(|mut acc, b| {
+            acc.expand(&b);
+            acc
+        });
+    let (min, max) = match extents {
+        Some(b) => ((b.min_x, -b.max_y), (b.max_x, -b.min_y)),
+        None => ((0.0, 0.0), (0.0, 0.0)),
+    };
+
+    // Objects referenced before they are written
+    let root = w.reserve();
+    let groups = w.reserve();
+    let layouts = w.reserve();
+    let plot_styles = w.reserve();
+    let normal_style = w.reserve();
+    let dimstyle = w.reserve();
+    let model_layout = w.reserve();
+    let paper_layout = w.reserve();
+
+    w.pair(0, "SECTION");
+    w.pair(2, "CLASSES");
+    for (name, class) in [
+        ("ACDBDICTIONARYWDFLT", "AcDbDictionaryWithDefault"),
+        ("ACDBPLACEHOLDER", "AcDbPlaceHolder"),
+        ("LAYOUT", "AcDbLayout"),
+    ] {
+        w.pair(0, "CLASS");
+        w.pair(1, name);
+        w.pair(2, class);
+        w.pair(3, "ObjectDBX Classes");
+        w.pair(90, "0");
+        w.pair(280, "0");
+        w.pair(281, "0");
+    }
+    w.pair(0, "ENDSEC");
+
+    w.pair(0, "SECTION");
+    w.pair(2, "TABLES");
+
+    // The active viewport is zoomed to the drawing
+    let table = w.table("VPORT", 1);
+    w.table_record("VPORT", &table, "AcDbViewportTableRecord", "*ACTIVE");
+    w.num(10, 0.0);
+    w.num(20, 0.0);
+    w.num(11, 1.0);
+    w.num(21, 1.0);
+    w.num(12, (min.0 + max.0) / 2.0);
+    w.num(22, (min.1 + max.1) / 2.0);
+    for (code, value) in [
+        (13, "0"),
+        (23, "0"),
+        (14, "10"),
+        (24, "10"),
+        (15, "10"),
+        (25, "10"),
+        (16, "0"),
+        (26, "0"),
+        (36, "1"),
+        (17, "0"),
+        (27, "0"),
+        (37, "0"),
+    ] {
+        w.pair(code, value);
+    }
+    w.num(40, (max.0 - min.0).max(max.1 - min.1).max(1.0) * 1.1);
+    for (code, value) in [
+        (41, "1"),
+        (42, "50"),
+        (43, "0"),
+        (44, "0"),
+        (50, "0"),
+        (51, "0"),
+        (71, "0"),
+        (72, "100"),
+        (73, "1"),
+        (74, "3"),
+        (75, "0"),
+        (76, "0"),
+        (77, "0"),
+        (78, "0"),
+    ] {
+        w.pair(code, value);
+    }
+    w.pair(0, "ENDTAB");
+
+    let table = w.table("LTYPE", 3);
+    for (name, description) in [
+        ("ByBlock", ""),
+        ("ByLayer", ""),
+        ("Continuous", "Solid line"),
+    ] {
+        w.table_record("LTYPE", &table, "AcDbLinetypeTableRecord", name);
+        w.pair(3, description);
+        w.pair(72, "65");
+        w.pair(73, "0");
+        w.pair(40, "0");
+    }
+    w.pair(0, "ENDTAB");
+
+    let layers = std::iter::once(("0".to_string(), (0, 0, 0)))
+        .chain(names.iter().map(|(index, name)| {
+            let color = get_cut_setting_color(*index, Some(&project.cut_settings));
+            (name.clone(), parse_hex_color(&color).unwrap_or((0, 0, 0)))
+        }))
+        .collect::<Vec<_>>();
+    let table = w.table("LAYER", layers.len());
+    for (name, rgb) in layers {
+        w.table_record("LAYER", &table, "AcDbLayerTableRecord", &name);
+        w.pair(62, &rgb_to_aci(rgb).to_string());
+        w.pair(
+            420,
+            &((rgb.0 as u32) << 16 | (rgb.1 as u32) << 8 | rgb.2 as u32).to_string(),
+        );
+        w.pair(6, "Continuous");
+        w.pair(370, "-3");
+        w.pair(390, &normal_style);
+    }
+    w.pair(0, "ENDTAB");
+
+    let table = w.table("STYLE", 1);
+    w.table_record("STYLE", &table, "AcDbTextStyleTableRecord", "Standard");
+    for (code, value) in [
+        (40, "0"),
+        (41, "1"),
+        (50, "0"),
+        (71, "0"),
+        (42, "2.5"),
+        (3, "txt"),
+        (4, ""),
+    ] {
+        w.pair(code, value);
+    }
+    w.pair(0, "ENDTAB");
+
+    w.table("VIEW", 0);
+    w.pair(0, "ENDTAB");
+    w.table("UCS", 0);
+    w.pair(0, "ENDTAB");
+
+    let table = w.table("APPID", 1);
+    w.table_record("APPID", &table, "AcDbRegAppTableRecord", "ACAD");
+    w.pair(0, "ENDTAB");
+
+    // DIMSTYLE records carry their handle under code 105
+    let table = w.table("DIMSTYLE", 1);
+    w.pair(100, "AcDbDimStyleTable");
+    w.pair(71, "1");
+    w.pair(340, &dimstyle);
+    w.pair(0, "DIMSTYLE");
+    w.pair(105, &dimstyle);
+    w.pair(330, &table);
+    w.pair(100, "AcDbSymbolTableRecord");
+    w.pair(100, "AcDbDimStyleTableRecord");
+    w.pair(2, "Standard");
+    w.pair(70, "0");
+    w.pair(0, "ENDTAB");
+
+    let table = w.table("BLOCK_RECORD", 2);
+    let model_space = w.table_record(
+        "BLOCK_RECORD",
+        &table,
+        "AcDbBlockTableRecord",
+        "*Model_Space",
+    );
+    w.pair(340, &model_layout);
+    let paper_space = w.table_record(
+        "BLOCK_RECORD",
+        &table,
+        "AcDbBlockTableRecord",
+        "*Paper_Space",
+    );
+    w.pair(340, &paper_layout);
+    w.pair(0, "ENDTAB");
+    w.pair(0, "ENDSEC");
+
+    w.pair(0, "SECTION");
+    w.pair(2, "BLOCKS");
+    for (name, record, paper) in [
+        ("*Model_Space", &model_space, false),
+        ("*Paper_Space", &paper_space, true),
+    ] {
+        w.pair(0, "BLOCK");
+        w.handle();
+        w.pair(330, record);
+        w.pair(100, "AcDbEntity");
+        if paper {
+            w.pair(67, "1");
+        }
+        w.pair(8, "0");
+        w.pair(100, "AcDbBlockBegin");
+        w.pair(2, name);
+        w.pair(70, "0");
+        w.point(10, (0.0, 0.0));
+        w.pair(3, name);
+        w.pair(1, "");
+        w.pair(0, "ENDBLK");
+        w.handle();
+        w.pair(330, record);
+        w.pair(100, "AcDbEntity");
+        if paper {
+            w.pair(67, "1");
+        }
+        w.pair(8, "0");
+        w.pair(100, "AcDbBlockEnd");
+    }
+    w.pair(0, "ENDSEC");
+
+    w.model_space = model_space.clone();
+    w.pair(0, "SECTION");
+    w.pair(2, "ENTITIES");
+    for shape in &leaves {
+        let layer = names[&shape.cut_index()].clone();
+        w.shape(&layer, shape, &mut log);
+    }
+    w.pair(0, "ENDSEC");
+
+    w.pair(0, "SECTION");
+    w.pair(2, "OBJECTS");
+    w.dictionary(
+        &root,
+        "0",
+        &[
+            ("ACAD_GROUP", &groups),
+            ("ACAD_LAYOUT", &layouts),
+            ("ACAD_PLOTSTYLENAME", &plot_styles),
+        ],
+    );
+    w.dictionary(&groups, &root, &[]);
+    w.dictionary(
+        &layouts,
+        &root,
+        &[("Layout1", &paper_layout), ("Model", &model_layout)],
+    );
+    // Layers point at the default plot style
+    w.pair(0, "ACDBDICTIONARYWDFLT");
+    w.pair(5, &plot_styles);
+    w.pair(330, &root);
+    w.pair(100, "AcDbDictionary");
+    w.pair(281, "1");
+    w.pair(3, "Normal");
+    w.pair(350, &normal_style);
+    w.pair(100, "AcDbDictionaryWithDefault");
+    w.pair(340, &normal_style);
+    w.pair(0, "ACDBPLACEHOLDER");
+    w.pair(5, &normal_style);
+    w.pair(330, &plot_styles);
+    for (name, handle, record, tab, limits) in [
+        ("Model", &model_layout, &model_space, "0", (min, max)),
+        (
+            "Layout1",
+            &paper_layout,
+            &paper_space,
+            "1",
+            ((0.0, 0.0), (420.0, 297.0)),
+        ),
+    ] {
+        w.pair(0, "LAYOUT");
+        w.pair(5, handle);
+        w.pair(330, &layouts);
+        w.pair(100, "AcDbPlotSettings");
+        for (code, value) in [
+            (1, ""),
+            (2, "none_device"),
+            (4, ""),
+            (6, ""),
+            (40, "0"),
+            (41, "0"),
+            (42, "0"),
+            (43, "0"),
+            (44, "0"),
+            (45, "0"),
+            (46, "0"),
+            (47, "0"),
+            (48, "0"),
+            (49, "0"),
+            (140, "0"),
+            (141, "0"),
+            (142, "1"),
+            (143, "1"),
+            (70, "688"),
+            (72, "1"),
+            (73, "0"),
+            (74, "5"),
+            (7, ""),
+            (75, "16"),
+            (147, "1"),
+            (148, "0"),
+            (149, "0"),
+        ] {
+            w.pair(code, value);
+        }
+        w.pair(100, "AcDbLayout");
+        w.pair(1, name);
+        w.pair(70, "1");
+        w.pair(71, tab);
+        w.num(10, limits.0.0);
+        w.num(20, limits.0.1);
+        w.num(11, limits.1.0);
+        w.num(21, limits.1.1);
+        w.point(12, (0.0, 0.0));
+        w.point(14, min);
+        w.point(15, max);
+        w.pair(146, "0");
+        w.point(13, (0.0, 0.0));
+        w.point(16, (1.0, 0.0));
+        w.point(17, (0.0, 1.0));
+        w.pair(76, "0");
+        w.pair(330, record);
+    }
+    w.pair(0, "ENDSEC");
+    w.pair(0, "EOF");
+
+    // The header goes first but $HANDSEED must exceed every handle used
+    let body = std::mem::take(&mut w.out);
+    let seed = w.reserve();
+    w.pair(0, "SECTION");
+    w.pair(2, "HEADER");
+    w.pair(9, "$ACADVER");
+    w.pair(1, "AC1015");
+    w.pair(9, "$DWGCODEPAGE");
+    w.pair(3, "ANSI_1252");
+    w.pair(9, "$HANDSEED");
+    w.pair(5, &seed);
+    w.pair(9, "$INSUNITS");
+    w.pair(70, "4");
+    w.pair(9, "$MEASUREMENT");
+    w.pair(70, "1");
+    w.pair(9, "$EXTMIN");
+    w.point(10, min);
+    w.pair(9, "$EXTMAX");
+    w.point(10, max);
+    w.pair(0, "ENDSEC");
+    w.out.push_str(&body);
+
+    log.dedup();
+    (w.out, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::types::{CutSetting, Path, PathPrimitive, Rect, Vec2};
+
+    /// Entity type names in the ENTITIES section
+    fn entity_kinds(dxf: &str) -> Vec<String> {
+        let lines: Vec<&str> = dxf.lines().map(str::trim).collect();
+        let start = lines.iter().position(|l| *l == "ENTITIES").unwrap();
+        lines[start - 1..]
+            .chunks(2)
+            .skip(1)
+            .take_while(|pair| pair != &["0", "ENDSEC"])
+            .filter(|pair| pair[0] == "0")
+            .map(|pair| pair[1].to_string())
+            .collect()
+    }
+
+    fn ellipse(rx: f64, ry: f64, xform: XForm) -> Ellipse {
+        Ellipse {
+            cut_index: 0,
+            xform,
+            rx,
+            ry,
+        }
+    }
+
+    #[test]
+    fn test_ellipse_axes() {
+        // Circle under a rotation stays a circle
+        let (cos, sin) = (0.6, 0.8);
+        let rot = XForm {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            e: 3.0,
+            f: 4.0,
+        };
+        let (center, major, ratio) = ellipse_axes(&ellipse(5.0, 5.0, rot), &rot);
+        assert_eq!(center, (3.0, 4.0));
+        assert!((major.0.hypot(major.1) - 5.0).abs() < 1e-9);
+        assert!((ratio - 1.0).abs() < 1e-9);
+
+        // Tall ellipse: the major axis is along Y
+        let id = XForm::identity();
+        let (_, major, ratio) = ellipse_axes(&ellipse(2.0, 8.0, id), &id);
+        assert!(major.0.abs() < 1e-9 && (major.1.abs() - 8.0).abs() < 1e-9);
+        assert!((ratio - 0.25).abs() < 1e-9);
+
+        // A sheared circle has the same axes as its dense samples
+        let shear = XForm {
+            c: 1.0,
+            ..XForm::identity()
+        };
+        let (_, major, ratio) = ellipse_axes(&ellipse(1.0, 1.0, shear), &shear);
+        let len = major.0.hypot(major.1);
+        let samples_max = (0..3600)
+            .map(|i| {
+                let t = i as f64 / 3600.0 * std::f64::consts::TAU;
+                let (x, y) = shear.transform_point(t.cos(), t.sin());
+                x.hypot(y)
+            })
+            .fold(0.0, f64::max);
+        assert!((len - samples_max).abs() < 1e-4);
+        // Area is preserved by a shear: pi * a * b == pi
+        assert!((len * len * ratio - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_layers_and_entities() {
+        let project = LightBurnProject {
+            app_version: String::new(),
+            format_version: String::new(),
+            cut_settings: vec![
+                CutSetting {
+                    index: 0,
+                    name: "Cut: outer".to_string(),
+                    color: Some("#FF0000".to_string()),
+                    ..Default::default()
+                },
+                CutSetting {
+                    index: 1,
+                    name: "cut: OUTER".to_string(),
+                    ..Default::default()
+                },
+            ],
+            shapes: vec![
+                Shape::Rect(Rect {
+                    cut_index: 0,
+                    xform: XForm::identity(),
+                    w: 10.0,
+                    h: 10.0,
+                    cr: 0.0,
+                }),
+                Shape::Ellipse(ellipse(5.0, 5.0, XForm::identity())),
+                Shape::Ellipse(Ellipse {
+                    cut_index: 1,
+                    ..ellipse(5.0, 2.0, XForm::identity())
+                }),
+                Shape::Path(Path {
+                    cut_index: 2,
+                    xform: XForm::identity(),
+                    vert_list: "".into(),
+                    prim_list: "".into(),
+                    parsed_verts: vec![
+                        Vec2::with_control_points(0.0, 0.0, Some(0.0), Some(5.0), None, None),
+                        Vec2::with_control_points(10.0, 0.0, None, None, Some(10.0), Some(5.0)),
+                        Vec2::new(20.0, 0.0),
+                    ]
+                    .into(),
+                    parsed_primitives: vec![
+                        PathPrimitive::Bezier {
+                            start_idx: 0,
+                            end_idx: 1,
+                        },
+                        PathPrimitive::Line {
+                            start_idx: 1,
+                            end_idx: 2,
+                        },
+                    ]
+                    .into(),
+                    vert_id: None,
+                    prim_id: None,
+                }),
+            ],
+            ..Default::default()
+        };
+
+        let dxf = lbrn2_to_dxf(&project);
+        assert_eq!(
+            entity_kinds(&dxf),
+            ["LWPOLYLINE", "CIRCLE", "ELLIPSE", "SPLINE"]
+        );
+        assert!(dxf.contains("  2\nCut_ outer\n 70\n0\n 62\n1\n420\n16711680\n"));
+        // The duplicate name falls back to the generated one
+        assert!(dxf.contains("  2\nC01\n"));
+        assert!(dxf.contains("  2\nC02\n"));
+        assert!(dxf.contains("$EXTMIN\n 10\n-5\n 20\n-5\n"));
+        assert!(dxf.contains("$EXTMAX\n 10\n20\n 20\n5\n"));
+
+        let (flat, _) = lbrn2_to_dxf_with_options(
+            &project,
+            &DxfExportOptions {
+                splines: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            entity_kinds(&flat),
+            ["LWPOLYLINE", "CIRCLE", "ELLIPSE", "LWPOLYLINE"]
+        );
+    }
+
+    #[test]
+    fn test_r2000_structure() {
+        let project = LightBurnProject {
+            shapes: vec![Shape::Ellipse(ellipse(5.0, 2.0, XForm::identity()))],
+            ..Default::default()
+        };
+        let dxf = lbrn2_to_dxf(&project);
+        let lines: Vec<&str> = dxf.lines().collect();
+        let pairs: Vec<(&str, &str)> = lines
+            .chunks(2)
+            .map(|pair| (pair[0].trim(), pair[1]))
+            .collect();
+        let after = |code: &str, value: &str| {
+            pairs
+                .windows(2)
+                .filter(|w| w[0] == (code, value))
+                .map(|w| w[1].1)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            after("0", "SECTION"),
+            [
+                "HEADER", "CLASSES", "TABLES", "BLOCKS", "ENTITIES", "OBJECTS"
+            ]
+        );
+        assert_eq!(
+            after("0", "TABLE"),
+            [
+                "VPORT",
+                "LTYPE",
+                "LAYER",
+                "STYLE",
+                "VIEW",
+                "UCS",
+                "APPID",
+                "DIMSTYLE",
+                "BLOCK_RECORD"
+            ]
+        );
+        assert_eq!(after("0", "BLOCK_RECORD").len(), 2);
+
+        // Layers only use linetypes that are defined
+        let linetypes: Vec<&str> = pairs
+            .windows(6)
+            .filter(|w| w[0] == ("0", "LTYPE") && w[5].0 == "2")
+            .map(|w| w[5].1)
+            .collect();
+        assert_eq!(linetypes, ["ByBlock", "ByLayer", "Continuous"]);
+        let layer_records = pairs.windows(2).filter(|w| w[0] == ("0", "LAYER")).count();
+        let tables = pairs.iter().position(|p| *p == ("2", "TABLES")).unwrap();
+        let blocks = pairs.iter().position(|p| *p == ("2", "BLOCKS")).unwrap();
+        assert_eq!(layer_records, 2);
+        assert!(
+            pairs[tables..blocks]
+                .iter()
+                .filter(|(code, _)| *code == "6")
+                .all(|(_, lt)| linetypes.contains(lt))
+        );
+
+        // Handles are unique, below the seed, and every reference resolves
+        let handles: Vec<u32> = pairs
+            .windows(2)
+            .filter(|w| (w[1].0 == "5" || w[1].0 == "105") && w[0] != ("9", "$HANDSEED"))
+            .map(|w| u32::from_str_radix(w[1].1, 16).unwrap())
+            .collect();
+        let mut unique = handles.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), handles.len());
+        let seed = u32::from_str_radix(after("9", "$HANDSEED")[0], 16).unwrap();
+        assert!(handles.iter().all(|h| *h < seed));
+        for (code, value) in &pairs {
+            if ["330", "340", "350", "390"].contains(code) && *value != "0" {
+                let h = u32::from_str_radix(value, 16).unwrap();
+                assert!(handles.contains(&h), "dangling {} {}", code, value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rgb_to_aci() {
+        assert_eq!(rgb_to_aci((0, 0, 0)), 7);
+        assert_eq!(rgb_to_aci((255, 0, 0)), 1);
+        assert_eq!(rgb_to_aci((0, 0, 160)), 5);
+        assert_eq!(rgb_to_aci((180, 180, 180)), 9);
+        assert_eq!(aci_to_rgb(3), Some((0, 255, 0)));
+        assert_eq!(aci_to_rgb(42), None);
+    }
+}
@@ -0,0 +1,1040 @@
+//! DXF to LBRN2 conversion
+//!
+//! Reads the ENTITIES section of an ASCII DXF, with one cut setting per layer.
+
+use super::dxf::aci_to_rgb;
+use super::style::lightburn_layer_color;
+use super::svg_import::{push_cubic, round};
+use super::types::*;
+use super::writer::{format_prim_list, format_vert_list};
+use std::collections::BTreeMap;
+use std::f64::consts::{FRAC_PI_2, TAU};
+use std::sync::Arc;
+
+/// Options for DXF import
+#[derive(Debug, Clone, Default)]
+pub struct DxfImportOptions {
+    /// Millimetres per drawing unit; `None` reads `$INSUNITS` and treats
+    /// unitless drawings as millimetres (default: None)
+    pub scale: Option<f64>,
+}
+
+/// One entity or table record: its type and group code/value pairs
+struct Record<'a> {
+    kind: &'a str,
+    pairs: Vec<(i32, &'a str)>,
+}
+
+impl<'a> Record<'a> {
+    fn str(&self, code: i32) -> Option<&'a str> {
+        self.pairs.iter().find(|(c, _)| *c == code).map(|(_, v)| *v)
+    }
+
+    fn num(&self, code: i32) -> Option<f64> {
+        self.str(code).and_then(|v| v.parse().ok())
+    }
+
+    fn int(&self, code: i32) -> Option<i64> {
+        self.str(code).and_then(|v| v.parse().ok())
+    }
+
+    fn layer(&self) -> &'a str {
+        self.str(8).unwrap_or("0")
+    }
+
+    /// All values of a repeated group code, in order
+    fn nums(&self, code: i32) -> Vec<f64> {
+        self.pairs
+            .iter()
+            .filter(|(c, _)| *c == code)
+            .filter_map(|(_, v)| v.parse().ok())
+            .collect()
+    }
+
+    /// Points from repeated `code`/`code + 10` pairs, in order
+    fn points(&self, code: i32) -> Vec<(f64, f64)> {
+        let xs = self.nums(code);
+        let ys = self.nums(code + 10);
+        xs.into_iter().zip(ys).collect()
+    }
+}
+
+/// Split a DXF file into group code/value pairs
+fn parse_pairs(dxf: &str) -> Result<Vec<(i32, &str)>, String> {
+    let mut lines = dxf.lines().enumerate();
+    let mut pairs = Vec::new();
+    while let Some((line, code)) = lines.next() {
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+        let code: i32 = code
+            .parse()
+            .map_err(|_| format!("Invalid group code '{}' on line {}", code, line + 1))?;
+        let Some((_, value)) = lines.next() else {
+            return Err(format!(
+                "Missing value for group code {} at end of file",
+                code
+            ));
+        };
+        pairs.push((code, value.trim()));
+    }
+    Ok(pairs)
+}
+
+/// Records of every section, keyed by section name
+fn parse_sections<'a>(pairs: &[(i32, &'a str)]) -> BTreeMap<&'a str, Vec<Record<'a>>> {
+    let mut sections: BTreeMap<&str, Vec<Record>> = BTreeMap::new();
+    let mut current: Option<&str> = None;
+    let mut i = 0;
+
+    while i < pairs.len() {
+        let (code, value) = pairs[i];
+        i += 1;
+        if code != 0 {
+            if let Some(record) = current
+                .and_then(|name| sections.get_mut(name))
+                .and_then(|records| records.last_mut())
+            {
+                record.pairs.push((code, value));
+            }
+            continue;
+        }
+        match value {
+            "SECTION" => {
+                if let Some(&(2, name)) = pairs.get(i) {
+                    // Pairs before the first record (header variables) go
+                    // into an unnamed one
+                    sections.entry(name).or_default().push(Record {
+                        kind: "",
+                        pairs: Vec::new(),
+                    });
+                    current = Some(name);
+                    i += 1;
+                }
+            }
+            "ENDSEC" => current = None,
+            "EOF" => break,
+            kind => {
+                if let Some(records) = current.and_then(|name| sections.get_mut(name)) {
+                    records.push(Record {
+                        kind,
+                        pairs: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+    sections
+}
+
+/// Millimetres per drawing unit for an `$INSUNITS` value
+fn insunits_scale(units: i64) -> Option<f64> {
+    match units {
+        // Unitless drawings are taken as millimetres
+        0 | 4 => Some(1.0),
+        1 => Some(25.4),
+        2 => Some(304.8),
+        3 => Some(1_609_344.0),
+        5 => Some(10.0),
+        6 => Some(1e3),
+        7 => Some(1e6),
+        8 => Some(2.54e-5),
+        9 => Some(0.0254),
+        10 => Some(914.4),
+        11 => Some(1e-7),
+        12 => Some(1e-6),
+        13 => Some(1e-3),
+        14 => Some(100.0),
+        15 => Some(1e4),
+        16 => Some(1e5),
+        17 => Some(1e12),
+        18 => Some(1.495_978_707e14),
+        19 => Some(9.460_730_472_580_8e18),
+        20 => Some(3.085_677_581_491_367e19),
+        _ => None,
+    }
+}
+
+/// Builds a LightBurn path from drawing-space segments
+struct PathBuilder {
+    /// Drawing units (and OCS mirroring) to LightBurn millimetres
+    xform: XForm,
+    verts: Vec<Vec2>,
+    prims: Vec<PathPrimitive>,
+    start: Option<usize>,
+    current: Option<usize>,
+}
+
+impl PathBuilder {
+    fn new(xform: XForm) -> Self {
+        Self {
+            xform,
+            verts: Vec::new(),
+            prims: Vec::new(),
+            start: None,
+            current: None,
+        }
+    }
+
+    fn push_vert(&mut self, p: (f64, f64)) -> usize {
+        let (x, y) = self.xform.transform_point(p.0, p.1);
+        self.verts.push(Vec2::new(round(x), round(y)));
+        self.verts.len() - 1
+    }
+
+    fn move_to(&mut self, p: (f64, f64)) {
+        let idx = self.push_vert(p);
+        self.start = Some(idx);
+        self.current = Some(idx);
+    }
+
+    fn line_to(&mut self, p: (f64, f64)) {
+        let Some(from) = self.current else {
+            return self.move_to(p);
+        };
+        let to = self.push_vert(p);
+        self.prims.push(PathPrimitive::Line {
+            start_idx: from,
+            end_idx: to,
+        });
+        self.current = Some(to);
+    }
+
+    fn cubic_to(&mut self, c0: (f64, f64), c1: (f64, f64), p: (f64, f64)) {
+        let Some(from) = self.current else {
+            return self.move_to(p);
+        };
+        let tx = |p: (f64, f64)| self.xform.transform_point(p.0, p.1);
+        let (c0, c1, p) = (tx(c0), tx(c1), tx(p));
+        let to = push_cubic(&mut self.verts, &mut self.prims, from, c0, c1, p);
+        self.current = Some(to);
+    }
+
+    /// Elliptical arc `center + u cos t + v sin t` for t from `t0` over
+    /// `sweep` radians, as cubics of at most 90 degrees each
+    ///
+    /// Starts a new subpath unless the current point is already on the
+    /// arc's start.
+    fn arc(&mut self, center: (f64, f64), u: (f64, f64), v: (f64, f64), t0: f64, sweep: f64) {
+        let point = |t: f64| {
+            (
+                center.0 + u.0 * t.cos() + v.0 * t.sin(),
+                center.1 + u.1 * t.cos() + v.1 * t.sin(),
+            )
+        };
+        let tangent = |t: f64| {
+            (
+                -u.0 * t.sin() + v.0 * t.cos(),
+                -u.1 * t.sin() + v.1 * t.cos(),
+            )
+        };
+
+        let start = point(t0);
+        let on_start = self.current.is_some_and(|i| {
+            let (x, y) = self.xform.transform_point(start.0, start.1);
+            (self.verts[i].x - x).abs() < 1e-6 && (self.verts[i].y - y).abs() < 1e-6
+        });
+        if !on_start {
+            self.move_to(start);
+        }
+
+        let pieces = (sweep.abs() / FRAC_PI_2 - 1e-9).ceil().max(1.0) as usize;
+        let step = sweep / pieces as f64;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        for i in 0..pieces {
+            let (ta, tb) = (t0 + step * i as f64, t0 + step * (i + 1) as f64);
+            let (pa, da) = (point(ta), tangent(ta));
+            let (pb, db) = (point(tb), tangent(tb));
+            self.cubic_to(
+                (pa.0 + k * da.0, pa.1 + k * da.1),
+                (pb.0 - k * db.0, pb.1 - k * db.1),
+                pb,
+            );
+        }
+    }
+
+    /// Close the current subpath, merging a final vertex that lands on the
+    /// start point
+    fn close(&mut self) {
+        let (Some(first), Some(last)) = (self.start, self.current) else {
+            return;
+        };
+        if first == last {
+            return;
+        }
+        let on_start = (self.verts[last].x - self.verts[first].x).abs() < 1e-6
+            && (self.verts[last].y - self.verts[first].y).abs() < 1e-6;
+        if on_start && last == self.verts.len() - 1 {
+            let dup = self.verts.pop().unwrap();
+            self.verts[first].c1x = dup.c1x;
+            self.verts[first].c1y = dup.c1y;
+            if let Some(
+                PathPrimitive::Line { end_idx, .. } | PathPrimitive::Bezier { end_idx, .. },
+            ) = self.prims.last_mut()
+            {
+                *end_idx = first;
+            }
+        } else {
+            self.prims.push(PathPrimitive::Line {
+                start_idx: last,
+                end_idx: first,
+            });
+        }
+        self.current = Some(first);
+    }
+
+    fn into_shape(self, cut_index: i32) -> Option<Shape> {
+        if self.prims.is_empty() {
+            return None;
+        }
+        Some(Shape::Path(Path {
+            cut_index,
+            xform: XForm::identity(),
+            vert_list: format_vert_list(&self.verts).into(),
+            prim_list: format_prim_list(&self.prims).into(),
+            parsed_verts: Arc::new(self.verts),
+            parsed_primitives: Arc::new(self.prims),
+            vert_id: None,
+            prim_id: None,
+        }))
+    }
+}
+
+/// Append a polyline with DXF bulges (tan of a quarter of each segment's
+/// included angle) to the builder
+fn push_bulged_polyline(builder: &mut PathBuilder, points: &[((f64, f64), f64)], closed: bool) {
+    let Some(&(first, _)) = points.first() else {
+        return;
+    };
+    builder.move_to(first);
+
+    let count = if closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+    for i in 0..count {
+        let (p0, bulge) = points[i];
+        let (p1, _) = points[(i + 1) % points.len()];
+        if bulge.abs() < 1e-12 {
+            builder.line_to(p1);
+            continue;
+        }
+
+        let theta = 4.0 * bulge.atan();
+        let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+        let chord = dx.hypot(dy);
+        if chord == 0.0 {
+            continue;
+        }
+        // The center lies on the chord's left for counter-clockwise arcs
+        let h = chord / 2.0 / (theta / 2.0).tan();
+        let center = (
+            (p0.0 + p1.0) / 2.0 - dy / chord * h,
+            (p0.1 + p1.1) / 2.0 + dx / chord * h,
+        );
+        let r = (p0.0 - center.0).hypot(p0.1 - center.1);
+        let t0 = (p0.1 - center.1).atan2(p0.0 - center.0);
+        builder.arc(center, (r, 0.0), (0.0, r), t0, theta);
+    }
+    if closed {
+        builder.close();
+    }
+}
+
+/// Insert knot `u` once into a B-spline of degree `p`
+fn insert_knot(p: usize, knots: &mut Vec<f64>, ctrl: &mut Vec<(f64, f64)>, u: f64) {
+    let k = knots
+        .iter()
+        .rposition(|&t| t <= u)
+        .unwrap_or(0)
+        .min(knots.len() - p - 2);
+    let mut out = Vec::with_capacity(ctrl.len() + 1);
+    for i in 0..=ctrl.len() {
+        let q = if i + p <= k {
+            ctrl[i]
+        } else if i > k {
+            ctrl[i - 1]
+        } else {
+            let a = (u - knots[i]) / (knots[i + p] - knots[i]);
+            (
+                (1.0 - a) * ctrl[i - 1].0 + a * ctrl[i].0,
+                (1.0 - a) * ctrl[i - 1].1 + a * ctrl[i].1,
+            )
+        };
+        out.push(q);
+    }
+    knots.insert(k + 1, u);
+    *ctrl = out;
+}
+
+/// Split a clamped, non-rational B-spline of degree 1..=3 into Bezier
+/// control polygons of `p + 1` points each
+fn spline_to_beziers(p: usize, knots: &[f64], ctrl: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+    let mut knots = knots.to_vec();
+    let mut ctrl = ctrl.to_vec();
+    let (lo, hi) = (knots[p], knots[knots.len() - p - 1]);
+
+    let mut interior: Vec<f64> = knots
+        .iter()
+        .copied()
+        .filter(|&t| t > lo && t < hi)
+        .collect();
+    interior.dedup();
+    for u in interior {
+        let multiplicity = knots.iter().filter(|&&t| t == u).count();
+        for _ in multiplicity..p {
+            insert_knot(p, &mut knots, &mut ctrl, u);
+        }
+    }
+
+    ctrl.windows(p + 1).step_by(p).map(|w| w.to_vec()).collect()
+}
+
+/// Evaluate a (possibly rational) B-spline with de Boor's algorithm
+fn de_boor(p: usize, knots: &[f64], ctrl: &[(f64, f64, f64)], u: f64) -> (f64, f64) {
+    let n = ctrl.len();
+    let k = (p..n)
+        .rev()
+        .find(|&k| knots[k] <= u)
+        .unwrap_or(p)
+        .min(n - 1);
+    let mut d: Vec<(f64, f64, f64)> = (0..=p).map(|j| ctrl[j + k - p]).collect();
+    for r in 1..=p {
+        for j in (r..=p).rev() {
+            let (lo, hi) = (knots[j + k - p], knots[j + 1 + k - r]);
+            let a = if hi > lo { (u - lo) / (hi - lo) } else { 0.0 };
+            d[j] = (
+                (1.0 - a) * d[j - 1].0 + a * d[j].0,
+                (1.0 - a) * d[j - 1].1 + a * d[j].1,
+                (1.0 - a) * d[j - 1].2 + a * d[j].2,
+            );
+        }
+    }
+    let (x, y, w) = d[p];
+    (x / w, y / w)
+}
+
+/// Color of a layer table record as `#RRGGBB`
+fn layer_color(record: &Record) -> Option<String> {
+    if let Some(rgb) = record.int(420) {
+        return Some(format!("#{:06X}", rgb & 0xFF_FFFF));
+    }
+    // Negative colors mark layers that are switched off
+    let aci = record.int(62)?.unsigned_abs() as i32;
+    let (r, g, b) = match aci {
+        7 => (0, 0, 0),
+        aci => aci_to_rgb(aci)?,
+    };
+    Some(format!("#{:02X}{:02X}{:02X}", r, g, b))
+}
+
+struct Importer<'a> {
+    /// Drawing units to millimetres
+    scale: f64,
+    layer_colors: BTreeMap<&'a str, String>,
+    /// Cut index of each layer, in order of first use
+    layers: Vec<&'a str>,
+    shapes: Vec<Shape>,
+    skipped: BTreeMap<String, usize>,
+    warnings: Vec<String>,
+}
+
+impl<'a> Importer<'a> {
+    fn cut_index(&mut self, layer: &'a str) -> i32 {
+        let index = match self.layers.iter().position(|l| *l == layer) {
+            Some(i) => i,
+            None => {
+                self.layers.push(layer);
+                self.layers.len() - 1
+            }
+        };
+        index as i32
+    }
+
+    /// Drawing to LightBurn transform for entities stored in world
+    /// coordinates: LINE, ELLIPSE, SPLINE and 3D POLYLINE
+    fn world_xform(&self) -> XForm {
+        XForm {
+            a: self.scale,
+            d: self.scale,
+            ..XForm::identity()
+        }
+    }
+
+    /// Drawing to LightBurn transform for entities stored in their object
+    /// coordinate system (ARC, CIRCLE, LWPOLYLINE and 2D POLYLINE), mirroring
+    /// X when the extrusion points down (the usual way CAD programs store
+    /// mirrored arcs)
+    fn ocs_xform(&self, record: &Record) -> XForm {
+        let mirror = record.num(230).is_some_and(|z| z < 0.0);
+        XForm {
+            a: if mirror { -self.scale } else { self.scale },
+            ..self.world_xform()
+        }
+    }
+
+    fn push(&mut self, layer: &'a str, builder: PathBuilder) {
+        let cut_index = self.cut_index(layer);
+        if let Some(shape) = builder.into_shape(cut_index) {
+            self.shapes.push(shape);
+        }
+    }
+
+    fn skip(&mut self, kind: &str) {
+        *self.skipped.entry(kind.to_string()).or_default() += 1;
+    }
+
+    fn import(&mut self, records: &'a [Record<'a>]) {
+        let mut i = 0;
+        while i < records.len() {
+            let record = &records[i];
+            i += 1;
+            match record.kind {
+                "LINE" => self.import_line(record),
+                "LWPOLYLINE" => self.import_lwpolyline(record),
+                "POLYLINE" => {
+                    let vertices: Vec<&Record> = records[i..]
+                        .iter()
+                        .take_while(|r| r.kind == "VERTEX")
+                        .collect();
+                    i += vertices.len();
+                    if records.get(i).is_some_and(|r| r.kind == "SEQEND") {
+                        i += 1;
+                    }
+                    self.import_polyline(record, &vertices);
+                }
+                "CIRCLE" => self.import_circle(record),
+                "ARC" => self.import_arc(record),
+                "ELLIPSE" => self.import_ellipse(record),
+                "SPLINE" => self.import_spline(record),
+                "" => {}
+                kind => self.skip(kind),
+            }
+        }
+    }
+
+    fn import_line(&mut self, record: &'a Record<'a>) {
+        let (Some(x0), Some(y0), Some(x1), Some(y1)) = (
+            record.num(10),
+            record.num(20),
+            record.num(11),
+            record.num(21),
+        ) else {
+            return self.skip("LINE");
+        };
+        let mut builder = PathBuilder::new(self.world_xform());
+        builder.move_to((x0, y0));
+        builder.line_to((x1, y1));
+        self.push(record.layer(), builder);
+    }
+
+    fn import_lwpolyline(&mut self, record: &'a Record<'a>) {
+        // Bulges (42) follow the vertex they belong to
+        let mut points: Vec<((f64, f64), f64)> = Vec::new();
+        let mut x = None;
+        for &(code, value) in &record.pairs {
+            let Ok(n) = value.parse::<f64>() else {
+                continue;
+            };
+            match code {
+                10 => x = Some(n),
+                20 => {
+                    if let Some(x) = x.take() {
+                        points.push(((x, n), 0.0));
+                    }
+                }
+                42 => {
+                    if let Some(last) = points.last_mut() {
+                        last.1 = n;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if points.len() < 2 {
+            return self.skip("LWPOLYLINE");
+        }
+
+        let closed = record.int(70).unwrap_or(0) & 1 != 0;
+        let mut builder = PathBuilder::new(self.ocs_xform(record));
+        push_bulged_polyline(&mut builder, &points, closed);
+        self.push(record.layer(), builder);
+    }
+
+    fn import_polyline(&mut self, record: &'a Record<'a>, vertices: &[&Record]) {
+        let flags = record.int(70).unwrap_or(0);
+        // 16 = polygon mesh, 64 = polyface mesh
+        if flags & (16 | 64) != 0 {
+            return self.skip("POLYLINE mesh");
+        }
+        let points: Vec<((f64, f64), f64)> = vertices
+            .iter()
+            .filter_map(|v| Some(((v.num(10)?, v.num(20)?), v.num(42).unwrap_or(0.0))))
+            .collect();
+        if points.len() < 2 {
+            return self.skip("POLYLINE");
+        }
+
+        // 8 = 3D polyline, whose vertices are in world coordinates
+        let xform = if flags & 8 != 0 {
+            self.world_xform()
+        } else {
+            self.ocs_xform(record)
+        };
+        let mut builder = PathBuilder::new(xform);
+        push_bulged_polyline(&mut builder, &points, flags & 1 != 0);
+        self.push(record.layer(), builder);
+    }
+
+    fn import_circle(&mut self, record: &'a Record<'a>) {
+        let (Some(cx), Some(cy), Some(r)) = (record.num(10), record.num(20), record.num(40)) else {
+            return self.skip("CIRCLE");
+        };
+        let (x, y) = self.ocs_xform(record).transform_point(cx, cy);
+        let cut_index = self.cut_index(record.layer());
+        self.shapes.push(Shape::Ellipse(Ellipse {
+            cut_index,
+            xform: XForm {
+                e: round(x),
+                f: round(y),
+                ..XForm::identity()
+            },
+            rx: round(r * self.scale),
+            ry: round(r * self.scale),
+        }));
+    }
+
+    fn import_arc(&mut self, record: &'a Record<'a>) {
+        let (Some(cx), Some(cy), Some(r), Some(a0), Some(a1)) = (
+            record.num(10),
+            record.num(20),
+            record.num(40),
+            record.num(50),
+            record.num(51),
+        ) else {
+            return self.skip("ARC");
+        };
+        // Arcs always run counter-clockwise from the start to the end angle
+        let (t0, t1) = (a0.to_radians(), a1.to_radians());
+        let mut sweep = (t1 - t0).rem_euclid(TAU);
+        if sweep == 0.0 {
+            sweep = TAU;
+        }
+        let mut builder = PathBuilder::new(self.ocs_xform(record));
+        builder.arc((cx, cy), (r, 0.0), (0.0, r), t0, sweep);
+        self.push(record.layer(), builder);
+    }
+
+    fn import_ellipse(&mut self, record: &'a Record<'a>) {
+        let (Some(cx), Some(cy), Some(mx), Some(my)) = (
+            record.num(10),
+            record.num(20),
+            record.num(11),
+            record.num(21),
+        ) else {
+            return self.skip("ELLIPSE");
+        };
+        let ratio = record.num(40).unwrap_or(1.0);
+        let t0 = record.num(41).unwrap_or(0.0);
+        let t1 = record.num(42).unwrap_or(TAU);
+        // Center and axis are in world coordinates; a downward extrusion only
+        // reverses the parameter direction
+        let dir = if record.num(230).is_some_and(|z| z < 0.0) {
+            -1.0
+        } else {
+            1.0
+        };
+        let u = (mx, my);
+        let v = (-my * ratio * dir, mx * ratio * dir);
+
+        let mut sweep = (t1 - t0).rem_euclid(TAU);
+        if sweep < 1e-9 {
+            sweep = TAU;
+        }
+
+        let scale = self.scale;
+        if (sweep - TAU).abs() < 1e-9 {
+            let rx = mx.hypot(my) * scale;
+            let angle = my.atan2(mx);
+            let cut_index = self.cut_index(record.layer());
+            self.shapes.push(Shape::Ellipse(Ellipse {
+                cut_index,
+                xform: XForm {
+                    a: round(angle.cos()),
+                    b: round(angle.sin()),
+                    c: round(-angle.sin()),
+                    d: round(angle.cos()),
+                    e: round(cx * scale),
+                    f: round(cy * scale),
+                },
+                rx: round(rx),
+                ry: round(rx * ratio),
+            }));
+            return;
+        }
+
+        let mut builder = PathBuilder::new(self.world_xform());
+        builder.arc((cx, cy), u, v, t0, sweep);
+        self.push(record.layer(), builder);
+    }
+
+    fn import_spline(&mut self, record: &'a Record<'a>) {
+        let flags = record.int(70).unwrap_or(0);
+        let degree = record.int(71).unwrap_or(3).max(1) as usize;
+        let knots = record.nums(40);
+        let weights = record.nums(41);
+        let ctrl = record.points(10);
+        let mut builder = PathBuilder::new(self.world_xform());
+
+        if ctrl.len() <= degree || knots.len() != ctrl.len() + degree + 1 {
+            // Splines saved with fit points only
+            let fit = record.points(11);
+            if fit.len() < 2 {
+                return self.skip("SPLINE");
+            }
+            self.warnings.push(format!(
+                "SPLINE on layer '{}' has no control points, using its fit points",
+                record.layer()
+            ));
+            builder.move_to(fit[0]);
+            for &p in &fit[1..] {
+                builder.line_to(p);
+            }
+        } else {
+            let rational = weights.len() == ctrl.len()
+                && weights.iter().any(|w| (w - weights[0]).abs() > 1e-12);
+            let clamped = knots[..=degree].iter().all(|&k| k == knots[0])
+                && knots[knots.len() - degree - 1..]
+                    .iter()
+                    .all(|&k| k == knots[knots.len() - 1]);
+
+            if degree <= 3 && clamped && !rational {
+                builder.move_to(ctrl[0]);
+                for bez in spline_to_beziers(degree, &knots, &ctrl) {
+                    match *bez.as_slice() {
+                        [_, p1] => builder.line_to(p1),
+                        [p0, c, p1] => builder.cubic_to(
+                            (
+                                p0.0 + 2.0 / 3.0 * (c.0 - p0.0),
+                                p0.1 + 2.0 / 3.0 * (c.1 - p0.1),
+                            ),
+                            (
+                                p1.0 + 2.0 / 3.0 * (c.0 - p1.0),
+                                p1.1 + 2.0 / 3.0 * (c.1 - p1.1),
+                            ),
+                            p1,
+                        ),
+                        [_, c0, c1, p1] => builder.cubic_to(c0, c1, p1),
+                        _ => {}
+                    }
+                }
+            } else {
+                // Sample anything Beziers cannot represent exactly
+                let homogeneous: Vec<(f64, f64, f64)> = ctrl
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(x, y))| {
+                        let w = if rational { weights[i] } else { 1.0 };
+                        (x * w, y * w, w)
+                    })
+                    .collect();
+                let (lo, hi) = (knots[degree], knots[ctrl.len()]);
+                let steps = 32 * (ctrl.len() - degree);
+                for i in 0..=steps {
+                    let u = lo + (hi - lo) * i as f64 / steps as f64;
+                    let p = de_boor(degree, &knots, &homogeneous, u);
+                    if i == 0 {
+                        builder.move_to(p);
+                    } else {
+                        builder.line_to(p);
+                    }
+                }
+            }
+        }
+
+        if flags & 1 != 0 {
+            builder.close();
+        } else if let (Some(first), Some(last)) = (builder.start, builder.current)
+            && first != last
+            && (builder.verts[first].x - builder.verts[last].x).abs() < 1e-6
+            && (builder.verts[first].y - builder.verts[last].y).abs() < 1e-6
+        {
+            builder.close();
+        }
+        self.push(record.layer(), builder);
+    }
+}
+
+/// Convert a DXF document to a LightBurnProject
+pub fn dxf_to_lbrn2(dxf: &str, options: &DxfImportOptions) -> Result<LightBurnProject, String> {
+    dxf_to_lbrn2_with_warnings(dxf, options).map(|(project, _)| project)
+}
+
+/// Convert a DXF document to a LightBurnProject, also returning the list of
+/// entities that could not be imported
+pub fn dxf_to_lbrn2_with_warnings(
+    dxf: &str,
+    options: &DxfImportOptions,
+) -> Result<(LightBurnProject, Vec<String>), String> {
+    let pairs = parse_pairs(dxf)?;
+    let sections = parse_sections(&pairs);
+    let Some(entities) = sections.get("ENTITIES") else {
+        return Err("DXF file has no ENTITIES section".to_string());
+    };
+
+    let mut warnings = Vec::new();
+    let scale = match options.scale {
+        Some(scale) => scale,
+        None => {
+            // Header variables are a flat list: 9 name, then its values
+            let header = sections.get("HEADER").map(|h| h.as_slice()).unwrap_or(&[]);
+            let units = header
+                .iter()
+                .flat_map(|r| r.pairs.iter())
+                .skip_while(|(c, v)| !(*c == 9 && *v == "$INSUNITS"))
+                .nth(1)
+                .and_then(|(_, v)| v.parse::<i64>().ok())
+                .unwrap_or(0);
+            insunits_scale(units).unwrap_or_else(|| {
+                warnings.push(format!(
+                    "Unsupported $INSUNITS value {}, assuming millimetres",
+                    units
+                ));
+                1.0
+            })
+        }
+    };
+
+    let layer_colors = sections
+        .get("TABLES")
+        .into_iter()
+        .flatten()
+        .filter(|r| r.kind == "LAYER")
+        .filter_map(|r| Some((r.str(2)?, layer_color(r)?)))
+        .collect();
+
+    let mut importer = Importer {
+        scale,
+        layer_colors,
+        layers: Vec::new(),
+        shapes: Vec::new(),
+        skipped: BTreeMap::new(),
+        warnings,
+    };
+    importer.import(entities);
+
+    for (kind, count) in &importer.skipped {
+        importer.warnings.push(format!(
+            "{} {} entit{} skipped",
+            count,
+            kind,
+            if *count == 1 { "y" } else { "ies" }
+        ));
+    }
+
+    let cut_settings = importer
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, layer)| {
+            let mut cs = CutSetting::new(i as i32);
+            cs.name = layer.to_string();
+            cs.color = importer
+                .layer_colors
+                .get(layer)
+                .cloned()
+                .or_else(|| lightburn_layer_color(i as i32).map(str::to_string));
+            cs
+        })
+        .collect();
+
+    let project = LightBurnProject {
+        app_version: String::new(),
+        format_version: "1".to_string(),
+        cut_settings,
+        shapes: importer.shapes,
+        ..Default::default()
+    };
+    Ok((project, importer.warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dxf(entities: &str) -> String {
+        format!("0\nSECTION\n2\nENTITIES\n{}0\nENDSEC\n0\nEOF\n", entities)
+    }
+
+    fn only_path(project: &LightBurnProject) -> &Path {
+        match project.shapes.as_slice() {
+            [Shape::Path(path)] => path,
+            other => panic!("expected one path, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bulge_becomes_semicircle() {
+        // Two half circles of radius 5 around (5, 0)
+        let project = dxf_to_lbrn2(
+            &dxf(
+                "0\nLWPOLYLINE\n8\nCUT\n90\n2\n70\n1\n10\n0\n20\n0\n42\n1\n10\n10\n20\n0\n42\n1\n",
+            ),
+            &DxfImportOptions::default(),
+        )
+        .unwrap();
+        let path = only_path(&project);
+        assert_eq!(project.cut_settings[0].name, "CUT");
+        assert!(
+            path.parsed_primitives
+                .iter()
+                .all(|p| matches!(p, PathPrimitive::Bezier { .. }))
+        );
+        assert_eq!(path.parsed_primitives.len(), 4);
+        // Closed back onto the first vertex
+        assert!(matches!(
+            path.parsed_primitives.last(),
+            Some(PathPrimitive::Bezier { end_idx: 0, .. })
+        ));
+        for v in path.parsed_verts.iter() {
+            assert!(((v.x - 5.0).hypot(v.y) - 5.0).abs() < 1e-6, "{:?}", v);
+        }
+        // A positive bulge runs counter-clockwise: below the chord first
+        assert!(path.parsed_verts[1].y < 0.0);
+    }
+
+    #[test]
+    fn test_spline_decomposition_matches_de_boor() {
+        let knots = [0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 2.0, 2.0, 2.0];
+        let ctrl = [(0.0, 0.0), (1.0, 3.0), (3.0, 4.0), (5.0, 1.0), (6.0, 0.0)];
+        let beziers = spline_to_beziers(3, &knots, &ctrl);
+        assert_eq!(beziers.len(), 2);
+
+        let homogeneous: Vec<_> = ctrl.iter().map(|&(x, y)| (x, y, 1.0)).collect();
+        for (i, bez) in beziers.iter().enumerate() {
+            for s in 0..=10 {
+                let t = s as f64 / 10.0;
+                let mt = 1.0 - t;
+                let x = mt.powi(3) * bez[0].0
+                    + 3.0 * mt * mt * t * bez[1].0
+                    + 3.0 * mt * t * t * bez[2].0
+                    + t.powi(3) * bez[3].0;
+                let y = mt.powi(3) * bez[0].1
+                    + 3.0 * mt * mt * t * bez[1].1
+                    + 3.0 * mt * t * t * bez[2].1
+                    + t.powi(3) * bez[3].1;
+                let expected = de_boor(3, &knots, &homogeneous, i as f64 + t);
+                assert!((x - expected.0).abs() < 1e-9 && (y - expected.1).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_units_and_layer_colors() {
+        let source = "0\nSECTION\n2\nHEADER\n9\n$INSUNITS\n70\n1\n0\nENDSEC\n\
+                      0\nSECTION\n2\nTABLES\n0\nTABLE\n2\nLAYER\n\
+                      0\nLAYER\n2\nRed\n62\n1\n0\nLAYER\n2\nTrue\n62\n3\n420\n1193046\n\
+                      0\nENDTAB\n0\nENDSEC\n\
+                      0\nSECTION\n2\nENTITIES\n\
+                      0\nCIRCLE\n8\nTrue\n10\n1\n20\n2\n40\n0.5\n\
+                      0\nTEXT\n8\nRed\n1\nHello\n\
+                      0\nLINE\n8\nRed\n10\n0\n20\n0\n11\n1\n21\n0\n\
+                      0\nENDSEC\n0\nEOF\n";
+        let (project, warnings) =
+            dxf_to_lbrn2_with_warnings(source, &DxfImportOptions::default()).unwrap();
+
+        assert_eq!(project.cut_settings.len(), 2);
+        assert_eq!(project.cut_settings[0].name, "True");
+        assert_eq!(project.cut_settings[0].color.as_deref(), Some("#123456"));
+        assert_eq!(project.cut_settings[1].color.as_deref(), Some("#FF0000"));
+
+        let Shape::Ellipse(circle) = &project.shapes[0] else {
+            panic!("expected a circle");
+        };
+        assert_eq!((circle.xform.e, circle.xform.f), (25.4, 50.8));
+        assert_eq!((circle.rx, circle.ry), (12.7, 12.7));
+        assert_eq!(project.shapes[1].cut_index(), 1);
+        assert_eq!(warnings, ["1 TEXT entity skipped"]);
+    }
+
+    #[test]
+    fn test_every_insunits_code() {
+        let expected = [
+            (0, 1.0),
+            (1, 25.4),
+            (2, 304.8),
+            (3, 1_609_344.0),
+            (4, 1.0),
+            (5, 10.0),
+            (6, 1e3),
+            (7, 1e6),
+            (8, 2.54e-5),
+            (9, 0.0254),
+            (10, 914.4),
+            (11, 1e-7),
+            (12, 1e-6),
+            (13, 1e-3),
+            (14, 100.0),
+            (15, 1e4),
+            (16, 1e5),
+            (17, 1e12),
+            (18, 1.495_978_707e14),
+            (19, 9.460_730_472_580_8e18),
+            (20, 3.085_677_581_491_367e19),
+        ];
+        for (code, mm) in expected {
+            // A line that comes out 1 m long, well clear of coordinate rounding
+            let source = format!(
+                "0\nSECTION\n2\nHEADER\n9\n$INSUNITS\n70\n{}\n0\nENDSEC\n\
+                 0\nSECTION\n2\nENTITIES\n0\nLINE\n8\n0\n10\n0\n20\n0\n11\n{:e}\n21\n0\n\
+                 0\nENDSEC\n0\nEOF\n",
+                code,
+                1000.0 / mm
+            );
+            let (project, warnings) =
+                dxf_to_lbrn2_with_warnings(&source, &DxfImportOptions::default()).unwrap();
+            let end = only_path(&project).parsed_verts[1].x;
+            assert!((end - 1000.0).abs() < 1e-6, "code {}: {}", code, end);
+            assert!(warnings.is_empty(), "code {}: {:?}", code, warnings);
+        }
+
+        let source = "0\nSECTION\n2\nHEADER\n9\n$INSUNITS\n70\n21\n0\nENDSEC\n\
+                      0\nSECTION\n2\nENTITIES\n0\nENDSEC\n0\nEOF\n";
+        let (_, warnings) =
+            dxf_to_lbrn2_with_warnings(source, &DxfImportOptions::default()).unwrap();
+        assert_eq!(
+            warnings,
+            ["Unsupported $INSUNITS value 21, assuming millimetres"]
+        );
+    }
+
+    #[test]
+    fn test_downward_extrusion_mirrors_only_ocs_entities() {
+        // A LINE is in world coordinates whatever its extrusion
+        let project = dxf_to_lbrn2(
+            &dxf("0\nLINE\n8\n0\n10\n1\n20\n2\n11\n5\n21\n2\n210\n0\n220\n0\n230\n-1\n"),
+            &DxfImportOptions::default(),
+        )
+        .unwrap();
+        let verts = &only_path(&project).parsed_verts;
+        assert_eq!((verts[0].x, verts[0].y), (1.0, 2.0));
+        assert_eq!((verts[1].x, verts[1].y), (5.0, 2.0));
+
+        // An ARC is in its OCS, whose X axis runs the other way
+        let project = dxf_to_lbrn2(
+            &dxf("0\nARC\n8\n0\n10\n10\n20\n0\n40\n1\n50\n0\n51\n90\n230\n-1\n"),
+            &DxfImportOptions::default(),
+        )
+        .unwrap();
+        let verts = &only_path(&project).parsed_verts;
+        assert_eq!((verts[0].x, verts[0].y), (-11.0, 0.0));
+    }
+
+    #[test]
+    fn test_malformed_dxf_is_an_error() {
+        assert!(dxf_to_lbrn2("0\nSECTION\nabc\n", &DxfImportOptions::default()).is_err());
+        assert!(dxf_to_lbrn2("0\nEOF\n", &DxfImportOptions::default()).is_err());
+    }
+}
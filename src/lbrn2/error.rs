@@ -0,0 +1,183 @@
+//! Error and warning types produced while reading LBRN2 files
+
+use std::fmt;
+
+/// Location of a construct within the LBRN2 source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SourcePos {
+    /// Byte offset from the start of the document
+    pub offset: usize,
+    /// 1-based line number
+    pub line: usize,
+}
+
+impl SourcePos {
+    /// Resolve a byte offset within `source` into a line/offset pair
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let offset = offset.min(source.len());
+        let line = source.as_bytes()[..offset]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1;
+        Self { offset, line }
+    }
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, byte {}", self.line, self.offset)
+    }
+}
+
+/// Problems found while parsing an LBRN2 document
+///
+/// `Xml` is always fatal. The remaining variants are recoverable: the parser
+/// substitutes a sensible default, records the problem in its warnings list
+/// and keeps going.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lbrn2Error {
+    /// The document is not well-formed XML
+    Xml { pos: SourcePos, message: String },
+    /// An `<XForm>` did not contain exactly six numbers; identity was used
+    InvalidXForm { pos: SourcePos, value: String },
+    /// A `<VertList>` entry could not be parsed and was skipped
+    MalformedVertList { pos: SourcePos, message: String },
+    /// A `<PrimList>` entry could not be parsed or points at a missing vertex
+    MalformedPrimList { pos: SourcePos, message: String },
+    /// A shape referenced a `VertID` that was never defined
+    DanglingVertId { pos: SourcePos, id: i32 },
+    /// A shape referenced a `PrimID` that was never defined
+    DanglingPrimId { pos: SourcePos, id: i32 },
+    /// A `Path` shape resolved to no vertices and was dropped
+    EmptyPath { pos: SourcePos },
+}
+
+impl Lbrn2Error {
+    /// Where in the source the problem was found
+    pub fn pos(&self) -> SourcePos {
+        match self {
+            Lbrn2Error::Xml { pos, .. }
+            | Lbrn2Error::InvalidXForm { pos, .. }
+            | Lbrn2Error::MalformedVertList { pos, .. }
+            | Lbrn2Error::MalformedPrimList { pos, .. }
+            | Lbrn2Error::DanglingVertId { pos, .. }
+            | Lbrn2Error::DanglingPrimId { pos, .. }
+            | Lbrn2Error::EmptyPath { pos } => *pos,
+        }
+    }
+}
+
+impl fmt::Display for Lbrn2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lbrn2Error::Xml { pos, message } => write!(f, "{}: XML error: {}", pos, message),
+            Lbrn2Error::InvalidXForm { pos, value } => {
+                write!(f, "{}: invalid XForm \"{}\", using identity", pos, value)
+            }
+            Lbrn2Error::MalformedVertList { pos, message } => {
+                write!(f, "{}: malformed VertList: {}", pos, message)
+            }
+            Lbrn2Error::MalformedPrimList { pos, message } => {
+                write!(f, "{}: malformed PrimList: {}", pos, message)
+            }
+            Lbrn2Error::DanglingVertId { pos, id } => {
+                write!(f, "{}: VertID={} was never defined", pos, id)
+            }
+            Lbrn2Error::DanglingPrimId { pos, id } => {
+                write!(f, "{}: PrimID={} was never defined", pos, id)
+            }
+            Lbrn2Error::EmptyPath { pos } => {
+                write!(f, "{}: Path shape has no vertices, skipping", pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Lbrn2Error {}
+
+/// What was wrong with a VertList or PrimList entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListErrorKind {
+    /// A vertex has fewer than two coordinates
+    MissingCoordinate,
+    /// A vertex coordinate is not a valid number
+    InvalidNumber,
+    /// A primitive has fewer than the two vertex indices it needs
+    MissingIndex { primitive: char, found: usize },
+    /// A primitive type other than `L` or `B`
+    UnknownPrimitive(char),
+    /// A character where a primitive type was expected
+    UnexpectedChar(char),
+}
+
+/// A malformed entry in a VertList or PrimList
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ListSyntaxError {
+    /// Byte offset of the entry within the list text
+    pub offset: usize,
+    /// Number of entries successfully parsed before this one
+    pub item: usize,
+    pub kind: ListErrorKind,
+}
+
+impl fmt::Display for ListSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ListErrorKind::MissingCoordinate => {
+                write!(f, "vertex {} is missing a coordinate", self.item)
+            }
+            ListErrorKind::InvalidNumber => {
+                write!(f, "vertex {} has an invalid coordinate", self.item)
+            }
+            ListErrorKind::MissingIndex { primitive, found } => write!(
+                f,
+                "primitive '{}' needs two vertex indices, found {}",
+                primitive, found
+            ),
+            ListErrorKind::UnknownPrimitive(c) => write!(f, "unknown primitive type '{}'", c),
+            ListErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+        }
+    }
+}
+
+impl std::error::Error for ListSyntaxError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_pos_from_offset() {
+        let src = "ab\ncd\nef";
+        assert_eq!(
+            SourcePos::from_offset(src, 0),
+            SourcePos { offset: 0, line: 1 }
+        );
+        assert_eq!(
+            SourcePos::from_offset(src, 3),
+            SourcePos { offset: 3, line: 2 }
+        );
+        assert_eq!(
+            SourcePos::from_offset(src, 7),
+            SourcePos { offset: 7, line: 3 }
+        );
+        // Offsets past the end clamp to the document length
+        assert_eq!(SourcePos::from_offset(src, 99).offset, src.len());
+    }
+
+    #[test]
+    fn test_display_includes_position() {
+        let err = Lbrn2Error::DanglingVertId {
+            pos: SourcePos {
+                offset: 42,
+                line: 3,
+            },
+            id: 7,
+        };
+        assert_eq!(
+            err.to_string(),
+            "line 3, byte 42: VertID=7 was never defined"
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! G-code generation for GRBL based lasers
+//!
+//! Burns flattened contours in workspace coordinates, which are already Y-up
+//! machine coordinates, with the laser off during `G0` travel.
+
+use super::geometry::{Polyline, shape_bezpaths};
+use super::path::NumberFormat;
+use super::svg::flatten_groups;
+use super::types::{CutSetting, CutType, LightBurnProject, Shape, XForm};
+use std::collections::BTreeSet;
+
+/// Spindle mode used while a layer is burning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LaserMode {
+    /// `M3`: constant power regardless of speed
+    Constant,
+    /// `M4`: power scaled with the actual speed, avoiding burnt corners
+    #[default]
+    Dynamic,
+}
+
+impl LaserMode {
+    fn command(&self) -> &'static str {
+        match self {
+            LaserMode::Constant => "M3",
+            LaserMode::Dynamic => "M4",
+        }
+    }
+}
+
+/// Default program start; `{min_x}`, `{min_y}`, `{max_x}` and `{max_y}` are
+/// replaced with the toolpath bounds
+pub const DEFAULT_GCODE_HEADER: &str = "; laser-tools G-code for GRBL
+; Bounds: X{min_x} Y{min_y} to X{max_x} Y{max_y}
+G00 G17 G40 G21 G54
+G90";
+
+/// Default program end: return to the origin; every layer already ends
+/// with the laser off
+pub const DEFAULT_GCODE_FOOTER: &str = "G0 X0 Y0
+M2";
+
+/// Options for G-code export
+#[derive(Debug, Clone, PartialEq)]
+pub struct GcodeOptions {
+    /// S value for 100% power, GRBL's `$30` (default: 1000)
+    pub s_max: f64,
+    /// Maximum distance between a curve and its flattened polyline in mm
+    /// (default: 0.01)
+    pub tolerance: f64,
+    /// Digits after the decimal point for coordinates (default: 3)
+    pub precision: usize,
+    /// Mode for layers that do not select constant power (default: M4)
+    pub laser_mode: LaserMode,
+    /// Speed for layers without one, in mm/s (default: 10)
+    pub default_speed: f64,
+    /// Power for layers without one, in percent (default: 20)
+    pub default_power: f64,
+    /// Text written before the first move, see [`DEFAULT_GCODE_HEADER`]
+    pub header: String,
+    /// Text written after the last move, see [`DEFAULT_GCODE_FOOTER`]
+    pub footer: String,
+}
+
+impl Default for GcodeOptions {
+    fn default() -> Self {
+        Self {
+            s_max: 1000.0,
+            tolerance: 0.01,
+            precision: 3,
+            laser_mode: LaserMode::Dynamic,
+            default_speed: 10.0,
+            default_power: 20.0,
+            header: DEFAULT_GCODE_HEADER.to_string(),
+            footer: DEFAULT_GCODE_FOOTER.to_string(),
+        }
+    }
+}
+
+/// Flatten a leaf shape into workspace polylines
+fn shape_contours(shape: &Shape, tolerance: f64, log: &mut Vec<String>) -> Vec<Polyline> {
+    match shape {
+        Shape::Bitmap(_) => {
+            log.push("Bitmap skipped: raster engraving is not supported".to_string());
+            Vec::new()
+        }
+        Shape::Text(text) if text.backup_path.is_none() => {
+            log.push(format!(
+                "Text \"{}\" skipped: no BackupPath outline to burn",
+                text.text
+            ));
+            Vec::new()
+        }
+        _ => shape_bezpaths(shape, &XForm::identity(), log)
+            .iter()
+            .map(|path| path.flatten(tolerance))
+            .collect(),
+    }
+}
+
+/// Layer mode: the `constPower` parameter selects M3, otherwise the default
+fn layer_mode(cs: Option<&CutSetting>, default: LaserMode) -> LaserMode {
+    match cs
+        .and_then(|cs| cs.params.get("constPower"))
+        .map(String::as_str)
+    {
+        Some("1") | Some("true") => LaserMode::Constant,
+        Some("0") | Some("false") => LaserMode::Dynamic,
+        _ => default,
+    }
+}
+
+/// Cut indices in burn order: cut settings by priority (ties keep file
+/// order), then layers without a cut setting by index
+pub(crate) fn layer_order(project: &LightBurnProject, used: &BTreeSet<i32>) -> Vec<i32> {
+    let mut settings: Vec<&CutSetting> = project.cut_settings.iter().collect();
+    settings.sort_by_key(|cs| cs.priority.unwrap_or(0));
+
+    let mut order: Vec<i32> = settings.iter().map(|cs| cs.index).collect();
+    order.dedup();
+    for index in used {
+        if !order.contains(index) {
+            order.push(*index);
+        }
+    }
+    order.retain(|i| used.contains(i));
+    order
+}
+
+/// Replace the bounds placeholders of a header or footer template
+fn fill_template(template: &str, bounds: (f64, f64, f64, f64), fmt: &NumberFormat) -> String {
+    template
+        .replace("{min_x}", &fmt.format(bounds.0))
+        .replace("{min_y}", &fmt.format(bounds.1))
+        .replace("{max_x}", &fmt.format(bounds.2))
+        .replace("{max_y}", &fmt.format(bounds.3))
+}
+
+/// Convert a LightBurn project to GRBL G-code
+pub fn lbrn2_to_gcode(project: &LightBurnProject, options: &GcodeOptions) -> String {
+    lbrn2_to_gcode_with_warnings(project, options).0
+}
+
+/// Convert a LightBurn project to GRBL G-code, also returning warnings about
+/// content that could not be converted
+pub fn lbrn2_to_gcode_with_warnings(
+    project: &LightBurnProject,
+    options: &GcodeOptions,
+) -> (String, Vec<String>) {
+    let mirrored = project.with_mirror_applied();
+    let project = mirrored.as_ref();
+    let fmt = NumberFormat {
+        precision: options.precision,
+        trim_zeros: true,
+    };
+    let f = |n: f64| fmt.format(n);
+    let mut log = Vec::new();
+
+    let mut leaves = Vec::new();
+    flatten_groups(&project.shapes, None, &mut leaves);
+    let used: BTreeSet<i32> = leaves.iter().map(Shape::cut_index).collect();
+
+    let mut body = String::new();
+    let mut bounds: Option<(f64, f64, f64, f64)> = None;
+
+    for index in layer_order(project, &used) {
+        let cs = project.cut_settings.iter().find(|cs| cs.index == index);
+        if cs.and_then(|cs| cs.do_output) == Some(false) {
+            continue;
+        }
+
+        let contours: Vec<Polyline> = leaves
+            .iter()
+            .filter(|s| s.cut_index() == index)
+            .flat_map(|s| shape_contours(s, options.tolerance, &mut log))
+            .collect();
+        if contours.is_empty() {
+            continue;
+        }
+
+        if let Some(cs) = cs
+            && matches!(cs.cut_type, CutType::Scan | CutType::OffsetFill)
+        {
+            log.push(format!(
+                "Layer {} is a {} layer; fills are not supported, tracing outlines instead",
+                index,
+                cs.cut_type.as_str()
+            ));
+        }
+
+        let speed = cs.and_then(|cs| cs.speed).unwrap_or(options.default_speed);
+        let power = cs
+            .and_then(|cs| cs.max_power)
+            .unwrap_or(options.default_power);
+        let passes = cs.and_then(|cs| cs.num_passes).unwrap_or(1).max(1);
+        let mode = layer_mode(cs, options.laser_mode);
+        let s = (power.clamp(0.0, 100.0) / 100.0 * options.s_max).round();
+        let feed = speed * 60.0;
+
+        body.push_str(&format!(
+            "; Layer C{:02}{}: {} mm/s, {}% power, {} pass{}\n",
+            index,
+            cs.filter(|cs| !cs.name.is_empty())
+                .map(|cs| format!(" ({})", cs.name))
+                .unwrap_or_default(),
+            f(speed),
+            f(power),
+            passes,
+            if passes == 1 { "" } else { "es" }
+        ));
+        body.push_str(&format!("{} S0\n", mode.command()));
+
+        for pass in 1..=passes {
+            if passes > 1 {
+                body.push_str(&format!("; Pass {}\n", pass));
+            }
+            for contour in &contours {
+                let mut last = String::new();
+                // Power and feed go on the first cut move, whichever vertex
+                // that is once repeated points are skipped
+                let mut cutting = false;
+                for (i, &(x, y)) in contour.points.iter().enumerate() {
+                    let xy = format!("X{} Y{}", f(x), f(y));
+                    if i == 0 {
+                        body.push_str(&format!("G0 {}\n", xy));
+                    } else if xy == last {
+                        continue;
+                    } else if !cutting {
+                        body.push_str(&format!("G1 {} S{} F{}\n", xy, f(s), f(feed)));
+                        cutting = true;
+                    } else {
+                        body.push_str(&format!("G1 {}\n", xy));
+                    }
+                    last = xy;
+
+                    let b = bounds.get_or_insert((x, y, x, y));
+                    *b = (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y));
+                }
+            }
+        }
+        body.push_str("M5\n");
+    }
+
+    let bounds = bounds.unwrap_or((0.0, 0.0, 0.0, 0.0));
+    let mut gcode = String::new();
+    for part in [
+        fill_template(&options.header, bounds, &fmt),
+        body,
+        fill_template(&options.footer, bounds, &fmt),
+    ] {
+        if part.is_empty() {
+            continue;
+        }
+        gcode.push_str(&part);
+        if !gcode.ends_with('\n') {
+            gcode.push('\n');
+        }
+    }
+
+    log.dedup();
+    (gcode, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::types::{Path, PathPrimitive, Rect, Vec2};
+
+    fn square_project(cut_settings: Vec<CutSetting>) -> LightBurnProject {
+        LightBurnProject {
+            app_version: String::new(),
+            format_version: String::new(),
+            cut_settings,
+            shapes: vec![Shape::Rect(Rect {
+                cut_index: 0,
+                xform: XForm {
+                    e: 10.0,
+                    f: 20.0,
+                    ..XForm::identity()
+                },
+                w: 4.0,
+                h: 2.0,
+                cr: 0.0,
+            })],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layer_parameters() {
+        let mut cs = CutSetting {
+            index: 0,
+            name: "Outline".to_string(),
+            speed: Some(25.0),
+            max_power: Some(80.0),
+            num_passes: Some(2),
+            ..Default::default()
+        };
+        cs.params.insert("constPower".to_string(), "1".to_string());
+
+        let gcode = lbrn2_to_gcode(&square_project(vec![cs]), &GcodeOptions::default());
+        assert!(gcode.contains("; Layer C00 (Outline): 25 mm/s, 80% power, 2 passes"));
+        assert!(gcode.contains("M3 S0\n"));
+        assert!(gcode.contains("; Bounds: X8 Y19 to X12 Y21\n"));
+        assert_eq!(gcode.matches("G1 X12 Y19 S800 F1500\n").count(), 2);
+        assert!(gcode.ends_with("M5\nG0 X0 Y0\nM2\n"));
+        assert_eq!(gcode.matches("M5").count(), 1);
+    }
+
+    #[test]
+    fn test_repeated_first_vertex_still_sets_power() {
+        let verts = [(0.0, 0.0), (0.0, 0.0), (5.0, 0.0), (5.0, 5.0)];
+        let path = Path::from_parts(
+            0,
+            XForm::identity(),
+            verts.iter().map(|&(x, y)| Vec2::new(x, y)).collect(),
+            (0..3)
+                .map(|i| PathPrimitive::Line {
+                    start_idx: i,
+                    end_idx: i + 1,
+                })
+                .collect(),
+        );
+        let project = LightBurnProject {
+            shapes: vec![Shape::Path(path)],
+            ..Default::default()
+        };
+
+        let gcode = lbrn2_to_gcode(&project, &GcodeOptions::default());
+        assert!(
+            gcode.contains("G0 X0 Y0\nG1 X5 Y0 S200 F600\nG1 X5 Y5\n"),
+            "{}",
+            gcode
+        );
+    }
+
+    #[test]
+    fn test_disabled_layers_are_skipped() {
+        let cs = CutSetting {
+            index: 0,
+            do_output: Some(false),
+            ..Default::default()
+        };
+        let options = GcodeOptions {
+            header: "G90".to_string(),
+            footer: String::new(),
+            ..Default::default()
+        };
+        assert_eq!(lbrn2_to_gcode(&square_project(vec![cs]), &options), "G90\n");
+    }
+}
@@ -0,0 +1,737 @@
+//! Shape geometry in world coordinates
+//!
+//! Turns shapes into contours of lines and cubics in LightBurn's Y-up
+//! workspace millimetres, and measures and flattens them.
+
+use super::bounds::{bezier_point, flatten_cubic};
+use super::svg::{ellipse_outline, rect_outline};
+use super::types::{Path, PathPrimitive, Shape, Vec2, XForm};
+
+/// Mirror about the X axis (LightBurn is Y-up, SVG is Y-down)
+pub const FLIP_Y: XForm = XForm {
+    a: 1.0,
+    b: 0.0,
+    c: 0.0,
+    d: -1.0,
+    e: 0.0,
+    f: 0.0,
+};
+
+/// A point in world coordinates
+pub type Point = (f64, f64);
+
+/// Straight-line distance between two points
+pub(crate) fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+pub(crate) fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+pub(crate) fn cross(a: Point, b: Point) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+/// Piece of a contour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line(Point, Point),
+    /// Start, first control point, second control point, end
+    Cubic(Point, Point, Point, Point),
+}
+
+impl Segment {
+    pub fn start(&self) -> Point {
+        match *self {
+            Segment::Line(p0, _) | Segment::Cubic(p0, ..) => p0,
+        }
+    }
+
+    pub fn end(&self) -> Point {
+        match *self {
+            Segment::Line(_, p1) | Segment::Cubic(.., p1) => p1,
+        }
+    }
+
+    /// Arc length in mm: exact for lines, integrated numerically for cubics
+    pub fn length(&self) -> f64 {
+        match *self {
+            Segment::Line(p0, p1) => distance(p0, p1),
+            Segment::Cubic(..) => integrate(
+                &|t| {
+                    let (dx, dy) = self.derivative(t);
+                    dx.hypot(dy)
+                },
+                0.0,
+                1.0,
+            ),
+        }
+    }
+
+    /// Part of the segment between parameters `t0` and `t1` in `0..=1`
+    pub fn subsegment(&self, t0: f64, t1: f64) -> Segment {
+        match *self {
+            Segment::Line(..) => Segment::Line(self.point(t0), self.point(t1)),
+            Segment::Cubic(..) => {
+                // The derivative scales with the parameter range taken
+                let scale = (t1 - t0) / 3.0;
+                let (p0, p1) = (self.point(t0), self.point(t1));
+                let (d0, d1) = (self.derivative(t0), self.derivative(t1));
+                Segment::Cubic(
+                    p0,
+                    (p0.0 + d0.0 * scale, p0.1 + d0.1 * scale),
+                    (p1.0 - d1.0 * scale, p1.1 - d1.1 * scale),
+                    p1,
+                )
+            }
+        }
+    }
+
+    /// Parameter at arc length `distance` mm from the start, clamped to the
+    /// segment
+    pub fn parameter_at(&self, distance: f64) -> f64 {
+        let length = self.length();
+        if distance <= 0.0 || length <= 0.0 {
+            return 0.0;
+        }
+        if distance >= length {
+            return 1.0;
+        }
+        if let Segment::Line(..) = self {
+            return distance / length;
+        }
+        // Newton's method, falling back to bisection when a step leaves the
+        // bracket around the root
+        let speed = |t: f64| {
+            let (dx, dy) = self.derivative(t);
+            dx.hypot(dy)
+        };
+        let (mut lo, mut hi) = (0.0, 1.0);
+        let mut t = distance / length;
+        for _ in 0..32 {
+            let error = integrate(&speed, 0.0, t) - distance;
+            if error.abs() <= 1e-9 {
+                break;
+            }
+            if error > 0.0 {
+                hi = t;
+            } else {
+                lo = t;
+            }
+            let step = t - error / speed(t);
+            t = if step > lo && step < hi {
+                step
+            } else {
+                (lo + hi) / 2.0
+            };
+        }
+        t
+    }
+
+    /// Point at parameter `t` in `0..=1`
+    fn point(&self, t: f64) -> Point {
+        match *self {
+            Segment::Line(p0, p1) => (p0.0 + (p1.0 - p0.0) * t, p0.1 + (p1.1 - p0.1) * t),
+            Segment::Cubic(p0, c0, c1, p1) => bezier_point(t, p0, c0, c1, p1),
+        }
+    }
+
+    /// Tangent at parameter `t`, scaled by the parameter speed
+    fn derivative(&self, t: f64) -> Point {
+        match *self {
+            Segment::Line(p0, p1) => (p1.0 - p0.0, p1.1 - p0.1),
+            Segment::Cubic(p0, c0, c1, p1) => {
+                let mt = 1.0 - t;
+                let (a, b, c) = (3.0 * mt * mt, 6.0 * mt * t, 3.0 * t * t);
+                (
+                    a * (c0.0 - p0.0) + b * (c1.0 - c0.0) + c * (p1.0 - c1.0),
+                    a * (c0.1 - p0.1) + b * (c1.1 - c0.1) + c * (p1.1 - c1.1),
+                )
+            }
+        }
+    }
+}
+
+/// Connected run of segments, each starting where the previous one ends
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BezPath {
+    pub segments: Vec<Segment>,
+    /// The last segment returns to the start of the first
+    pub closed: bool,
+}
+
+impl BezPath {
+    /// Closed contour of straight lines through `points`
+    pub fn polygon(points: &[Point]) -> Self {
+        let n = points.len();
+        let segments = (0..n)
+            .map(|i| Segment::Line(points[i], points[(i + 1) % n]))
+            .collect();
+        Self {
+            segments,
+            closed: n > 1,
+        }
+    }
+
+    /// Point the contour starts from
+    pub fn start(&self) -> Option<Point> {
+        self.segments.first().map(Segment::start)
+    }
+
+    /// Point the contour ends on, the start again when closed
+    pub fn end(&self) -> Option<Point> {
+        self.segments.last().map(Segment::end)
+    }
+
+    /// The same contour traversed the other way
+    pub fn reversed(&self) -> BezPath {
+        let segments = self
+            .segments
+            .iter()
+            .rev()
+            .map(|s| match *s {
+                Segment::Line(p0, p1) => Segment::Line(p1, p0),
+                Segment::Cubic(p0, c0, c1, p1) => Segment::Cubic(p1, c1, c0, p0),
+            })
+            .collect();
+        BezPath {
+            segments,
+            closed: self.closed,
+        }
+    }
+
+    /// Total arc length in mm
+    pub fn length(&self) -> f64 {
+        self.segments.iter().map(Segment::length).sum()
+    }
+
+    /// Enclosed area in mm², positive when the contour runs counterclockwise
+    /// in Y-up coordinates; zero for an open contour
+    ///
+    /// Exact for cubics: the area integrand of a cubic is a quintic, which
+    /// three-point Gauss-Legendre quadrature integrates without error.
+    pub fn signed_area(&self) -> f64 {
+        if !self.closed {
+            return 0.0;
+        }
+        let twice: f64 = self
+            .segments
+            .iter()
+            .map(|segment| {
+                gauss_legendre_3(&|t| {
+                    let (x, y) = segment.point(t);
+                    let (dx, dy) = segment.derivative(t);
+                    x * dy - y * dx
+                })
+            })
+            .sum();
+        twice / 2.0
+    }
+
+    /// Polyline within `tolerance` mm of the curves
+    ///
+    /// A closed contour's polyline ends on the point it starts from.
+    pub fn flatten(&self, tolerance: f64) -> Polyline {
+        let mut points = Vec::new();
+        if let Some(first) = self.segments.first() {
+            points.push(first.start());
+        }
+        for segment in &self.segments {
+            match *segment {
+                Segment::Line(_, p1) => points.push(p1),
+                Segment::Cubic(p0, c0, c1, p1) => {
+                    flatten_cubic(p0, c0, c1, p1, tolerance, &mut points)
+                }
+            }
+        }
+        Polyline {
+            points,
+            closed: self.closed,
+        }
+    }
+}
+
+/// Flattened contour
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Polyline {
+    pub points: Vec<Point>,
+    /// The last point coincides with the first
+    pub closed: bool,
+}
+
+impl Polyline {
+    /// Total length of the polyline in mm
+    pub fn length(&self) -> f64 {
+        self.points.windows(2).map(|w| distance(w[0], w[1])).sum()
+    }
+
+    /// Enclosed area in mm², positive when the points run counterclockwise
+    /// in Y-up coordinates; zero for an open polyline
+    pub fn signed_area(&self) -> f64 {
+        if !self.closed {
+            return 0.0;
+        }
+        ring_area(&self.points)
+    }
+}
+
+/// Three-point Gauss-Legendre quadrature over `0..=1`
+fn gauss_legendre_3(f: &impl Fn(f64) -> f64) -> f64 {
+    let x = 0.5 * (3.0f64 / 5.0).sqrt();
+    (5.0 * f(0.5 - x) + 8.0 * f(0.5) + 5.0 * f(0.5 + x)) / 18.0
+}
+
+/// Five-point Gauss-Legendre quadrature over `a..=b`
+fn gauss_legendre_5(f: &impl Fn(f64) -> f64, a: f64, b: f64) -> f64 {
+    const NODES: [(f64, f64); 5] = [
+        (0.0, 0.568_888_888_888_888_9),
+        (-0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+        (0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+        (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+        (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    ];
+    let (mid, half) = ((a + b) / 2.0, (b - a) / 2.0);
+    half * NODES
+        .iter()
+        .map(|&(x, w)| w * f(mid + half * x))
+        .sum::<f64>()
+}
+
+/// Integral of a smooth function over `a..=b`, bisecting until the halves
+/// agree with the whole to within 1e-9
+pub(crate) fn integrate(f: &impl Fn(f64) -> f64, a: f64, b: f64) -> f64 {
+    fn refine(f: &impl Fn(f64) -> f64, a: f64, b: f64, whole: f64, depth: u32) -> f64 {
+        let mid = (a + b) / 2.0;
+        let (left, right) = (gauss_legendre_5(f, a, mid), gauss_legendre_5(f, mid, b));
+        if depth == 0 || (left + right - whole).abs() <= 1e-9 * whole.abs().max(1.0) {
+            return left + right;
+        }
+        refine(f, a, mid, left, depth - 1) + refine(f, mid, b, right, depth - 1)
+    }
+    refine(f, a, b, gauss_legendre_5(f, a, b), 16)
+}
+
+/// Distance from `p` to the segment from `a` to `b`
+pub(crate) fn segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (ab, ap) = ((b.0 - a.0, b.1 - a.1), (p.0 - a.0, p.1 - a.1));
+    let len2 = ab.0 * ab.0 + ab.1 * ab.1;
+    if len2 == 0.0 {
+        return ap.0.hypot(ap.1);
+    }
+    let t = ((ap.0 * ab.0 + ap.1 * ab.1) / len2).clamp(0.0, 1.0);
+    (ap.0 - t * ab.0).hypot(ap.1 - t * ab.1)
+}
+
+/// Drop repeated points of a closed polyline, and points within `eps` of
+/// the segment between their neighbours, so tiny jogs and leftover split
+/// points do not end up as corners
+pub(crate) fn simplify(points: &[Point], eps: f64) -> Vec<Point> {
+    let same = |p: Point, q: Point| distance(p, q) <= 1e-9;
+    let mut ring: Vec<Point> = Vec::with_capacity(points.len());
+    for &p in points {
+        if ring.last().is_some_and(|&q| same(p, q)) {
+            continue;
+        }
+        while ring.len() >= 2
+            && segment_distance(ring[ring.len() - 1], ring[ring.len() - 2], p) <= eps
+        {
+            ring.pop();
+        }
+        ring.push(p);
+    }
+
+    // Close the seam between the last point and the first
+    while ring.len() > 1 && same(ring[0], ring[ring.len() - 1]) {
+        ring.pop();
+    }
+    while ring.len() >= 3
+        && segment_distance(ring[ring.len() - 1], ring[ring.len() - 2], ring[0]) <= eps
+    {
+        ring.pop();
+    }
+    while ring.len() >= 3 && segment_distance(ring[0], ring[ring.len() - 1], ring[1]) <= eps {
+        ring.remove(0);
+    }
+    ring
+}
+
+/// Signed area of a closed polyline in mm², positive when it runs
+/// counterclockwise in Y-up coordinates
+pub(crate) fn ring_area(ring: &[Point]) -> f64 {
+    let twice: f64 = ring
+        .iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(&p, &q)| cross(p, q))
+        .sum();
+    twice / 2.0
+}
+
+/// Whether `p` lies inside a closed polyline, by the even-odd rule
+pub(crate) fn ring_contains(ring: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        if (a.1 > p.1) != (b.1 > p.1) {
+            let x = a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+            if x > p.0 {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Whether each of `(cut_index, ring)` is a hole: its first point lies
+/// inside an odd number of the other rings on its layer. Empty rings are
+/// never holes and enclose nothing.
+pub(crate) fn even_odd_holes(rings: &[(i32, &[Point])]) -> Vec<bool> {
+    rings
+        .iter()
+        .enumerate()
+        .map(|(i, &(layer, ring))| {
+            let Some(&probe) = ring.first() else {
+                return false;
+            };
+            let depth = rings
+                .iter()
+                .enumerate()
+                .filter(|&(j, &(other_layer, other))| {
+                    j != i && other_layer == layer && ring_contains(other, probe)
+                })
+                .count();
+            depth % 2 == 1
+        })
+        .collect()
+}
+
+impl Shape {
+    /// Contours of the shape with its transform and `parent` applied
+    ///
+    /// Bitmaps, and text saved without a `BackupPath`, have no contours.
+    /// Primitives referring to missing vertices are skipped.
+    pub fn to_bezpath(&self, parent: &XForm) -> Vec<BezPath> {
+        shape_bezpaths(self, parent, &mut Vec::new())
+    }
+
+    /// [`to_bezpath`](Self::to_bezpath) flattened to polylines within
+    /// `tolerance` mm
+    pub fn flatten(&self, parent: &XForm, tolerance: f64) -> Vec<Polyline> {
+        self.to_bezpath(parent)
+            .iter()
+            .map(|path| path.flatten(tolerance))
+            .collect()
+    }
+}
+
+/// Contours of a shape, logging primitives that had to be skipped
+pub(crate) fn shape_bezpaths(shape: &Shape, parent: &XForm, log: &mut Vec<String>) -> Vec<BezPath> {
+    // Skipping the identity keeps coordinates bit for bit, down to -0.0
+    let xform = if *parent == XForm::identity() {
+        *shape.xform()
+    } else {
+        parent.compose(shape.xform())
+    };
+
+    match shape {
+        Shape::Rect(rect) => path_bezpaths(&rect_outline(rect), &xform, log),
+        Shape::Ellipse(ellipse) => path_bezpaths(&ellipse_outline(ellipse), &xform, log),
+        Shape::Path(path) => path_bezpaths(path, &xform, log),
+        Shape::Bitmap(_) => Vec::new(),
+        Shape::Text(text) => match &text.backup_path {
+            Some(outline) => path_bezpaths(outline, &xform, log),
+            None => Vec::new(),
+        },
+        Shape::Group(group) => group
+            .children
+            .iter()
+            .flat_map(|child| shape_bezpaths(child, &xform, log))
+            .collect(),
+    }
+}
+
+/// Split a path into connected contours with `xform` applied
+///
+/// A new contour starts wherever a primitive does not continue from the
+/// previous one; a contour is closed when it ends on the vertex it started
+/// from.
+pub(crate) fn path_bezpaths(path: &Path, xform: &XForm, log: &mut Vec<String>) -> Vec<BezPath> {
+    let verts = &path.parsed_verts;
+    let tx = |x: f64, y: f64| xform.transform_point(x, y);
+
+    if path.is_line_closed() {
+        let points: Vec<Point> = verts.iter().map(|v| tx(v.x, v.y)).collect();
+        if points.len() < 2 {
+            return Vec::new();
+        }
+        return vec![BezPath::polygon(&points)];
+    }
+
+    let mut paths = Vec::new();
+    let mut current: Vec<Segment> = Vec::new();
+    let mut first_idx: Option<usize> = None;
+    let mut last_idx: Option<usize> = None;
+
+    for prim in path.parsed_primitives.iter() {
+        let (PathPrimitive::Line { start_idx, end_idx }
+        | PathPrimitive::Bezier { start_idx, end_idx }) = *prim;
+        let (Some(v0), Some(v1)) = (verts.get(start_idx), verts.get(end_idx)) else {
+            log.push(format!(
+                "Invalid primitive indices {}, {}, skipping",
+                start_idx, end_idx
+            ));
+            continue;
+        };
+
+        if last_idx != Some(start_idx) {
+            if !current.is_empty() {
+                paths.push(BezPath {
+                    segments: std::mem::take(&mut current),
+                    closed: first_idx == last_idx,
+                });
+            }
+            first_idx = Some(start_idx);
+        }
+
+        let segment = match (prim, v0.c0x, v0.c0y, v1.c1x, v1.c1y) {
+            (PathPrimitive::Bezier { .. }, Some(c0x), Some(c0y), Some(c1x), Some(c1y)) => {
+                Segment::Cubic(tx(v0.x, v0.y), tx(c0x, c0y), tx(c1x, c1y), tx(v1.x, v1.y))
+            }
+            _ => Segment::Line(tx(v0.x, v0.y), tx(v1.x, v1.y)),
+        };
+        current.push(segment);
+        last_idx = Some(end_idx);
+    }
+
+    if !current.is_empty() {
+        paths.push(BezPath {
+            segments: current,
+            closed: first_idx == last_idx,
+        });
+    }
+    paths
+}
+
+/// Path through world-space contours for a shape placed by `xform`, whose
+/// full transform including any groups is `world`; `None` when `world`
+/// cannot be inverted
+pub(crate) fn bezpaths_to_path(
+    cut_index: i32,
+    xform: XForm,
+    world: &XForm,
+    paths: &[BezPath],
+) -> Option<Path> {
+    let inverse = world.inverse()?;
+    let local = |p: Point| inverse.transform_point(p.0, p.1);
+
+    let mut verts: Vec<Vec2> = Vec::new();
+    let mut prims = Vec::new();
+    for path in paths {
+        let first = verts.len();
+        let count = path.segments.len();
+        for segment in &path.segments {
+            let (x, y) = local(segment.start());
+            verts.push(Vec2::new(x, y));
+        }
+        if !path.closed
+            && let Some(last) = path.segments.last()
+        {
+            let (x, y) = local(last.end());
+            verts.push(Vec2::new(x, y));
+        }
+
+        for (k, segment) in path.segments.iter().enumerate() {
+            let start_idx = first + k;
+            let end_idx = if path.closed {
+                first + (k + 1) % count
+            } else {
+                start_idx + 1
+            };
+            match *segment {
+                Segment::Line(..) => prims.push(PathPrimitive::Line { start_idx, end_idx }),
+                Segment::Cubic(_, c0, c1, _) => {
+                    let (c0, c1) = (local(c0), local(c1));
+                    verts[start_idx].c0x = Some(c0.0);
+                    verts[start_idx].c0y = Some(c0.1);
+                    verts[end_idx].c1x = Some(c1.0);
+                    verts[end_idx].c1y = Some(c1.1);
+                    prims.push(PathPrimitive::Bezier { start_idx, end_idx });
+                }
+            }
+        }
+    }
+    Some(Path::from_parts(cut_index, xform, verts, prims))
+}
+
+/// A leaf shape with its workspace contours, see [`collect_leaves`]
+pub(crate) struct Leaf<'a> {
+    pub shape: &'a Shape,
+    /// Transform to the workspace, including every enclosing group
+    pub world: XForm,
+    pub contours: Vec<BezPath>,
+}
+
+impl Leaf<'_> {
+    /// Path through workspace contours, placed like the leaf; `None` when
+    /// its transform cannot be inverted
+    pub fn path(&self, contours: &[BezPath]) -> Option<Shape> {
+        bezpaths_to_path(
+            self.shape.cut_index(),
+            *self.shape.xform(),
+            &self.world,
+            contours,
+        )
+        .map(Shape::Path)
+    }
+}
+
+/// Gather every leaf shape below `shapes` in depth-first order, logging
+/// primitives that refer to missing vertices
+pub(crate) fn collect_leaves<'a>(
+    shapes: &'a [Shape],
+    parent: &XForm,
+    out: &mut Vec<Leaf<'a>>,
+    log: &mut Vec<String>,
+) {
+    for shape in shapes {
+        let world = parent.compose(shape.xform());
+        if let Shape::Group(group) = shape {
+            collect_leaves(&group.children, &world, out, log);
+            continue;
+        }
+        out.push(Leaf {
+            shape,
+            world,
+            contours: shape_bezpaths(shape, parent, log),
+        });
+    }
+}
+
+/// Replace leaf shapes depth-first in the same order as [`collect_leaves`],
+/// `None` keeping a leaf as it is; groups emptied by the replacement are
+/// removed
+pub(crate) fn splice_leaves(
+    shapes: &mut Vec<Shape>,
+    replacements: &mut impl Iterator<Item = Option<Vec<Shape>>>,
+) {
+    let mut kept = Vec::with_capacity(shapes.len());
+    for mut shape in shapes.drain(..) {
+        if let Shape::Group(group) = &mut shape {
+            let was_empty = group.children.is_empty();
+            splice_leaves(&mut group.children, replacements);
+            if was_empty || !group.children.is_empty() {
+                kept.push(shape);
+            }
+            continue;
+        }
+        match replacements.next().flatten() {
+            Some(replacement) => kept.extend(replacement),
+            None => kept.push(shape),
+        }
+    }
+    *shapes = kept;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::translate;
+    use crate::lbrn2::types::{Ellipse, Group, Rect, Vec2};
+
+    #[test]
+    fn test_group_transforms_and_y_flip() {
+        let group = Shape::Group(Group {
+            cut_index: 0,
+            xform: translate(100.0, 0.0),
+            children: vec![Shape::Rect(Rect {
+                cut_index: 0,
+                xform: translate(0.0, 10.0),
+                w: 4.0,
+                h: 2.0,
+                cr: 0.0,
+            })],
+        });
+
+        let paths = group.to_bezpath(&XForm::identity());
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].closed);
+        assert_eq!(
+            paths[0].segments[0],
+            Segment::Line((98.0, 9.0), (102.0, 9.0))
+        );
+
+        let flipped = group.flatten(&FLIP_Y, 0.01);
+        assert_eq!(flipped[0].points.first(), Some(&(98.0, -9.0)));
+        assert_eq!(flipped[0].points.last(), Some(&(98.0, -9.0)));
+        // Mirroring reverses the winding
+        assert_eq!(flipped[0].signed_area(), -8.0);
+    }
+
+    #[test]
+    fn test_curved_outlines_measure_close_to_exact() {
+        let circle = Shape::Ellipse(Ellipse {
+            cut_index: 0,
+            xform: XForm::identity(),
+            rx: 10.0,
+            ry: 10.0,
+        });
+        let polyline = &circle.flatten(&XForm::identity(), 0.001)[0];
+        assert!(polyline.closed);
+        assert!((polyline.length() - 20.0 * std::f64::consts::PI).abs() < 0.05);
+        assert!((polyline.signed_area() - 100.0 * std::f64::consts::PI).abs() < 0.1);
+
+        let rounded = Shape::Rect(Rect {
+            cut_index: 0,
+            xform: XForm::identity(),
+            w: 20.0,
+            h: 10.0,
+            cr: 2.0,
+        });
+        let paths = rounded.to_bezpath(&XForm::identity());
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].closed);
+        let polyline = paths[0].flatten(0.001);
+        let exact = 200.0 - (4.0 - std::f64::consts::PI) * 4.0;
+        assert!((polyline.signed_area() - exact).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_exact_length_and_area_of_curves() {
+        let circle = Shape::Ellipse(Ellipse {
+            cut_index: 0,
+            xform: XForm::identity(),
+            rx: 10.0,
+            ry: 10.0,
+        });
+        let path = &circle.to_bezpath(&XForm::identity())[0];
+        let polyline = path.flatten(1e-6);
+        assert!((path.length() - polyline.length()).abs() < 1e-4);
+        assert!((path.signed_area() - polyline.signed_area()).abs() < 1e-3);
+
+        // A cubic with collinear, evenly spaced controls is a line
+        let straight = Segment::Cubic((0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0));
+        assert!((straight.length() - 18f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_path_splits_into_open_and_closed_contours() {
+        let verts = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(20.0, 0.0),
+            Vec2::new(30.0, 0.0),
+        ];
+        let line = |start_idx, end_idx| PathPrimitive::Line { start_idx, end_idx };
+        let prims = vec![line(0, 1), line(1, 2), line(2, 0), line(3, 4), line(4, 9)];
+        let path = Path::from_parts(0, XForm::identity(), verts, prims);
+
+        let mut log = Vec::new();
+        let paths = path_bezpaths(&path, &XForm::identity(), &mut log);
+        assert_eq!(log, vec!["Invalid primitive indices 4, 9, skipping"]);
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].closed);
+        assert!(!paths[1].closed);
+        assert_eq!(paths[1].flatten(0.01).length(), 10.0);
+        assert_eq!(paths[1].flatten(0.01).signed_area(), 0.0);
+    }
+}
@@ -0,0 +1,468 @@
+//! Joining open path fragments
+//!
+//! Chains open contours whose endpoints meet into longer or closed paths.
+
+use super::geometry::{BezPath, Point, Segment, collect_leaves, distance, splice_leaves};
+use super::types::{LightBurnProject, Shape, XForm};
+use std::collections::HashMap;
+
+/// Counts of open and closed contours before and after [`join_paths`] or
+/// [`close_paths`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JoinReport {
+    /// Open contours in the input
+    pub open_before: usize,
+    /// Open contours left in the output
+    pub open_after: usize,
+    /// Contours the operation closed
+    pub closed: usize,
+}
+
+/// Move the start of a segment onto `p`, dragging its first control point
+/// along
+fn set_start(segment: &mut Segment, p: Point) {
+    match segment {
+        Segment::Line(p0, _) => *p0 = p,
+        Segment::Cubic(p0, c0, _, _) => {
+            *c0 = (c0.0 + p.0 - p0.0, c0.1 + p.1 - p0.1);
+            *p0 = p;
+        }
+    }
+}
+
+/// Move the end of a segment onto `p`, dragging its last control point
+/// along
+fn set_end(segment: &mut Segment, p: Point) {
+    match segment {
+        Segment::Line(_, p1) => *p1 = p,
+        Segment::Cubic(_, _, c1, p1) => {
+            *c1 = (c1.0 + p.0 - p1.0, c1.1 + p.1 - p1.1);
+            *p1 = p;
+        }
+    }
+}
+
+/// Whether open segments end within `tolerance` of where they start, and
+/// are more than a single straight line doubling back on itself
+fn loops_back(segments: &[Segment], tolerance: f64) -> bool {
+    match (segments.first(), segments.last()) {
+        (Some(first), Some(last)) => {
+            distance(first.start(), last.end()) <= tolerance
+                && (segments.len() > 1 || matches!(first, Segment::Cubic(..)))
+        }
+        _ => false,
+    }
+}
+
+/// Close a contour onto its start when its end is that close
+fn close_contour(path: &mut BezPath, tolerance: f64) -> bool {
+    if path.closed || !loops_back(&path.segments, tolerance) {
+        return false;
+    }
+    let start = path.segments[0].start();
+    if let Some(last) = path.segments.last_mut() {
+        set_end(last, start);
+    }
+    path.closed = true;
+    true
+}
+
+/// Endpoints of the fragments, bucketed on a grid of the tolerance
+struct Endpoints {
+    cell: f64,
+    cells: HashMap<(i64, i64), Vec<(usize, bool)>>,
+}
+
+impl Endpoints {
+    fn new(fragments: &[BezPath], tolerance: f64) -> Self {
+        let mut endpoints = Self {
+            cell: tolerance.max(1e-9),
+            cells: HashMap::new(),
+        };
+        for (i, fragment) in fragments.iter().enumerate() {
+            for (p, at_end) in [(fragment.start(), false), (fragment.end(), true)] {
+                if let Some(p) = p {
+                    let key = endpoints.key(p);
+                    endpoints.cells.entry(key).or_default().push((i, at_end));
+                }
+            }
+        }
+        endpoints
+    }
+
+    fn key(&self, p: Point) -> (i64, i64) {
+        (
+            (p.0 / self.cell).floor() as i64,
+            (p.1 / self.cell).floor() as i64,
+        )
+    }
+
+    /// Closest endpoint of an unused fragment within `tolerance` of `p`,
+    /// and whether it is the fragment's end rather than its start
+    fn nearest(
+        &self,
+        p: Point,
+        fragments: &[BezPath],
+        used: &[bool],
+        tolerance: f64,
+    ) -> Option<(usize, bool)> {
+        let (kx, ky) = self.key(p);
+        let mut best: Option<(usize, bool, f64)> = None;
+        for key in (kx - 1..=kx + 1).flat_map(|x| (ky - 1..=ky + 1).map(move |y| (x, y))) {
+            for &(i, at_end) in self.cells.get(&key).into_iter().flatten() {
+                if used[i] {
+                    continue;
+                }
+                let fragment = &fragments[i];
+                let q = if at_end {
+                    fragment.end()
+                } else {
+                    fragment.start()
+                };
+                let d = distance(p, q.unwrap_or_default());
+                if d <= tolerance && best.is_none_or(|(_, _, best)| d < best) {
+                    best = Some((i, at_end, d));
+                }
+            }
+        }
+        best.map(|(i, at_end, _)| (i, at_end))
+    }
+}
+
+/// Joined contour and the fragments it was built from
+struct Chain {
+    path: BezPath,
+    fragments: Vec<usize>,
+}
+
+/// Chain open fragments whose endpoints meet, in the order the fragments
+/// come, growing each chain forwards and then backwards until it closes
+/// or nothing more connects
+fn chain_fragments(fragments: &[BezPath], tolerance: f64) -> Vec<Chain> {
+    let endpoints = Endpoints::new(fragments, tolerance);
+    let mut used = vec![false; fragments.len()];
+    let mut chains = Vec::new();
+
+    for first in 0..fragments.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let mut segments = fragments[first].segments.clone();
+        let mut members = vec![first];
+
+        while !loops_back(&segments, tolerance) {
+            let Some(end) = segments.last().map(Segment::end) else {
+                break;
+            };
+            let Some((i, at_end)) = endpoints.nearest(end, fragments, &used, tolerance) else {
+                break;
+            };
+            used[i] = true;
+            members.push(i);
+            let mut next = if at_end {
+                fragments[i].reversed()
+            } else {
+                fragments[i].clone()
+            };
+            set_start(&mut next.segments[0], end);
+            segments.append(&mut next.segments);
+        }
+
+        while !loops_back(&segments, tolerance) {
+            let Some(start) = segments.first().map(Segment::start) else {
+                break;
+            };
+            let Some((i, at_end)) = endpoints.nearest(start, fragments, &used, tolerance) else {
+                break;
+            };
+            used[i] = true;
+            members.push(i);
+            let mut prev = if at_end {
+                fragments[i].clone()
+            } else {
+                fragments[i].reversed()
+            };
+            if let Some(last) = prev.segments.last_mut() {
+                set_end(last, start);
+            }
+            prev.segments.append(&mut segments);
+            segments = prev.segments;
+        }
+
+        let mut path = BezPath {
+            segments,
+            closed: false,
+        };
+        close_contour(&mut path, tolerance);
+        chains.push(Chain {
+            path,
+            fragments: members,
+        });
+    }
+    chains
+}
+
+/// Join open contours on each layer whose endpoints lie within `tolerance`
+/// mm of each other
+///
+/// Fragments are turned around where needed, and a chain that ends within
+/// `tolerance` of its start is closed. Each chain becomes a single path in
+/// the place of its first fragment, which keeps that fragment's transform.
+/// Closed contours stay as they are, and paths none of whose contours were
+/// joined or closed are left untouched. Also returns warnings about
+/// primitives that refer to missing vertices.
+pub fn join_paths(
+    project: &LightBurnProject,
+    tolerance: f64,
+) -> (LightBurnProject, JoinReport, Vec<String>) {
+    let mut log = Vec::new();
+    let mut leaves = Vec::new();
+    collect_leaves(&project.shapes, &XForm::identity(), &mut leaves, &mut log);
+
+    // Open contours of every path with the leaf they came from
+    let mut fragments: Vec<(usize, BezPath)> = Vec::new();
+    for (i, leaf) in leaves.iter().enumerate() {
+        if !matches!(leaf.shape, Shape::Path(_)) {
+            continue;
+        }
+        for contour in &leaf.contours {
+            if !contour.closed {
+                fragments.push((i, contour.clone()));
+            }
+        }
+    }
+
+    let mut report = JoinReport {
+        open_before: fragments.len(),
+        ..Default::default()
+    };
+    let mut rewrite = vec![false; leaves.len()];
+    let mut chains: Vec<Vec<BezPath>> = vec![Vec::new(); leaves.len()];
+    let mut layers: Vec<i32> = fragments
+        .iter()
+        .map(|(i, _)| leaves[*i].shape.cut_index())
+        .collect();
+    layers.sort_unstable();
+    layers.dedup();
+    for cut_index in layers {
+        let (owners, paths): (Vec<usize>, Vec<BezPath>) = fragments
+            .iter()
+            .filter(|(i, _)| leaves[*i].shape.cut_index() == cut_index)
+            .cloned()
+            .unzip();
+        for chain in chain_fragments(&paths, tolerance) {
+            if chain.path.closed {
+                report.closed += 1;
+            } else {
+                report.open_after += 1;
+            }
+            if chain.path.closed || chain.fragments.len() > 1 {
+                for &f in &chain.fragments {
+                    rewrite[owners[f]] = true;
+                }
+            }
+            chains[owners[chain.fragments[0]]].push(chain.path);
+        }
+    }
+
+    let mut replacements = leaves
+        .iter()
+        .zip(chains)
+        .enumerate()
+        .map(|(i, (leaf, chains))| {
+            if !rewrite[i] {
+                return None;
+            }
+            let closed: Vec<BezPath> = leaf.contours.iter().filter(|c| c.closed).cloned().collect();
+            let mut shapes = Vec::new();
+            if !closed.is_empty() {
+                shapes.extend(leaf.path(&closed));
+            }
+            for chain in chains {
+                shapes.extend(leaf.path(std::slice::from_ref(&chain)));
+            }
+            Some(shapes)
+        });
+
+    let mut project = project.clone();
+    splice_leaves(&mut project.shapes, &mut replacements);
+    (project, report, log)
+}
+
+/// Close open contours whose end lies within `tolerance` mm of their start
+///
+/// The end is moved onto the start. Only the paths holding such a contour
+/// are rewritten. Warnings are returned as for [`join_paths`].
+pub fn close_paths(
+    project: &LightBurnProject,
+    tolerance: f64,
+) -> (LightBurnProject, JoinReport, Vec<String>) {
+    let mut log = Vec::new();
+    let mut leaves = Vec::new();
+    collect_leaves(&project.shapes, &XForm::identity(), &mut leaves, &mut log);
+
+    let mut report = JoinReport::default();
+    let replacements: Vec<Option<Vec<Shape>>> = leaves
+        .iter()
+        .map(|leaf| {
+            if !matches!(leaf.shape, Shape::Path(_)) {
+                return None;
+            }
+            let mut contours = leaf.contours.clone();
+            let mut closed = 0;
+            for contour in &mut contours {
+                if !contour.closed {
+                    report.open_before += 1;
+                    if close_contour(contour, tolerance) {
+                        closed += 1;
+                    } else {
+                        report.open_after += 1;
+                    }
+                }
+            }
+            report.closed += closed;
+            if closed == 0 {
+                return None;
+            }
+            leaf.path(&contours).map(|shape| vec![shape])
+        })
+        .collect();
+
+    let mut project = project.clone();
+    splice_leaves(&mut project.shapes, &mut replacements.into_iter());
+    (project, report, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::{contours, translate};
+    use crate::lbrn2::types::{Group, Path, PathPrimitive, Vec2};
+
+    /// Path of separate straight lines, each with its own pair of vertices
+    fn lines(cut_index: i32, xform: XForm, lines: &[(Point, Point)]) -> Shape {
+        let mut verts = Vec::new();
+        let mut prims = Vec::new();
+        for &(a, b) in lines {
+            prims.push(PathPrimitive::Line {
+                start_idx: verts.len(),
+                end_idx: verts.len() + 1,
+            });
+            verts.push(Vec2::new(a.0, a.1));
+            verts.push(Vec2::new(b.0, b.1));
+        }
+        Shape::Path(Path::from_parts(cut_index, xform, verts, prims))
+    }
+
+    #[test]
+    fn test_fragments_join_into_closed_outline() {
+        // A square drawn as four lines in two paths, one of them backwards
+        // and off by a rounding error, plus a lone line on another layer
+        let project = LightBurnProject {
+            shapes: vec![
+                lines(
+                    0,
+                    XForm::identity(),
+                    &[((0.0, 0.0), (10.0, 0.0)), ((0.0, 10.0), (10.0, 10.0))],
+                ),
+                lines(1, XForm::identity(), &[((0.0, 0.0), (0.0, 10.0))]),
+                lines(
+                    0,
+                    XForm::identity(),
+                    &[((10.0, 0.0), (10.0, 10.001)), ((0.0, 0.0), (0.0, 10.0))],
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let (joined, report, _) = join_paths(&project, 0.01);
+        assert_eq!(
+            report,
+            JoinReport {
+                open_before: 5,
+                open_after: 1,
+                closed: 1,
+            }
+        );
+        assert_eq!(joined.shapes.len(), 2);
+        let square = contours(&joined.shapes[..1]);
+        assert_eq!(square.len(), 1);
+        assert!(square[0].closed);
+        assert_eq!(square[0].segments.len(), 4);
+        assert!((square[0].signed_area().abs() - 100.0).abs() < 0.01);
+        assert_eq!(joined.shapes[1], project.shapes[1]);
+
+        // Too far apart to join at a tighter tolerance
+        let (_, report, _) = join_paths(&project, 1e-4);
+        assert_eq!(report.closed, 0);
+    }
+
+    #[test]
+    fn test_joined_path_keeps_first_fragment_placement() {
+        let offset = translate(5.0, 5.0);
+        let group = Shape::Group(Group {
+            cut_index: 0,
+            xform: offset,
+            children: vec![lines(0, XForm::identity(), &[((0.0, 0.0), (10.0, 0.0))])],
+        });
+        let project = LightBurnProject {
+            // The second fragment is already in workspace coordinates
+            shapes: vec![
+                group,
+                lines(0, XForm::identity(), &[((15.0, 5.0), (15.0, 15.0))]),
+            ],
+            ..Default::default()
+        };
+
+        let (joined, report, _) = join_paths(&project, 0.01);
+        assert_eq!(report.open_after, 1);
+        assert_eq!(joined.shapes.len(), 1);
+        let Shape::Group(group) = &joined.shapes[0] else {
+            panic!("expected the group to stay");
+        };
+        let path = contours(&group.children[..1]);
+        assert_eq!(path[0].start(), Some((0.0, 0.0)));
+        assert_eq!(path[0].end(), Some((10.0, 10.0)));
+        let world = contours(&joined.shapes[..1]);
+        assert_eq!(world[0].end(), Some((15.0, 15.0)));
+    }
+
+    #[test]
+    fn test_close_nearly_closed_paths() {
+        let triangle = [
+            ((0.0, 0.0), (10.0, 0.0)),
+            ((10.0, 0.0), (5.0, 8.0)),
+            ((5.0, 8.0), (0.005, 0.0)),
+        ];
+        let mut verts: Vec<Vec2> = triangle.iter().map(|(a, _)| Vec2::new(a.0, a.1)).collect();
+        verts.push(Vec2::new(0.005, 0.0));
+        let prims = (0..3)
+            .map(|i| PathPrimitive::Line {
+                start_idx: i,
+                end_idx: i + 1,
+            })
+            .collect();
+        let project = LightBurnProject {
+            shapes: vec![
+                Shape::Path(Path::from_parts(0, XForm::identity(), verts, prims)),
+                lines(0, XForm::identity(), &[((20.0, 0.0), (30.0, 0.0))]),
+            ],
+            ..Default::default()
+        };
+
+        let (closed, report, _) = close_paths(&project, 0.01);
+        assert_eq!(
+            report,
+            JoinReport {
+                open_before: 2,
+                open_after: 1,
+                closed: 1,
+            }
+        );
+        let triangle = contours(&closed.shapes[..1]);
+        assert!(triangle[0].closed);
+        assert_eq!(triangle[0].end(), Some((0.0, 0.0)));
+        assert_eq!(closed.shapes[1], project.shapes[1]);
+    }
+}
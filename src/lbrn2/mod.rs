@@ -1,18 +1,65 @@
 //! LBRN2 to SVG conversion module
 //!
-//! This module provides functionality to parse LightBurn LBRN2 project files
-//! and convert them to SVG format.
+//! This module provides functionality to parse LightBurn LBRN2 project files,
+//! either whole or streamed shape by shape, convert them to SVG, DXF or
+//! G-code, flatten shapes to world-space contours, measure jobs, compensate for
+//! kerf, combine and weld closed shapes, join path fragments, remove duplicate
+//! and overlapping lines, add holding tabs, optimize the cut order, import SVG
+//! and DXF artwork and write LBRN2 back out.
 
+pub mod bitmap;
+pub mod boolean;
 pub mod bounds;
+pub mod dedupe;
+pub mod dxf;
+pub mod dxf_import;
+pub mod error;
+pub mod gcode;
+pub mod geometry;
+pub mod join;
+pub mod offset;
+pub mod order;
 pub mod parser;
 pub mod path;
+pub mod reader;
+pub mod stats;
 pub mod style;
 pub mod svg;
+pub mod svg_import;
+pub mod tabs;
+#[cfg(test)]
+mod testing;
 pub mod types;
+pub mod writer;
 
 // Re-export main public API
+pub use boolean::{
+    BooleanOp, Region, boolean_polygons, boolean_shapes, weld_layers, weld_layers_with_warnings,
+};
+pub use dedupe::{Removal, remove_duplicates, remove_duplicates_with_report};
+pub use dxf::{DxfExportOptions, lbrn2_to_dxf, lbrn2_to_dxf_with_options};
+pub use dxf_import::{DxfImportOptions, dxf_to_lbrn2, dxf_to_lbrn2_with_warnings};
+pub use error::{Lbrn2Error, ListErrorKind, ListSyntaxError, SourcePos};
+pub use gcode::{GcodeOptions, LaserMode, lbrn2_to_gcode, lbrn2_to_gcode_with_warnings};
+pub use geometry::{BezPath, Polyline, Segment};
+pub use join::{JoinReport, close_paths, join_paths};
+pub use offset::{
+    JoinStyle, OffsetOptions, compensate_kerf, compensate_kerf_with_warnings, offset_polygon,
+};
+pub use order::{LoopDirection, OrderOptions, OrderReport, optimize_cut_order};
 pub use parser::{
-    parse_lbrn2_complete as parse_lbrn2, parse_prim_list, parse_vert_list, parse_xform,
+    parse_lbrn2_complete as parse_lbrn2, parse_lbrn2_with_warnings, parse_prim_list,
+    parse_vert_list, parse_xform, try_parse_prim_list, try_parse_vert_list, try_parse_xform,
+};
+pub use reader::{Lbrn2Reader, Shapes};
+pub use stats::{
+    LayerStats, ProjectStats, StatsOptions, project_stats, project_stats_with_options,
+};
+pub use svg::{
+    SvgAsset, SvgExportOptions, SvgUnits, lbrn2_to_svg, lbrn2_to_svg_layered,
+    lbrn2_to_svg_with_assets, lbrn2_to_svg_with_options,
 };
-pub use svg::lbrn2_to_svg;
+pub use svg_import::{SvgImportOptions, svg_to_lbrn2, svg_to_lbrn2_with_warnings};
+pub use tabs::{TabOptions, TabReport, add_layer_tabs, add_tabs};
 pub use types::*;
+pub use writer::write_lbrn2;
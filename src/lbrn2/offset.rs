@@ -0,0 +1,595 @@
+//! Polygon offsetting and kerf compensation
+//!
+//! Grows or shrinks closed outlines, moving holes the opposite way to parts.
+
+use super::geometry::{
+    BezPath, Leaf, Point, Segment, collect_leaves, cross, even_odd_holes, ring_area,
+    segment_distance, simplify, splice_leaves, sub,
+};
+use super::types::{LightBurnProject, Shape, XForm};
+use std::f64::consts::FRAC_PI_2;
+
+/// How offset edges meet at a convex corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JoinStyle {
+    /// Circular arc around the corner, the path the beam's edge follows
+    #[default]
+    Round,
+    /// Edges extended until they meet, up to the miter limit
+    Miter,
+    /// Edges connected by a straight line
+    Bevel,
+}
+
+/// Options for [`offset_polygon`] and [`compensate_kerf`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffsetOptions {
+    /// Corner treatment (default: round)
+    pub join: JoinStyle,
+    /// Longest miter as a multiple of the offset distance before a miter
+    /// join falls back to a bevel (default: 4)
+    pub miter_limit: f64,
+    /// Maximum distance between a curve and its flattened polyline in mm
+    /// (default: 0.01)
+    pub tolerance: f64,
+}
+
+impl Default for OffsetOptions {
+    fn default() -> Self {
+        Self {
+            join: JoinStyle::Round,
+            miter_limit: 4.0,
+            tolerance: 0.01,
+        }
+    }
+}
+
+/// Corner of an offset contour
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    p: Point,
+    /// Center of the arc arriving at this node, for round joins; `None`
+    /// when the edge from the previous node is straight
+    arc: Option<Point>,
+    /// Made where the offset crossed itself rather than from a corner
+    crossing: bool,
+}
+
+impl Node {
+    fn line(p: Point) -> Self {
+        Self {
+            p,
+            arc: None,
+            crossing: false,
+        }
+    }
+}
+
+/// Outward unit normal of an edge on a counterclockwise contour
+fn normal(from: Point, to: Point) -> Point {
+    let (dx, dy) = sub(to, from);
+    let len = dx.hypot(dy);
+    (dy / len, -dx / len)
+}
+
+/// Offset a closed polyline, growing the region it encloses by `distance`
+/// mm, or shrinking it when `distance` is negative
+///
+/// The winding of the points does not matter and is kept in the result. A
+/// shrinking contour may split into several or vanish altogether; the
+/// result is empty for fewer than three distinct points.
+pub fn offset_polygon(points: &[Point], distance: f64, options: &OffsetOptions) -> Vec<BezPath> {
+    let mut ring = simplify(points, options.tolerance / 2.0);
+    if ring.len() < 3 {
+        return Vec::new();
+    }
+
+    // Work counterclockwise, so outward is to the right of every edge
+    let area = ring_area(&ring);
+    if area == 0.0 {
+        return Vec::new();
+    }
+    let clockwise = area < 0.0;
+    if clockwise {
+        ring.reverse();
+    }
+
+    let nodes = offset_nodes(&ring, distance, options);
+    let mut loops = Vec::new();
+    split_loops(nodes, &mut loops);
+
+    let min_area = options.tolerance * options.tolerance;
+    let clearance = distance.abs() - options.tolerance;
+    loops
+        .into_iter()
+        // Loops running backwards are where the offset folded over itself,
+        // and loops whose corners mostly sit too close to the original were
+        // turned inside out
+        .filter(|loop_| ring_area(&loop_.iter().map(|n| n.p).collect::<Vec<_>>()) > min_area)
+        .filter(|loop_| {
+            let corners: Vec<Point> = loop_.iter().filter(|n| !n.crossing).map(|n| n.p).collect();
+            let stride = corners.len().div_ceil(16).max(1);
+            let (clear, sampled) = corners.iter().step_by(stride).fold((0, 0), |(c, n), &p| {
+                (
+                    c + usize::from(distance_to_ring(&ring, p) >= clearance),
+                    n + 1,
+                )
+            });
+            2 * clear >= sampled
+        })
+        .map(|ring| {
+            let path = bezpath(&ring);
+            if clockwise { path.reversed() } else { path }
+        })
+        .collect()
+}
+
+/// Distance from `p` to the nearest edge of a closed polyline
+fn distance_to_ring(ring: &[Point], p: Point) -> f64 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(&a, &b)| segment_distance(p, a, b))
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Offset every corner of a counterclockwise ring
+fn offset_nodes(ring: &[Point], d: f64, options: &OffsetOptions) -> Vec<Node> {
+    let n = ring.len();
+    let mut nodes = Vec::with_capacity(n);
+
+    for i in 0..n {
+        let (prev, p, next) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+        let (n_in, n_out) = (normal(prev, p), normal(p, next));
+        let q_in = (p.0 + d * n_in.0, p.1 + d * n_in.1);
+        let q_out = (p.0 + d * n_out.0, p.1 + d * n_out.1);
+        let turn = cross(sub(p, prev), sub(next, p));
+        let dot = n_in.0 * n_out.0 + n_in.1 * n_out.1;
+
+        // Where the two offset edges meet, if they are not antiparallel
+        let miter = (1.0 + dot > 1e-9).then(|| {
+            let k = d / (1.0 + dot);
+            (p.0 + k * (n_in.0 + n_out.0), p.1 + k * (n_in.1 + n_out.1))
+        });
+        // How far the miter overshoots the arc, as a multiple of the offset
+        let overshoot = miter.map(|_| (2.0 / (1.0 + dot)).sqrt());
+
+        // Edges pulled apart by the offset need a join; edges pushed into
+        // each other meet at their intersection
+        let opens = turn * d > 0.0;
+        match (opens, miter, overshoot) {
+            (false, Some(m), _) => nodes.push(Node::line(m)),
+            (true, Some(m), Some(r)) if d.abs() * (r - 1.0) <= options.tolerance => {
+                nodes.push(Node::line(m))
+            }
+            (true, Some(m), Some(r))
+                if options.join == JoinStyle::Miter && r <= options.miter_limit =>
+            {
+                nodes.push(Node::line(m))
+            }
+            (true, _, _) if options.join == JoinStyle::Round => {
+                nodes.push(Node::line(q_in));
+                push_arc(&mut nodes, p, q_in, q_out, options.tolerance);
+            }
+            _ => {
+                nodes.push(Node::line(q_in));
+                nodes.push(Node::line(q_out));
+            }
+        }
+    }
+    nodes
+}
+
+/// Nodes flattening the arc from `from` to `to` around `center`, the short
+/// way, to within `tolerance`
+///
+/// Every node remembers the center, so the arc is written as Beziers again
+/// once self-crossings have been cut away.
+fn push_arc(nodes: &mut Vec<Node>, center: Point, from: Point, to: Point, tolerance: f64) {
+    let (a, b) = (sub(from, center), sub(to, center));
+    let sweep = cross(a, b).atan2(a.0 * b.0 + a.1 * b.1);
+    let radius = a.0.hypot(a.1);
+    // A chord spanning angle s strays r * (1 - cos(s / 2)) from the arc
+    let step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+    let pieces = (sweep.abs() / step.max(1e-3)).ceil().clamp(1.0, 256.0) as usize;
+    for k in 1..=pieces {
+        let p = if k == pieces {
+            to
+        } else {
+            let (sin, cos) = (sweep * k as f64 / pieces as f64).sin_cos();
+            (
+                center.0 + a.0 * cos - a.1 * sin,
+                center.1 + a.0 * sin + a.1 * cos,
+            )
+        };
+        nodes.push(Node {
+            p,
+            arc: Some(center),
+            crossing: false,
+        });
+    }
+}
+
+/// A crossing between two edges that are not neighbours, as the edge
+/// indices and the crossing point
+///
+/// Edges are swept in order of their left end, so only edges whose x
+/// ranges overlap are compared.
+fn find_crossing(ring: &[Node]) -> Option<(usize, usize, Point)> {
+    let n = ring.len();
+    let edge = |i: usize| (ring[i].p, ring[(i + 1) % n].p);
+    let mut order: Vec<usize> = (0..n).collect();
+    let min_x = |i: usize| edge(i).0.0.min(edge(i).1.0);
+    order.sort_by(|&a, &b| min_x(a).total_cmp(&min_x(b)));
+
+    let mut active: Vec<usize> = Vec::new();
+    for &i in &order {
+        let (p, p2) = edge(i);
+        active.retain(|&j| {
+            let (q, q2) = edge(j);
+            q.0.max(q2.0) >= p.0.min(p2.0)
+        });
+        for &j in &active {
+            let (lo, hi) = (i.min(j), i.max(j));
+            if hi - lo == 1 || (lo == 0 && hi == n - 1) {
+                continue;
+            }
+            let (q, q2) = edge(j);
+            let (r, s) = (sub(p2, p), sub(q2, q));
+            let denom = cross(r, s);
+            if denom.abs() <= 1e-12 * r.0.hypot(r.1) * s.0.hypot(s.1) {
+                continue;
+            }
+            let t = cross(sub(q, p), s) / denom;
+            let u = cross(sub(q, p), r) / denom;
+            if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                return Some((lo, hi, (p.0 + t * r.0, p.1 + t * r.1)));
+            }
+        }
+        active.push(i);
+    }
+    None
+}
+
+/// Split a ring at its self-crossings into simple loops
+///
+/// Every split leaves two strictly smaller rings, so this terminates. Arcs
+/// cut by a split become straight edges.
+fn split_loops(ring: Vec<Node>, out: &mut Vec<Vec<Node>>) {
+    let mut pending = vec![ring];
+    while let Some(ring) = pending.pop() {
+        if ring.len() < 3 {
+            continue;
+        }
+        let Some((i, j, x)) = find_crossing(&ring) else {
+            out.push(ring);
+            continue;
+        };
+
+        let mut inner: Vec<Node> = ring[i + 1..=j].to_vec();
+        inner[0].arc = None;
+        let x = Node {
+            crossing: true,
+            ..Node::line(x)
+        };
+        inner.push(x);
+
+        let mut outer: Vec<Node> = ring[j + 1..].iter().chain(&ring[..=i]).copied().collect();
+        outer[0].arc = None;
+        outer.push(x);
+
+        pending.push(inner);
+        pending.push(outer);
+    }
+}
+
+/// Closed contour through the nodes, with each run of arc nodes written as
+/// cubic Beziers of at most a quarter turn
+fn bezpath(ring: &[Node]) -> BezPath {
+    let n = ring.len();
+    // Start on a straight edge so no arc wraps around the end of the ring
+    let first = ring.iter().position(|node| node.arc.is_none()).unwrap_or(0);
+    let node = |i: usize| ring[(first + i) % n];
+
+    let mut segments = Vec::new();
+    let mut i = 1;
+    while i <= n {
+        let from = node(i - 1).p;
+        let Some(center) = node(i).arc else {
+            segments.push(Segment::Line(from, node(i).p));
+            i += 1;
+            continue;
+        };
+        while i < n && node(i + 1).arc == Some(center) {
+            i += 1;
+        }
+        push_arc_segments(&mut segments, center, from, node(i).p);
+        i += 1;
+    }
+    BezPath {
+        segments,
+        closed: true,
+    }
+}
+
+/// Cubics approximating the arc from `from` to `to` around `center`, the
+/// short way
+fn push_arc_segments(segments: &mut Vec<Segment>, center: Point, from: Point, to: Point) {
+    let (a, b) = (sub(from, center), sub(to, center));
+    let sweep = cross(a, b).atan2(a.0 * b.0 + a.1 * b.1);
+    let pieces = (sweep.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+    let piece = sweep / pieces as f64;
+    let k = 4.0 / 3.0 * (piece / 4.0).tan();
+
+    let mut start = from;
+    for j in 1..=pieces {
+        let end = if j == pieces {
+            to
+        } else {
+            let (sin, cos) = (piece * j as f64).sin_cos();
+            (
+                center.0 + a.0 * cos - a.1 * sin,
+                center.1 + a.0 * sin + a.1 * cos,
+            )
+        };
+        let (u, v) = (sub(start, center), sub(end, center));
+        segments.push(Segment::Cubic(
+            start,
+            (start.0 - k * u.1, start.1 + k * u.0),
+            (end.0 + k * v.1, end.1 - k * v.0),
+            end,
+        ));
+        start = end;
+    }
+}
+
+/// Flattened workspace contours of a leaf, `None` when the shape is left
+/// alone
+fn leaf_rings(
+    leaf: &Leaf,
+    options: &OffsetOptions,
+    log: &mut Vec<String>,
+) -> Option<Vec<Vec<Point>>> {
+    match leaf.shape {
+        Shape::Bitmap(_) => None,
+        Shape::Text(text) if text.backup_path.is_none() => {
+            log.push(format!(
+                "Text \"{}\" left as is: no BackupPath outline to offset",
+                text.text
+            ));
+            None
+        }
+        _ if leaf.contours.iter().all(|p| p.closed) => Some(
+            leaf.contours
+                .iter()
+                .map(|p| p.flatten(options.tolerance).points)
+                .collect(),
+        ),
+        shape => {
+            log.push(format!(
+                "Shape on layer {} left as is: kerf needs closed contours",
+                shape.cut_index()
+            ));
+            None
+        }
+    }
+}
+
+/// Offset closed contours by half the kerf so parts cut to their drawn size
+///
+/// Outer contours grow and holes shrink by `kerf / 2` mm. A contour is a
+/// hole when an odd number of other closed contours on its layer enclose
+/// it. Shapes are replaced by paths in their own coordinates, keeping their
+/// transform; shapes with open contours, bitmaps and text without glyph
+/// outlines are left as they are.
+pub fn compensate_kerf(
+    project: &LightBurnProject,
+    kerf: f64,
+    options: &OffsetOptions,
+) -> LightBurnProject {
+    compensate_kerf_with_warnings(project, kerf, options).0
+}
+
+/// [`compensate_kerf`], also returning warnings about shapes left as they
+/// are
+pub fn compensate_kerf_with_warnings(
+    project: &LightBurnProject,
+    kerf: f64,
+    options: &OffsetOptions,
+) -> (LightBurnProject, Vec<String>) {
+    let mut log = Vec::new();
+    let mut leaves = Vec::new();
+    collect_leaves(&project.shapes, &XForm::identity(), &mut leaves, &mut log);
+    let rings: Vec<Option<Vec<Vec<Point>>>> = leaves
+        .iter()
+        .map(|leaf| leaf_rings(leaf, options, &mut log))
+        .collect();
+
+    let layered: Vec<(i32, &[Point])> = leaves
+        .iter()
+        .zip(&rings)
+        .flat_map(|(leaf, rings)| {
+            let cut_index = leaf.shape.cut_index();
+            rings
+                .iter()
+                .flatten()
+                .map(move |ring| (cut_index, ring.as_slice()))
+        })
+        .collect();
+    let mut holes = even_odd_holes(&layered).into_iter();
+
+    let replacements: Vec<Option<Vec<Shape>>> = leaves
+        .iter()
+        .zip(&rings)
+        .map(|(leaf, contours)| {
+            let paths: Vec<BezPath> = contours
+                .as_ref()?
+                .iter()
+                .zip(holes.by_ref())
+                .flat_map(|(ring, hole)| {
+                    let distance = if hole { -kerf } else { kerf } / 2.0;
+                    offset_polygon(ring, distance, options)
+                })
+                .collect();
+            if paths.is_empty() {
+                return Some(Vec::new());
+            }
+            leaf.path(&paths).map(|path| vec![path])
+        })
+        .collect();
+
+    let mut project = project.clone();
+    splice_leaves(&mut project.shapes, &mut replacements.into_iter());
+    log.dedup();
+    (project, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::{square, square_ring, translate};
+    use crate::lbrn2::types::Group;
+
+    fn area(paths: &[BezPath]) -> f64 {
+        paths.iter().map(BezPath::signed_area).sum()
+    }
+
+    #[test]
+    fn test_join_styles() {
+        let miter = OffsetOptions {
+            join: JoinStyle::Miter,
+            ..Default::default()
+        };
+        let paths = offset_polygon(&square_ring(0.0, 0.0, 10.0), 1.0, &miter);
+        assert_eq!(paths.len(), 1);
+        assert!((area(&paths) - 144.0).abs() < 1e-9);
+
+        let bevel = OffsetOptions {
+            join: JoinStyle::Bevel,
+            ..Default::default()
+        };
+        let paths = offset_polygon(&square_ring(0.0, 0.0, 10.0), 1.0, &bevel);
+        assert!((area(&paths) - (144.0 - 2.0)).abs() < 1e-9);
+
+        // Four quarter circles of radius 1 around the corners
+        let paths = offset_polygon(&square_ring(0.0, 0.0, 10.0), 1.0, &OffsetOptions::default());
+        let exact = 100.0 + 40.0 + std::f64::consts::PI;
+        assert!((area(&paths) - exact).abs() < 1e-3);
+        assert_eq!(
+            paths[0]
+                .segments
+                .iter()
+                .filter(|s| matches!(s, Segment::Cubic(..)))
+                .count(),
+            4
+        );
+
+        // A miter past the limit is beveled
+        let spike = [(0.0, 0.0), (10.0, 0.0), (0.0, 1.0)];
+        let paths = offset_polygon(&spike, 0.1, &miter);
+        let points = paths[0].flatten(0.01).points;
+        assert!(points.iter().all(|p| p.0 < 10.5));
+    }
+
+    #[test]
+    fn test_shrinking_keeps_winding_and_drops_folds() {
+        // Clockwise input stays clockwise
+        let mut cw = square_ring(0.0, 0.0, 10.0);
+        cw.reverse();
+        let paths = offset_polygon(&cw, -1.0, &OffsetOptions::default());
+        assert!((area(&paths) + 64.0).abs() < 1e-9);
+
+        // Shrinking a dumbbell past its neck splits it in two
+        let dumbbell = [
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 4.5),
+            (20.0, 4.5),
+            (20.0, 0.0),
+            (30.0, 0.0),
+            (30.0, 10.0),
+            (20.0, 10.0),
+            (20.0, 5.5),
+            (10.0, 5.5),
+            (10.0, 10.0),
+            (0.0, 10.0),
+        ];
+        let bevel = OffsetOptions {
+            join: JoinStyle::Bevel,
+            ..Default::default()
+        };
+        // Each lobe keeps a sliver of the neck between the bevels
+        let paths = offset_polygon(&dumbbell, -1.0, &bevel);
+        assert_eq!(paths.len(), 2);
+        assert!((area(&paths) - 2.0 * 64.25).abs() < 1e-9);
+
+        let paths = offset_polygon(&dumbbell, -1.0, &OffsetOptions::default());
+        assert_eq!(paths.len(), 2);
+        assert!(area(&paths) > 128.0 && area(&paths) < 128.5);
+
+        assert!(
+            offset_polygon(&square_ring(0.0, 0.0, 1.0), -1.0, &OffsetOptions::default()).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_kerf_grows_parts_and_shrinks_holes() {
+        let project = LightBurnProject {
+            // The middle square is a hole; the one on another layer is not
+            shapes: vec![
+                square(50.0, 50.0, 20.0, 0),
+                square(50.0, 50.0, 10.0, 0),
+                square(50.0, 50.0, 4.0, 1),
+            ],
+            ..Default::default()
+        };
+        let options = OffsetOptions {
+            join: JoinStyle::Miter,
+            ..Default::default()
+        };
+        let (kerf, log) = compensate_kerf_with_warnings(&project, 0.2, &options);
+        assert!(log.is_empty());
+
+        let sizes: Vec<(f64, f64)> = kerf
+            .shapes
+            .iter()
+            .map(|shape| {
+                let Shape::Path(path) = shape else {
+                    panic!("expected a path");
+                };
+                assert_eq!(path.xform, *project.shapes[0].xform());
+                let xs = path.parsed_verts.iter().map(|v| v.x);
+                let max = xs.clone().fold(f64::MIN, f64::max);
+                let min = xs.fold(f64::MAX, f64::min);
+                (min, max)
+            })
+            .collect();
+        assert!((sizes[0].0 + 10.1).abs() < 1e-9 && (sizes[0].1 - 10.1).abs() < 1e-9);
+        assert!((sizes[1].0 + 4.9).abs() < 1e-9 && (sizes[1].1 - 4.9).abs() < 1e-9);
+        assert!((sizes[2].0 + 2.1).abs() < 1e-9 && (sizes[2].1 - 2.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_kerf_drops_groups_whose_shapes_vanish() {
+        let group = |e: f64, children| {
+            Shape::Group(Group {
+                cut_index: 0,
+                xform: translate(e, 0.0),
+                children,
+            })
+        };
+        let project = LightBurnProject {
+            shapes: vec![
+                group(0.0, vec![square(0.0, 0.0, 1.0, 0)]),
+                group(10.0, vec![square(0.0, 0.0, 4.0, 0)]),
+            ],
+            ..Default::default()
+        };
+        // A negative kerf shrinks parts; the 1 mm square disappears
+        let kerf = compensate_kerf(&project, -2.0, &OffsetOptions::default());
+        assert_eq!(kerf.shapes.len(), 1);
+        let Shape::Group(group) = &kerf.shapes[0] else {
+            panic!("expected a group");
+        };
+        assert!(matches!(group.children[..], [Shape::Path(_)]));
+    }
+}
@@ -0,0 +1,587 @@
+//! Cut order optimization
+//!
+//! Reorders shapes to shorten travel, cutting inner contours before outer ones.
+
+use super::gcode::layer_order;
+use super::geometry::{BezPath, Point, bezpaths_to_path, distance, path_bezpaths, ring_contains};
+use super::stats::project_stats;
+use super::svg::flatten_groups;
+use super::types::{LightBurnProject, Shape, XForm};
+use std::collections::BTreeSet;
+
+/// Flattening tolerance in mm for the containment test
+const RING_TOLERANCE: f64 = 0.05;
+
+/// How far ahead in the route 2-opt looks for a section to reverse
+const TWO_OPT_WINDOW: usize = 400;
+
+/// Direction closed contours are cut in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopDirection {
+    /// As drawn
+    #[default]
+    Keep,
+    /// Clockwise in the workspace (Y up)
+    Clockwise,
+    /// Counterclockwise in the workspace (Y up)
+    CounterClockwise,
+}
+
+/// Options for [`optimize_cut_order`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderOptions {
+    /// Let open contours be cut from either end (default: true)
+    pub reverse_open: bool,
+    /// Start closed contours at the vertex nearest the end of the previous
+    /// cut instead of where they were drawn from (default: false)
+    pub choose_starts: bool,
+    /// Direction to cut closed contours in (default: keep)
+    pub loop_direction: LoopDirection,
+}
+
+impl Default for OrderOptions {
+    fn default() -> Self {
+        Self {
+            reverse_open: true,
+            choose_starts: false,
+            loop_direction: LoopDirection::Keep,
+        }
+    }
+}
+
+/// Travel before and after [`optimize_cut_order`], measured along the
+/// route [`project_stats`] follows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderReport {
+    /// Length of the rapid moves in mm as the shapes were ordered
+    pub travel_before: f64,
+    /// Length of the rapid moves in mm after reordering
+    pub travel_after: f64,
+}
+
+/// What the route visits: a single contour, or a text's glyph outlines,
+/// which stay together in the order they were drawn
+struct Unit {
+    shape: Shape,
+    /// Workspace contours; a single one unless `fixed`
+    paths: Vec<BezPath>,
+    /// Flattened closed contours, for the containment test
+    rings: Vec<Vec<Point>>,
+    bounds: (Point, Point),
+    /// Entered and left where it was drawn, never turned around
+    fixed: bool,
+    /// Segment a closed contour starts from
+    start: usize,
+    /// An open contour is cut from its end
+    reversed: bool,
+    /// The contour was turned to run in the requested direction
+    turned: bool,
+}
+
+impl Unit {
+    fn new(shape: Shape, paths: Vec<BezPath>, fixed: bool, options: &OrderOptions) -> Self {
+        let mut unit = Unit {
+            shape,
+            rings: Vec::new(),
+            bounds: ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN)),
+            paths,
+            fixed,
+            start: 0,
+            reversed: false,
+            turned: false,
+        };
+        for path in &unit.paths {
+            let points = path.flatten(RING_TOLERANCE).points;
+            for &(x, y) in &points {
+                let (min, max) = &mut unit.bounds;
+                *min = (min.0.min(x), min.1.min(y));
+                *max = (max.0.max(x), max.1.max(y));
+            }
+            if path.closed {
+                unit.rings.push(points);
+            }
+        }
+
+        if !fixed && unit.paths[0].closed {
+            let area = unit.paths[0].signed_area();
+            unit.turned = match options.loop_direction {
+                LoopDirection::Keep => false,
+                LoopDirection::Clockwise => area > 0.0,
+                LoopDirection::CounterClockwise => area < 0.0,
+            };
+            if unit.turned {
+                unit.paths[0] = unit.paths[0].reversed();
+            }
+        }
+        unit
+    }
+
+    /// A closed contour that can be entered anywhere along it
+    fn is_loop(&self) -> bool {
+        !self.fixed && self.paths[0].closed
+    }
+
+    /// Whether reversing a stretch of the route that holds this unit keeps
+    /// its entry and exit valid
+    fn is_symmetric(&self, options: &OrderOptions) -> bool {
+        self.is_loop() || (!self.fixed && options.reverse_open)
+    }
+
+    fn entry(&self) -> Point {
+        let path = &self.paths[0];
+        if self.is_loop() {
+            path.segments[self.start].start()
+        } else if self.reversed {
+            path.end().unwrap_or_default()
+        } else {
+            path.start().unwrap_or_default()
+        }
+    }
+
+    fn exit(&self) -> Point {
+        if self.fixed {
+            return self.paths.last().and_then(BezPath::end).unwrap_or_default();
+        }
+        let path = &self.paths[0];
+        if self.is_loop() {
+            path.segments[self.start].start()
+        } else if self.reversed {
+            path.start().unwrap_or_default()
+        } else {
+            path.end().unwrap_or_default()
+        }
+    }
+
+    /// Distance from the bounds to `p`, which no entry point can beat
+    fn lower_bound(&self, p: Point) -> f64 {
+        let (min, max) = self.bounds;
+        let dx = (min.0 - p.0).max(p.0 - max.0).max(0.0);
+        let dy = (min.1 - p.1).max(p.1 - max.1).max(0.0);
+        dx.hypot(dy)
+    }
+
+    /// Nearest entry to `p` the unit allows: the segment a closed contour
+    /// would start from, whether an open one would be reversed, and the
+    /// distance to it
+    fn nearest_entry(&self, p: Point, options: &OrderOptions) -> (usize, bool, f64) {
+        let path = &self.paths[0];
+        if self.is_loop() && options.choose_starts {
+            let (start, d) = path
+                .segments
+                .iter()
+                .map(|s| distance(p, s.start()))
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0.0));
+            return (start, false, d);
+        }
+        if !self.fixed && !path.closed && options.reverse_open {
+            let to_start = distance(p, path.start().unwrap_or_default());
+            let to_end = distance(p, path.end().unwrap_or_default());
+            return (0, to_end < to_start, to_start.min(to_end));
+        }
+        (self.start, self.reversed, distance(p, self.entry()))
+    }
+
+    /// Turn the unit to be entered as close to `p` as allowed
+    fn enter_from(&mut self, p: Point, options: &OrderOptions) {
+        (self.start, self.reversed, _) = self.nearest_entry(p, options);
+    }
+
+    /// The shape to write, rebuilt when its contour was turned or restarted
+    fn into_shape(self) -> Shape {
+        if !(self.turned || self.reversed || self.start > 0) {
+            return self.shape;
+        }
+        let path = &self.paths[0];
+        let path = if self.reversed {
+            path.reversed()
+        } else {
+            let mut segments = path.segments[self.start..].to_vec();
+            segments.extend_from_slice(&path.segments[..self.start]);
+            BezPath {
+                segments,
+                closed: path.closed,
+            }
+        };
+        let xform = *self.shape.xform();
+        match bezpaths_to_path(self.shape.cut_index(), xform, &xform, &[path]) {
+            Some(path) => Shape::Path(path),
+            None => self.shape,
+        }
+    }
+}
+
+/// Split a layer's leaf shapes into the units the route visits; shapes
+/// without contours, such as bitmaps, are returned separately
+fn layer_units(leaves: Vec<Shape>, options: &OrderOptions) -> (Vec<Shape>, Vec<Unit>) {
+    let mut still = Vec::new();
+    let mut units = Vec::new();
+    for leaf in leaves {
+        let paths = leaf.to_bezpath(&XForm::identity());
+        if paths.is_empty() {
+            still.push(leaf);
+            continue;
+        }
+        if matches!(leaf, Shape::Text(_)) {
+            units.push(Unit::new(leaf, paths, true, options));
+            continue;
+        }
+        if paths.len() == 1 {
+            units.push(Unit::new(leaf, paths, false, options));
+            continue;
+        }
+
+        // A path with several contours becomes one path per contour, so each
+        // can take its own place in the route
+        let Shape::Path(path) = &leaf else {
+            units.push(Unit::new(leaf, paths, true, options));
+            continue;
+        };
+        let local = path_bezpaths(path, &XForm::identity(), &mut Vec::new());
+        for (world, local) in paths.into_iter().zip(local) {
+            let contour = bezpaths_to_path(
+                path.cut_index,
+                path.xform,
+                &XForm::identity(),
+                std::slice::from_ref(&local),
+            );
+            if let Some(contour) = contour {
+                units.push(Unit::new(Shape::Path(contour), vec![world], false, options));
+            }
+        }
+    }
+    (still, units)
+}
+
+/// For every unit, the units whose closed contours enclose it
+fn containers(units: &[Unit]) -> Vec<Vec<usize>> {
+    let area = |u: &Unit| (u.bounds.1.0 - u.bounds.0.0) * (u.bounds.1.1 - u.bounds.0.1);
+    units
+        .iter()
+        .enumerate()
+        .map(|(i, inner)| {
+            let probe = inner.paths[0].start().unwrap_or_default();
+            units
+                .iter()
+                .enumerate()
+                .filter(|&(j, outer)| {
+                    j != i
+                        && area(outer) > area(inner)
+                        && outer.bounds.0.0 <= inner.bounds.0.0
+                        && outer.bounds.0.1 <= inner.bounds.0.1
+                        && outer.bounds.1.0 >= inner.bounds.1.0
+                        && outer.bounds.1.1 >= inner.bounds.1.1
+                        && outer.rings.iter().any(|ring| ring_contains(ring, probe))
+                })
+                .map(|(j, _)| j)
+                .collect()
+        })
+        .collect()
+}
+
+/// Route through the units starting from `from`: always the nearest unit
+/// with nothing left to cut inside it
+fn nearest_neighbour(
+    units: &mut [Unit],
+    outer: &[Vec<usize>],
+    from: Point,
+    options: &OrderOptions,
+) -> Vec<usize> {
+    let mut waiting = vec![0usize; units.len()];
+    for containers in outer {
+        for &j in containers {
+            waiting[j] += 1;
+        }
+    }
+
+    let mut done = vec![false; units.len()];
+    let mut order = Vec::with_capacity(units.len());
+    let mut at = from;
+    while order.len() < units.len() {
+        // Nested contours that enclose each other leave nothing free; the
+        // nearest of what remains breaks the cycle
+        let free = (0..units.len()).any(|i| !done[i] && waiting[i] == 0);
+        let mut best: Option<(usize, f64)> = None;
+        for (i, unit) in units.iter().enumerate() {
+            if done[i] || (free && waiting[i] > 0) {
+                continue;
+            }
+            if best.is_some_and(|(_, d)| unit.lower_bound(at) >= d) {
+                continue;
+            }
+            let (_, _, d) = unit.nearest_entry(at, options);
+            if best.is_none_or(|(_, best)| d < best) {
+                best = Some((i, d));
+            }
+        }
+        let Some((i, _)) = best else {
+            break;
+        };
+
+        units[i].enter_from(at, options);
+        at = units[i].exit();
+        done[i] = true;
+        order.push(i);
+        for &j in &outer[i] {
+            waiting[j] -= 1;
+        }
+    }
+    order
+}
+
+/// Shorten the route by reversing stretches of it while that keeps every
+/// unit after the ones inside it
+fn two_opt(
+    units: &mut [Unit],
+    outer: &[Vec<usize>],
+    order: &mut [usize],
+    from: Point,
+    options: &OrderOptions,
+) {
+    let n = order.len();
+    let mut position = vec![0; units.len()];
+    // Units that cannot be turned around split the route into stretches
+    // that are reversed on their own
+    let mut blocked = vec![0; n + 1];
+    for (k, &i) in order.iter().enumerate() {
+        position[i] = k;
+        blocked[k + 1] = blocked[k] + usize::from(!units[i].is_symmetric(options));
+    }
+
+    for _ in 0..50 {
+        let mut improved = false;
+        for i in 0..n {
+            let prev = if i == 0 {
+                from
+            } else {
+                units[order[i - 1]].exit()
+            };
+            let first = units[order[i]].entry();
+            for j in i + 1..n.min(i + TWO_OPT_WINDOW) {
+                if blocked[j + 1] - blocked[i] > 0 {
+                    break;
+                }
+                let last = units[order[j]].exit();
+                let mut before = distance(prev, first);
+                let mut after = distance(prev, last);
+                if let Some(&next) = order.get(j + 1) {
+                    let next = units[next].entry();
+                    before += distance(last, next);
+                    after += distance(first, next);
+                }
+                if after >= before - 1e-9 {
+                    continue;
+                }
+                let nested = order[i..=j]
+                    .iter()
+                    .any(|&k| outer[k].iter().any(|&c| (i..=j).contains(&position[c])));
+                if nested {
+                    continue;
+                }
+
+                order[i..=j].reverse();
+                for (k, &unit) in order.iter().enumerate().take(j + 1).skip(i) {
+                    position[unit] = k;
+                    if !units[unit].is_loop() {
+                        units[unit].reversed = !units[unit].reversed;
+                    }
+                }
+                improved = true;
+                break;
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// Route through one layer's leaf shapes, returning them in cut order with
+/// the point the laser ends on
+fn order_layer(leaves: Vec<Shape>, from: Point, options: &OrderOptions) -> (Vec<Shape>, Point) {
+    let (mut shapes, mut units) = layer_units(leaves, options);
+    let outer = containers(&units);
+    let mut order = nearest_neighbour(&mut units, &outer, from, options);
+    two_opt(&mut units, &outer, &mut order, from, options);
+
+    // Starts were picked for the greedy route; pick them again for the
+    // final one
+    let mut at = from;
+    for &i in &order {
+        units[i].enter_from(at, options);
+        at = units[i].exit();
+    }
+
+    let mut units: Vec<Option<Unit>> = units.into_iter().map(Some).collect();
+    shapes.extend(
+        order
+            .iter()
+            .filter_map(|&i| units[i].take())
+            .map(Unit::into_shape),
+    );
+    (shapes, at)
+}
+
+/// Reorder the shapes on each layer to shorten travel between cuts
+///
+/// Layers are visited in burn order starting from the origin, as the G-code
+/// export runs them, and each is routed from where the previous one ended.
+/// Shapes end up grouped by layer in that order, groups are dissolved into
+/// their members and paths with several contours are split into one path
+/// per contour. A contour inside a closed contour on its layer is cut
+/// before it. Contours that are restarted or turned around become paths;
+/// layers with output turned off keep their order.
+pub fn optimize_cut_order(
+    project: &LightBurnProject,
+    options: &OrderOptions,
+) -> (LightBurnProject, OrderReport) {
+    let mut leaves = Vec::new();
+    flatten_groups(&project.shapes, None, &mut leaves);
+    let used: BTreeSet<i32> = leaves.iter().map(Shape::cut_index).collect();
+
+    let mut shapes = Vec::with_capacity(leaves.len());
+    let mut at = (0.0, 0.0);
+    for index in layer_order(project, &used) {
+        let layer: Vec<Shape> = leaves
+            .iter()
+            .filter(|s| s.cut_index() == index)
+            .cloned()
+            .collect();
+        let cs = project.cut_settings.iter().find(|cs| cs.index == index);
+        if cs.and_then(|cs| cs.do_output) == Some(false) {
+            shapes.extend(layer);
+            continue;
+        }
+        let (ordered, end) = order_layer(layer, at, options);
+        shapes.extend(ordered);
+        at = end;
+    }
+
+    let ordered = LightBurnProject {
+        shapes,
+        ..project.clone()
+    };
+    let report = OrderReport {
+        travel_before: project_stats(project).travel_length,
+        travel_after: project_stats(&ordered).travel_length,
+    };
+    (ordered, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::square;
+    use crate::lbrn2::types::{Path, PathPrimitive, Vec2};
+
+    fn line(from: Point, to: Point) -> Shape {
+        Shape::Path(Path::from_parts(
+            0,
+            XForm::identity(),
+            vec![Vec2::new(from.0, from.1), Vec2::new(to.0, to.1)],
+            vec![PathPrimitive::Line {
+                start_idx: 0,
+                end_idx: 1,
+            }],
+        ))
+    }
+
+    fn centers(project: &LightBurnProject) -> Vec<(f64, f64)> {
+        project
+            .shapes
+            .iter()
+            .map(|s| (s.xform().e, s.xform().f))
+            .collect()
+    }
+
+    #[test]
+    fn test_route_shortens_travel() {
+        // A row of squares drawn out of order
+        let xs = [50.0, 10.0, 90.0, 30.0, 70.0, 20.0, 80.0, 40.0, 60.0];
+        let project = LightBurnProject {
+            shapes: xs.iter().map(|&x| square(x, 10.0, 4.0, 0)).collect(),
+            ..Default::default()
+        };
+        let (ordered, report) = optimize_cut_order(&project, &OrderOptions::default());
+        let order: Vec<f64> = centers(&ordered).iter().map(|c| c.0).collect();
+        assert_eq!(
+            order,
+            [10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0]
+        );
+        assert!(report.travel_after < report.travel_before);
+        assert!((report.travel_after - project_stats(&ordered).travel_length).abs() < 1e-9);
+
+        // Open lines are entered from whichever end is closer
+        let project = LightBurnProject {
+            shapes: vec![
+                line((20.0, 0.0), (10.0, 0.0)),
+                line((0.0, 5.0), (0.0, 10.0)),
+            ],
+            ..Default::default()
+        };
+        let (ordered, _) = optimize_cut_order(&project, &OrderOptions::default());
+        assert_eq!(ordered.shapes[0], project.shapes[1]);
+        let Shape::Path(path) = &ordered.shapes[1] else {
+            panic!("expected a path");
+        };
+        assert_eq!(path.parsed_verts[0].x, 10.0);
+    }
+
+    #[test]
+    fn test_inner_contours_are_cut_first() {
+        // The outer square is nearest the origin but holds two holes, and
+        // a third square sits on its own
+        let project = LightBurnProject {
+            shapes: vec![
+                square(10.0, 10.0, 20.0, 0),
+                square(35.0, 10.0, 4.0, 0),
+                square(5.0, 10.0, 4.0, 0),
+                square(15.0, 10.0, 4.0, 0),
+            ],
+            ..Default::default()
+        };
+        let (ordered, _) = optimize_cut_order(&project, &OrderOptions::default());
+        let order = centers(&ordered);
+        let outer = order.iter().position(|&c| c == (10.0, 10.0)).unwrap();
+        let holes = [(5.0, 10.0), (15.0, 10.0)].map(|h| order.iter().position(|&c| c == h));
+        assert!(holes.iter().all(|h| h.unwrap() < outer));
+    }
+
+    #[test]
+    fn test_loop_starts_and_directions() {
+        // Counterclockwise square drawn from its corner furthest from the
+        // origin
+        let corners = [(12.0, 12.0), (8.0, 12.0), (8.0, 8.0), (12.0, 8.0)];
+        let project = LightBurnProject {
+            shapes: vec![Shape::Path(Path::from_parts(
+                0,
+                XForm::identity(),
+                corners.iter().map(|&(x, y)| Vec2::new(x, y)).collect(),
+                (0..4)
+                    .map(|i| PathPrimitive::Line {
+                        start_idx: i,
+                        end_idx: (i + 1) % 4,
+                    })
+                    .collect(),
+            ))],
+            ..Default::default()
+        };
+        let options = OrderOptions {
+            choose_starts: true,
+            loop_direction: LoopDirection::Clockwise,
+            ..Default::default()
+        };
+        let (ordered, report) = optimize_cut_order(&project, &options);
+        let contours = ordered.shapes[0].to_bezpath(&XForm::identity());
+        assert_eq!(contours.len(), 1);
+        assert!(contours[0].signed_area() < 0.0);
+        // Entered at the corner nearest the origin
+        assert_eq!(contours[0].start(), Some((8.0, 8.0)));
+        assert!(report.travel_after < report.travel_before);
+
+        // By default a lone contour is left untouched
+        let (ordered, _) = optimize_cut_order(&project, &OrderOptions::default());
+        assert_eq!(ordered.shapes, project.shapes);
+    }
+}
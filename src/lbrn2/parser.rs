@@ -1,398 +1,541 @@
+use super::error::{Lbrn2Error, ListErrorKind, ListSyntaxError, SourcePos};
+use super::path::transform_path;
+use super::reader::{Lbrn2Reader, LineCounter};
+use super::style::lightburn_layer_color;
 use super::types::*;
 use quick_xml::Reader;
-use quick_xml::events::Event;
-use std::collections::HashMap;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::sync::Arc;
 
 /// Parse XForm string "a b c d e f" into XForm struct
+///
+/// Falls back to identity when the string does not hold six numbers.
 pub fn parse_xform(xform_str: &str) -> XForm {
-    let parts: Vec<f64> = xform_str
-        .split_whitespace()
-        .filter_map(|s| s.parse().ok())
-        .collect();
-
-    if parts.len() == 6 {
-        XForm {
-            a: parts[0],
-            b: parts[1],
-            c: parts[2],
-            d: parts[3],
-            e: parts[4],
-            f: parts[5],
+    try_parse_xform(xform_str).unwrap_or_else(XForm::identity)
+}
+
+/// Parse XForm string "a b c d e f", returning `None` if it is malformed
+pub fn try_parse_xform(xform_str: &str) -> Option<XForm> {
+    let mut parts = [0.0; 6];
+    let mut count = 0;
+
+    for token in xform_str.split_whitespace() {
+        if count == 6 {
+            return None;
         }
-    } else {
-        eprintln!("Invalid XForm string, using identity: {}", xform_str);
-        XForm::identity()
+        parts[count] = token.parse().ok()?;
+        count += 1;
+    }
+
+    if count != 6 {
+        return None;
     }
+
+    Some(XForm {
+        a: parts[0],
+        b: parts[1],
+        c: parts[2],
+        d: parts[3],
+        e: parts[4],
+        f: parts[5],
+    })
 }
 
-/// Parse control point data from a string like "c0x1c1x49c1y48"
-fn parse_control_point_data(cp_str: &str) -> (Option<f64>, Option<f64>, Option<f64>, Option<f64>) {
-    let mut c0x = None;
-    let mut c0y = None;
-    let mut c1x = None;
-    let mut c1y = None;
+/// Single-pass reader over the bytes of a VertList or PrimList
+///
+/// Numbers are parsed straight from slices of the input, so scanning does
+/// not allocate.
+struct ListCursor<'a> {
+    src: &'a str,
+    pos: usize,
+}
 
-    if !cp_str.starts_with('c') {
-        return (c0x, c0y, c1x, c1y);
+impl<'a> ListCursor<'a> {
+    fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
     }
 
-    let mut i = 0;
-    let chars: Vec<char> = cp_str.chars().collect();
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
 
-    while i < chars.len() {
-        let remaining: String = chars[i..].iter().collect();
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
 
-        let key = if remaining.starts_with("c0x") {
-            Some("c0x")
-        } else if remaining.starts_with("c0y") {
-            Some("c0y")
-        } else if remaining.starts_with("c1x") {
-            Some("c1x")
-        } else if remaining.starts_with("c1y") {
-            Some("c1y")
-        } else {
-            None
-        };
+    /// Advance while `pred` holds and return the bytes passed over
+    fn take_while(&mut self, pred: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&pred) {
+            self.pos += 1;
+        }
+        // Only ASCII bytes are taken, so both ends are char boundaries
+        &self.src[start..self.pos]
+    }
 
-        if let Some(k) = key {
-            i += 3;
-            let mut num_str = String::new();
-
-            while i < chars.len() {
-                let ch = chars[i];
-                if ch == '-'
-                    || ch == '+'
-                    || ch.is_ascii_digit()
-                    || ch == '.'
-                    || ch == 'e'
-                    || ch == 'E'
-                {
-                    num_str.push(ch);
-                    i += 1;
-                } else {
-                    break;
-                }
-            }
+    /// The characters of a float: sign, digits, point and exponent
+    fn number_token(&mut self) -> &'a str {
+        self.take_while(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+    }
 
-            if let Ok(value) = num_str.parse::<f64>() {
-                match k {
-                    "c0x" => c0x = Some(value),
-                    "c0y" => c0y = Some(value),
-                    "c1x" => c1x = Some(value),
-                    "c1y" => c1y = Some(value),
-                    _ => {}
-                }
-            }
-        } else {
-            i += 1;
+    /// A non-negative integer, or `None` if there are no digits or it
+    /// overflows
+    fn index(&mut self) -> Option<usize> {
+        self.skip_whitespace();
+        let digits = self.take_while(|b| b.is_ascii_digit());
+        if digits.is_empty() {
+            return None;
+        }
+        digits.bytes().try_fold(0usize, |acc, d| {
+            acc.checked_mul(10)?.checked_add(usize::from(d - b'0'))
+        })
+    }
+
+    /// Skip to the next occurrence of `byte`, or to the end
+    fn skip_until(&mut self, byte: u8) {
+        while self.peek().is_some_and(|b| b != byte) {
+            self.pos += 1;
         }
     }
 
-    (c0x, c0y, c1x, c1y)
+    /// The character at the cursor, consumed whole even if it is multi-byte
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.src[self.pos..].chars().next()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
 }
 
-/// Parse VertList string into Vec<Vec2>
-pub fn parse_vert_list(vert_list_str: &str) -> Vec<Vec2> {
-    let mut vertices = Vec::new();
-    let chars: Vec<char> = vert_list_str.chars().collect();
-    let mut i = 0;
-    let len = chars.len();
-
-    while i < len {
-        // Skip whitespace
-        while i < len && chars[i].is_whitespace() {
-            i += 1;
+/// Parse the control points that follow a vertex's coordinates, up to the
+/// next `V`
+///
+/// Keys are `c0x`, `c0y`, `c1x` and `c1y`, each followed by a number.
+/// Anything else is skipped, as are values that do not parse.
+fn parse_control_points(cursor: &mut ListCursor, vertex: &mut Vec2) {
+    while let Some(b) = cursor.peek() {
+        if b == b'V' {
+            break;
         }
 
-        if i < len && chars[i] == 'V' {
-            i += 1;
-
-            // Skip whitespace after V
-            while i < len && chars[i].is_whitespace() {
-                i += 1;
+        let rest = &cursor.src.as_bytes()[cursor.pos..];
+        let slot = match rest {
+            [b'c', b'0', b'x', ..] => &mut vertex.c0x,
+            [b'c', b'0', b'y', ..] => &mut vertex.c0y,
+            [b'c', b'1', b'x', ..] => &mut vertex.c1x,
+            [b'c', b'1', b'y', ..] => &mut vertex.c1y,
+            _ => {
+                cursor.pos += 1;
+                continue;
             }
+        };
+        cursor.pos += 3;
+        if let Ok(value) = cursor.number_token().parse() {
+            *slot = Some(value);
+        }
+    }
+}
 
-            // Parse x coordinate
-            let mut x_str = String::new();
-            while i < len {
-                let ch = chars[i];
-                if ch == '-'
-                    || ch == '+'
-                    || ch.is_ascii_digit()
-                    || ch == '.'
-                    || ch == 'e'
-                    || ch == 'E'
-                {
-                    x_str.push(ch);
-                    i += 1;
-                } else {
-                    break;
-                }
-            }
+/// Parse VertList string into Vec<Vec2>
+///
+/// Malformed vertices are skipped; use [`try_parse_vert_list`] or the
+/// project parser to find out about them.
+pub fn parse_vert_list(vert_list_str: &str) -> Vec<Vec2> {
+    parse_vert_list_reporting(vert_list_str, &mut Vec::new())
+}
 
-            // Skip whitespace between x and y
-            while i < len && chars[i].is_whitespace() {
-                i += 1;
-            }
+/// Parse VertList string, failing on the first malformed vertex
+pub fn try_parse_vert_list(vert_list_str: &str) -> Result<Vec<Vec2>, ListSyntaxError> {
+    let mut errors = Vec::new();
+    let verts = parse_vert_list_reporting(vert_list_str, &mut errors);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(verts),
+    }
+}
 
-            // Parse y coordinate
-            let mut y_str = String::new();
-            while i < len {
-                let ch = chars[i];
-                if ch == '-'
-                    || ch == '+'
-                    || ch.is_ascii_digit()
-                    || ch == '.'
-                    || ch == 'e'
-                    || ch == 'E'
-                {
-                    y_str.push(ch);
-                    i += 1;
-                } else {
-                    break;
-                }
-            }
+/// Parse VertList string, recording every vertex that had to be skipped
+fn parse_vert_list_reporting(vert_list_str: &str, errors: &mut Vec<ListSyntaxError>) -> Vec<Vec2> {
+    let mut vertices = Vec::new();
+    let mut cursor = ListCursor::new(vert_list_str);
 
-            // Collect control point string until next V or end
-            let mut cp_str = String::new();
-            while i < len && chars[i] != 'V' {
-                cp_str.push(chars[i]);
-                i += 1;
+    loop {
+        cursor.skip_whitespace();
+        match cursor.peek() {
+            None => break,
+            Some(b'V') => {}
+            Some(_) => {
+                cursor.pos += 1;
+                continue;
             }
+        }
 
-            if x_str.is_empty() || y_str.is_empty() {
-                eprintln!(
-                    "Failed to parse vertex from X: \"{}\", Y: \"{}\" in VertList: \"{}\"",
-                    x_str, y_str, vert_list_str
-                );
+        let vertex_start = cursor.pos;
+        cursor.pos += 1;
+        cursor.skip_whitespace();
+        let x_str = cursor.number_token();
+        cursor.skip_whitespace();
+        let y_str = cursor.number_token();
+
+        let kind = match (x_str.parse::<f64>(), y_str.parse::<f64>()) {
+            (Ok(x), Ok(y)) => {
+                let mut vertex = Vec2::new(x, y);
+                parse_control_points(&mut cursor, &mut vertex);
+                vertices.push(vertex);
                 continue;
             }
+            _ if x_str.is_empty() || y_str.is_empty() => ListErrorKind::MissingCoordinate,
+            _ => ListErrorKind::InvalidNumber,
+        };
 
-            let x: f64 = x_str.parse().unwrap_or(0.0);
-            let y: f64 = y_str.parse().unwrap_or(0.0);
-
-            let (c0x, c0y, c1x, c1y) = parse_control_point_data(cp_str.trim());
-
-            vertices.push(Vec2::with_control_points(x, y, c0x, c0y, c1x, c1y));
-        } else {
-            i += 1;
-        }
+        errors.push(ListSyntaxError {
+            offset: vertex_start,
+            item: vertices.len(),
+            kind,
+        });
+        cursor.skip_until(b'V');
     }
 
     vertices
 }
 
 /// Parse PrimList string into Vec<PathPrimitive>
+///
+/// Unknown or incomplete primitives are skipped; use [`try_parse_prim_list`]
+/// or the project parser to find out about them.
 pub fn parse_prim_list(prim_list_str: &str) -> Vec<PathPrimitive> {
-    let mut primitives = Vec::new();
-    let chars: Vec<char> = prim_list_str.chars().collect();
-    let mut i = 0;
-    let len = chars.len();
-
-    fn parse_next_int(chars: &[char], i: &mut usize, len: usize) -> Option<usize> {
-        // Skip whitespace
-        while *i < len && chars[*i].is_whitespace() {
-            *i += 1;
-        }
-
-        let mut num_str = String::new();
-        while *i < len && chars[*i].is_ascii_digit() {
-            num_str.push(chars[*i]);
-            *i += 1;
-        }
+    parse_prim_list_reporting(prim_list_str, &mut Vec::new())
+}
 
-        if !num_str.is_empty() {
-            num_str.parse().ok()
-        } else {
-            None
-        }
+/// Parse PrimList string, failing on the first malformed primitive
+pub fn try_parse_prim_list(prim_list_str: &str) -> Result<Vec<PathPrimitive>, ListSyntaxError> {
+    let mut errors = Vec::new();
+    let prims = parse_prim_list_reporting(prim_list_str, &mut errors);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(prims),
     }
+}
 
-    while i < len {
-        // Skip whitespace
-        while i < len && chars[i].is_whitespace() {
-            i += 1;
-        }
+/// Parse PrimList string, recording every primitive that had to be skipped
+fn parse_prim_list_reporting(
+    prim_list_str: &str,
+    errors: &mut Vec<ListSyntaxError>,
+) -> Vec<PathPrimitive> {
+    let mut primitives = Vec::new();
+    let mut cursor = ListCursor::new(prim_list_str);
 
-        if i >= len {
+    loop {
+        cursor.skip_whitespace();
+        let prim_start = cursor.pos;
+        let Some(prim_type) = cursor.next_char() else {
             break;
-        }
+        };
 
-        let prim_type = chars[i];
         if !prim_type.is_alphabetic() {
-            i += 1;
+            errors.push(ListSyntaxError {
+                offset: prim_start,
+                item: primitives.len(),
+                kind: ListErrorKind::UnexpectedChar(prim_type),
+            });
             continue;
         }
 
-        i += 1;
+        // Up to four indices; only the first two are used by L and B
+        let mut args = [0usize; 4];
+        let mut found = 0;
+        while found < args.len() {
+            match cursor.index() {
+                Some(n) => {
+                    args[found] = n;
+                    found += 1;
+                }
+                None => break,
+            }
+        }
 
-        let mut args = Vec::new();
-        for _ in 0..4 {
-            if let Some(num) = parse_next_int(&chars, &mut i, len) {
-                args.push(num);
-            } else {
-                break;
+        let kind = match (prim_type, found) {
+            ('L', 2..) => {
+                primitives.push(PathPrimitive::Line {
+                    start_idx: args[0],
+                    end_idx: args[1],
+                });
+                continue;
+            }
+            ('B', 2..) => {
+                primitives.push(PathPrimitive::Bezier {
+                    start_idx: args[0],
+                    end_idx: args[1],
+                });
+                continue;
+            }
+            ('L' | 'B', _) => ListErrorKind::MissingIndex {
+                primitive: prim_type,
+                found,
+            },
+            _ => ListErrorKind::UnknownPrimitive(prim_type),
+        };
+
+        errors.push(ListSyntaxError {
+            offset: prim_start,
+            item: primitives.len(),
+            kind,
+        });
+    }
+
+    primitives
+}
+
+/// Attributes shared by `<Shape>` and `<BackupPath>` elements
+#[derive(Debug, Default)]
+struct ShapeAttrs {
+    shape_type: String,
+    cut_index: i32,
+    w: f64,
+    h: f64,
+    cr: f64,
+    rx: f64,
+    ry: f64,
+    vert_id: Option<i32>,
+    prim_id: Option<i32>,
+    data: String,
+    text: String,
+    font: String,
+    letter_spacing: f64,
+    line_spacing: f64,
+    align_h: i32,
+    align_v: i32,
+    weld: bool,
+}
+
+impl ShapeAttrs {
+    fn from_element(e: &BytesStart) -> Self {
+        let mut attrs = ShapeAttrs::default();
+
+        for attr in e.attributes().flatten() {
+            let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
+            let value = std::str::from_utf8(&attr.value).unwrap_or("");
+            match key {
+                "Type" => attrs.shape_type = value.to_string(),
+                "CutIndex" => attrs.cut_index = value.parse().unwrap_or(0),
+                "W" => attrs.w = value.parse().unwrap_or(0.0),
+                "H" => attrs.h = value.parse().unwrap_or(0.0),
+                "Cr" => attrs.cr = value.parse().unwrap_or(0.0),
+                "Rx" => attrs.rx = value.parse().unwrap_or(0.0),
+                "Ry" => attrs.ry = value.parse().unwrap_or(0.0),
+                "VertID" => attrs.vert_id = value.parse().ok(),
+                "PrimID" => attrs.prim_id = value.parse().ok(),
+                "Data" => attrs.data = value.to_string(),
+                "Str" => attrs.text = unescape_attr(&attr),
+                "Font" => attrs.font = unescape_attr(&attr),
+                "LS" => attrs.letter_spacing = value.parse().unwrap_or(0.0),
+                "LnS" => attrs.line_spacing = value.parse().unwrap_or(0.0),
+                "Ah" => attrs.align_h = value.parse().unwrap_or(0),
+                "Av" => attrs.align_v = value.parse().unwrap_or(0),
+                "Weld" => attrs.weld = value == "1",
+                _ => {}
             }
         }
 
-        if prim_type == 'L' && args.len() >= 2 {
-            primitives.push(PathPrimitive::Line {
-                start_idx: args[0],
-                end_idx: args[1],
-            });
-        } else if prim_type == 'B' && args.len() >= 2 {
-            primitives.push(PathPrimitive::Bezier {
-                start_idx: args[0],
-                end_idx: args[1],
-            });
+        attrs
+    }
+
+    /// Build a text shape; a backup path placed differently from the text is
+    /// re-expressed in the text's local coordinates
+    fn into_text(self, xform: XForm, backup_path: Option<Shape>) -> Text {
+        let backup_path = match backup_path {
+            Some(Shape::Path(bp)) if bp.xform == xform => Some(bp),
+            Some(Shape::Path(bp)) => xform
+                .inverse()
+                .map(|inv| transform_path(&bp, &inv.compose(&bp.xform))),
+            _ => None,
+        };
+
+        Text {
+            cut_index: self.cut_index,
+            xform,
+            text: self.text,
+            font: self.font,
+            h: self.h,
+            letter_spacing: self.letter_spacing,
+            line_spacing: self.line_spacing,
+            align_h: self.align_h,
+            align_v: self.align_v,
+            weld: self.weld,
+            backup_path,
         }
     }
+}
 
-    primitives
+/// Reader over an LBRN2 document that tracks line numbers
+pub(crate) type XmlReader<R> = Reader<LineCounter<R>>;
+
+/// Raw and parsed form of a VertList, shared by every path with its VertID
+type SharedVerts = (Arc<str>, Arc<Vec<Vec2>>);
+/// Raw and parsed form of a PrimList, shared by every path with its PrimID
+type SharedPrims = (Arc<str>, Arc<Vec<PathPrimitive>>);
+
+/// State shared across the whole document while parsing
+#[derive(Default)]
+pub(crate) struct ParseContext {
+    vertex_cache: HashMap<i32, SharedVerts>,
+    primitive_cache: HashMap<i32, SharedPrims>,
+    pub(crate) warnings: Vec<Lbrn2Error>,
+}
+
+impl ParseContext {
+    /// Move the lists defined under each `VertID`/`PrimID` into the project
+    pub(crate) fn share_into(self, project: &mut LightBurnProject) {
+        project.shared_verts = self
+            .vertex_cache
+            .into_iter()
+            .filter(|(_, (_, verts))| !verts.is_empty())
+            .map(|(id, (_, verts))| (id, verts))
+            .collect();
+        project.shared_prims = self
+            .primitive_cache
+            .into_iter()
+            .map(|(id, (_, prims))| (id, prims))
+            .collect();
+    }
+}
+
+/// Source position of a byte offset the reader has already passed
+pub(crate) fn source_pos<R>(reader: &XmlReader<R>, offset: u64) -> SourcePos {
+    reader.get_ref().pos(offset as usize)
+}
+
+pub(crate) fn xml_error<R>(reader: &XmlReader<R>, e: quick_xml::Error) -> Lbrn2Error {
+    Lbrn2Error::Xml {
+        pos: source_pos(reader, reader.error_position()),
+        message: e.to_string(),
+    }
 }
 
 /// Parse an LBRN2 XML string into a LightBurnProject
-pub fn parse_lbrn2_complete(xml_string: &str) -> Result<LightBurnProject, String> {
-    let mut reader = Reader::from_str(xml_string);
-    reader.config_mut().trim_text(true);
-
-    let mut project = LightBurnProject {
-        app_version: String::new(),
-        format_version: String::new(),
-        cut_settings: Vec::new(),
-        shapes: Vec::new(),
-    };
+///
+/// Recoverable problems are discarded; use [`parse_lbrn2_with_warnings`] to
+/// inspect them.
+pub fn parse_lbrn2_complete(xml_string: &str) -> Result<LightBurnProject, Lbrn2Error> {
+    parse_lbrn2_with_warnings(xml_string).map(|(project, _)| project)
+}
 
-    let mut vertex_cache: HashMap<i32, (String, Vec<Vec2>)> = HashMap::new();
-    let mut primitive_cache: HashMap<i32, (String, Vec<PathPrimitive>)> = HashMap::new();
+/// Parse an LBRN2 XML string, also returning every recoverable problem found
+///
+/// Only malformed XML is fatal. Bad XForms, malformed VertList/PrimList
+/// entries, dangling VertID/PrimID references and empty paths are reported
+/// in the returned warnings, each with its source position. To process a
+/// large file without holding all of it in memory, use [`Lbrn2Reader`].
+pub fn parse_lbrn2_with_warnings(
+    xml_string: &str,
+) -> Result<(LightBurnProject, Vec<Lbrn2Error>), Lbrn2Error> {
+    let mut reader = Lbrn2Reader::new(xml_string.as_bytes());
+    let mut shapes = Vec::new();
+    while let Some(shape) = reader.next_shape()? {
+        shapes.push(shape);
+    }
 
-    let mut buf = Vec::new();
+    let warnings = reader.take_warnings();
+    let mut project = reader.into_project();
+    project.shapes = shapes;
+    Ok((project, warnings))
+}
 
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                let name_bytes = e.name();
-                let name = std::str::from_utf8(name_bytes.as_ref()).unwrap_or("");
-
-                if name == "LightBurnProject" {
-                    for attr in e.attributes().flatten() {
-                        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
-                        let value = std::str::from_utf8(&attr.value).unwrap_or("");
-                        match key {
-                            "AppVersion" => project.app_version = value.to_string(),
-                            "FormatVersion" => project.format_version = value.to_string(),
-                            _ => {}
-                        }
-                    }
-                } else if name == "CutSetting" {
-                    let cs = parse_cut_setting_inner(&mut reader)?;
-                    project.cut_settings.push(cs);
-                } else if name == "Shape" {
-                    // Collect attributes first
-                    let mut shape_type = String::new();
-                    let mut cut_index: i32 = 0;
-                    let mut w: f64 = 0.0;
-                    let mut h: f64 = 0.0;
-                    let mut cr: f64 = 0.0;
-                    let mut rx: f64 = 0.0;
-                    let mut ry: f64 = 0.0;
-                    let mut vert_id: Option<i32> = None;
-                    let mut prim_id: Option<i32> = None;
-                    let mut has_backup_path = false;
-                    let mut data_attr = String::new();
-
-                    for attr in e.attributes().flatten() {
-                        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
-                        let value = std::str::from_utf8(&attr.value).unwrap_or("");
-                        match key {
-                            "Type" => shape_type = value.to_string(),
-                            "CutIndex" => cut_index = value.parse().unwrap_or(0),
-                            "W" => w = value.parse().unwrap_or(0.0),
-                            "H" => h = value.parse().unwrap_or(0.0),
-                            "Cr" => cr = value.parse().unwrap_or(0.0),
-                            "Rx" => rx = value.parse().unwrap_or(0.0),
-                            "Ry" => ry = value.parse().unwrap_or(0.0),
-                            "VertID" => vert_id = value.parse().ok(),
-                            "PrimID" => prim_id = value.parse().ok(),
-                            "HasBackupPath" => has_backup_path = value == "1",
-                            "Data" => data_attr = value.to_string(),
-                            _ => {}
-                        }
-                    }
+pub(crate) fn parse_project_attributes(e: &BytesStart, project: &mut LightBurnProject) {
+    for attr in e.attributes().flatten() {
+        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
+        let value = std::str::from_utf8(&attr.value).unwrap_or("");
+        match key {
+            "AppVersion" => project.app_version = value.to_string(),
+            "FormatVersion" => project.format_version = value.to_string(),
+            "DeviceName" => project.device_name = Some(unescape_attr(&attr)),
+            "MaterialHeight" => project.material_height = value.trim().parse().ok(),
+            "MirrorX" => project.mirror_x = parse_bool(value).unwrap_or(false),
+            "MirrorY" => project.mirror_y = parse_bool(value).unwrap_or(false),
+            _ => {}
+        }
+    }
+}
 
-                    if let Some(shape) = parse_shape_inner(
-                        &mut reader,
-                        shape_type,
-                        cut_index,
-                        w,
-                        h,
-                        cr,
-                        rx,
-                        ry,
-                        vert_id,
-                        prim_id,
-                        has_backup_path,
-                        data_attr,
-                        &mut vertex_cache,
-                        &mut primitive_cache,
-                    )? {
-                        project.shapes.push(shape);
-                    }
-                }
-            }
-            Ok(Event::Empty(ref e)) => {
-                let name_bytes = e.name();
-                let name = std::str::from_utf8(name_bytes.as_ref()).unwrap_or("");
-                if name == "Shape"
-                    && let Some(shape) = parse_shape_from_empty_element(e)?
-                {
-                    project.shapes.push(shape);
-                }
+/// Read the attributes of `<Notes>` or `<Thumbnail>`
+pub(crate) fn parse_project_child(e: &BytesStart, project: &mut LightBurnProject) {
+    let is_notes = e.name().as_ref() == b"Notes";
+    let mut notes = Notes::default();
+
+    for attr in e.attributes().flatten() {
+        match (is_notes, attr.key.as_ref()) {
+            (true, b"Notes") => notes.text = unescape_attr(&attr),
+            (true, b"ShowOnLoad") => {
+                notes.show_on_load =
+                    parse_bool(std::str::from_utf8(&attr.value).unwrap_or("")).unwrap_or(false)
             }
-            Ok(Event::Eof) => break,
-            Err(e) => return Err(format!("XML parsing error: {:?}", e)),
+            (false, b"Source") => project.thumbnail = Some(unescape_attr(&attr)),
             _ => {}
         }
-        buf.clear();
     }
 
-    Ok(project)
+    if is_notes {
+        project.notes = Some(notes);
+    }
+}
+
+/// Attribute value with XML entities resolved, or the raw text if they
+/// cannot be
+fn unescape_attr(attr: &Attribute) -> String {
+    attr.unescape_value()
+        .map(|v| v.into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(&attr.value).into_owned())
 }
 
-fn parse_cut_setting_inner(reader: &mut Reader<&[u8]>) -> Result<CutSetting, String> {
-    let mut index: i32 = 0;
-    let name = String::new();
+/// Parse the boolean spellings LightBurn uses (`1`/`0`, `True`/`False`)
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim() {
+        "1" | "True" | "true" => Some(true),
+        "0" | "False" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_cut_setting_inner<R: BufRead>(
+    reader: &mut XmlReader<R>,
+    start: &BytesStart,
+) -> Result<CutSetting, Lbrn2Error> {
+    let mut cut_type = CutType::default();
+    for attr in start.attributes().flatten() {
+        if attr.key.as_ref() == b"type" {
+            cut_type = CutType::from_attr(std::str::from_utf8(&attr.value).unwrap_or(""));
+        }
+    }
+
+    // Every direct child looks like <name Value="..."/>; collect them all
+    // and pick out the ones with typed fields afterwards
+    let values = parse_value_params(reader)?;
+    Ok(cut_setting_from_values(cut_type, values))
+}
+
+/// Collect the `Value` of every direct child of the element just opened,
+/// keyed by element name, consuming events up to its end tag
+pub(crate) fn parse_value_params<R: BufRead>(
+    reader: &mut XmlReader<R>,
+) -> Result<BTreeMap<String, String>, Lbrn2Error> {
+    let mut values = BTreeMap::new();
     let mut buf = Vec::new();
     let mut depth = 1;
 
     loop {
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(ref e)) => {
-                depth += 1;
-                let tag_bytes = e.name();
-                let tag = std::str::from_utf8(tag_bytes.as_ref()).unwrap_or("");
-                if tag == "index" {
-                    for attr in e.attributes().flatten() {
-                        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
-                        let value = std::str::from_utf8(&attr.value).unwrap_or("");
-                        if key == "Value" {
-                            index = value.parse().unwrap_or(0);
-                        }
-                    }
+                if depth == 1 {
+                    collect_value_param(e, &mut values);
                 }
+                depth += 1;
             }
-            Ok(Event::Empty(ref e)) => {
-                let tag_bytes = e.name();
-                let tag = std::str::from_utf8(tag_bytes.as_ref()).unwrap_or("");
-                if tag == "index" {
-                    for attr in e.attributes().flatten() {
-                        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
-                        let value = std::str::from_utf8(&attr.value).unwrap_or("");
-                        if key == "Value" {
-                            index = value.parse().unwrap_or(0);
-                        }
-                    }
-                }
+            Ok(Event::Empty(ref e)) if depth == 1 => {
+                collect_value_param(e, &mut values);
             }
             Ok(Event::End(_)) => {
                 depth -= 1;
@@ -401,41 +544,97 @@ fn parse_cut_setting_inner(reader: &mut Reader<&[u8]>) -> Result<CutSetting, Str
                 }
             }
             Ok(Event::Eof) => break,
-            Err(e) => return Err(format!("Error parsing CutSetting: {:?}", e)),
+            Err(e) => return Err(xml_error(reader, e)),
             _ => {}
         }
         buf.clear();
     }
 
-    Ok(CutSetting {
+    Ok(values)
+}
+
+/// Record the `Value` attribute of a parameter element
+fn collect_value_param(e: &BytesStart, values: &mut BTreeMap<String, String>) {
+    let tag_bytes = e.name();
+    let tag = std::str::from_utf8(tag_bytes.as_ref()).unwrap_or("");
+    for attr in e.attributes().flatten() {
+        if attr.key.as_ref() == b"Value" {
+            values.insert(tag.to_string(), unescape_attr(&attr));
+        }
+    }
+}
+
+/// Build a CutSetting from its raw parameters
+///
+/// Known parameters that parse are moved into typed fields; everything else,
+/// including known parameters with unparsable values, stays in `params`.
+fn cut_setting_from_values(cut_type: CutType, mut values: BTreeMap<String, String>) -> CutSetting {
+    fn take<T: std::str::FromStr>(values: &mut BTreeMap<String, String>, key: &str) -> Option<T> {
+        let parsed = values.get(key)?.trim().parse().ok()?;
+        values.remove(key);
+        Some(parsed)
+    }
+
+    fn take_bool(values: &mut BTreeMap<String, String>, key: &str) -> Option<bool> {
+        let parsed = parse_bool(values.get(key)?)?;
+        values.remove(key);
+        Some(parsed)
+    }
+
+    let index = take(&mut values, "index").unwrap_or(0);
+    let name = values.remove("name").unwrap_or_default();
+
+    CutSetting {
         index,
         name,
-        color: None,
+        color: lightburn_layer_color(index).map(str::to_string),
         stroke_width: None,
-    })
+        cut_type,
+        min_power: take(&mut values, "minPower"),
+        max_power: take(&mut values, "maxPower"),
+        speed: take(&mut values, "speed"),
+        num_passes: take(&mut values, "numPasses"),
+        priority: take(&mut values, "priority"),
+        z_offset: take(&mut values, "zOffset"),
+        kerf: take(&mut values, "kerf"),
+        tabs: TabSettings {
+            enabled: take_bool(&mut values, "tabsEnabled"),
+            size: take(&mut values, "tabSize"),
+            spacing: take(&mut values, "tabSpacing"),
+            count: take(&mut values, "tabCount"),
+            count_max: take(&mut values, "tabCountMax"),
+            skip_inner: take_bool(&mut values, "skipInnerTabs"),
+        },
+        do_output: take_bool(&mut values, "doOutput"),
+        hide: take_bool(&mut values, "hide"),
+        params: values,
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn parse_shape_inner(
-    reader: &mut Reader<&[u8]>,
-    shape_type: String,
-    cut_index: i32,
-    w: f64,
-    h: f64,
-    cr: f64,
-    rx: f64,
-    ry: f64,
-    vert_id: Option<i32>,
-    prim_id: Option<i32>,
-    has_backup_path: bool,
-    data_attr: String,
-    vertex_cache: &mut HashMap<i32, (String, Vec<Vec2>)>,
-    primitive_cache: &mut HashMap<i32, (String, Vec<PathPrimitive>)>,
-) -> Result<Option<Shape>, String> {
+/// Parse the `<Shape>` element `e`, whose start tag began at `start_offset`
+pub(crate) fn parse_shape<R: BufRead>(
+    reader: &mut XmlReader<R>,
+    e: &BytesStart,
+    start_offset: u64,
+    ctx: &mut ParseContext,
+) -> Result<Option<Shape>, Lbrn2Error> {
+    let attrs = ShapeAttrs::from_element(e);
+    let shape_pos = source_pos(reader, start_offset);
+    parse_shape_inner(reader, attrs, shape_pos, ctx)
+}
+
+fn parse_shape_inner<R: BufRead>(
+    reader: &mut XmlReader<R>,
+    mut attrs: ShapeAttrs,
+    shape_pos: SourcePos,
+    ctx: &mut ParseContext,
+) -> Result<Option<Shape>, Lbrn2Error> {
     let mut xform = XForm::identity();
     let mut vert_list = String::new();
+    let mut vert_list_pos = shape_pos;
     let mut prim_list = String::new();
-    let mut data = data_attr;
+    let mut prim_list_pos = shape_pos;
+    let mut data = std::mem::take(&mut attrs.data);
     let mut children: Vec<Shape> = Vec::new();
     let mut backup_path_shape: Option<Shape> = None;
 
@@ -446,6 +645,7 @@ fn parse_shape_inner(
     let mut in_backup_path = false;
 
     loop {
+        let event_start = reader.buffer_position();
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(ref e)) => {
                 depth += 1;
@@ -457,106 +657,17 @@ fn parse_shape_inner(
                     in_children = true;
                 } else if tag == "BackupPath" {
                     // BackupPath element has shape attributes directly on it
-                    let mut bp_shape_type = String::new();
-                    let mut bp_cut_index: i32 = 0;
-                    let mut bp_w: f64 = 0.0;
-                    let mut bp_h: f64 = 0.0;
-                    let mut bp_cr: f64 = 0.0;
-                    let mut bp_rx: f64 = 0.0;
-                    let mut bp_ry: f64 = 0.0;
-                    let mut bp_vert_id: Option<i32> = None;
-                    let mut bp_prim_id: Option<i32> = None;
-                    let mut bp_has_backup_path = false;
-                    let mut bp_data_attr = String::new();
-
-                    for attr in e.attributes().flatten() {
-                        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
-                        let value = std::str::from_utf8(&attr.value).unwrap_or("");
-                        match key {
-                            "Type" => bp_shape_type = value.to_string(),
-                            "CutIndex" => bp_cut_index = value.parse().unwrap_or(0),
-                            "W" => bp_w = value.parse().unwrap_or(0.0),
-                            "H" => bp_h = value.parse().unwrap_or(0.0),
-                            "Cr" => bp_cr = value.parse().unwrap_or(0.0),
-                            "Rx" => bp_rx = value.parse().unwrap_or(0.0),
-                            "Ry" => bp_ry = value.parse().unwrap_or(0.0),
-                            "VertID" => bp_vert_id = value.parse().ok(),
-                            "PrimID" => bp_prim_id = value.parse().ok(),
-                            "HasBackupPath" => bp_has_backup_path = value == "1",
-                            "Data" => bp_data_attr = value.to_string(),
-                            _ => {}
-                        }
-                    }
-
-                    if let Some(bp) = parse_shape_inner(
-                        reader,
-                        bp_shape_type,
-                        bp_cut_index,
-                        bp_w,
-                        bp_h,
-                        bp_cr,
-                        bp_rx,
-                        bp_ry,
-                        bp_vert_id,
-                        bp_prim_id,
-                        bp_has_backup_path,
-                        bp_data_attr,
-                        vertex_cache,
-                        primitive_cache,
-                    )? {
+                    let bp_attrs = ShapeAttrs::from_element(e);
+                    let bp_pos = source_pos(reader, event_start);
+                    if let Some(bp) = parse_shape_inner(reader, bp_attrs, bp_pos, ctx)? {
                         backup_path_shape = Some(bp);
                     }
                     in_backup_path = false;
                     depth -= 1; // BackupPath is handled, adjust depth
                 } else if tag == "Shape" {
-                    // Collect child shape attributes
-                    let mut child_shape_type = String::new();
-                    let mut child_cut_index: i32 = 0;
-                    let mut child_w: f64 = 0.0;
-                    let mut child_h: f64 = 0.0;
-                    let mut child_cr: f64 = 0.0;
-                    let mut child_rx: f64 = 0.0;
-                    let mut child_ry: f64 = 0.0;
-                    let mut child_vert_id: Option<i32> = None;
-                    let mut child_prim_id: Option<i32> = None;
-                    let mut child_has_backup_path = false;
-                    let mut child_data_attr = String::new();
-
-                    for attr in e.attributes().flatten() {
-                        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
-                        let value = std::str::from_utf8(&attr.value).unwrap_or("");
-                        match key {
-                            "Type" => child_shape_type = value.to_string(),
-                            "CutIndex" => child_cut_index = value.parse().unwrap_or(0),
-                            "W" => child_w = value.parse().unwrap_or(0.0),
-                            "H" => child_h = value.parse().unwrap_or(0.0),
-                            "Cr" => child_cr = value.parse().unwrap_or(0.0),
-                            "Rx" => child_rx = value.parse().unwrap_or(0.0),
-                            "Ry" => child_ry = value.parse().unwrap_or(0.0),
-                            "VertID" => child_vert_id = value.parse().ok(),
-                            "PrimID" => child_prim_id = value.parse().ok(),
-                            "HasBackupPath" => child_has_backup_path = value == "1",
-                            "Data" => child_data_attr = value.to_string(),
-                            _ => {}
-                        }
-                    }
-
-                    if let Some(child) = parse_shape_inner(
-                        reader,
-                        child_shape_type,
-                        child_cut_index,
-                        child_w,
-                        child_h,
-                        child_cr,
-                        child_rx,
-                        child_ry,
-                        child_vert_id,
-                        child_prim_id,
-                        child_has_backup_path,
-                        child_data_attr,
-                        vertex_cache,
-                        primitive_cache,
-                    )? {
+                    let child_attrs = ShapeAttrs::from_element(e);
+                    let child_pos = source_pos(reader, event_start);
+                    if let Some(child) = parse_shape_inner(reader, child_attrs, child_pos, ctx)? {
                         if in_backup_path {
                             backup_path_shape = Some(child);
                         } else if in_children {
@@ -570,7 +681,7 @@ fn parse_shape_inner(
                 let tag_bytes = e.name();
                 let tag = std::str::from_utf8(tag_bytes.as_ref()).unwrap_or("");
                 if tag == "Shape"
-                    && let Some(child) = parse_shape_from_empty_element(e)?
+                    && let Some(child) = parse_shape_from_empty_element(e)
                 {
                     if in_backup_path {
                         backup_path_shape = Some(child);
@@ -580,11 +691,33 @@ fn parse_shape_inner(
                 }
             }
             Ok(Event::Text(ref e)) => {
-                let text = String::from_utf8_lossy(e.as_ref()).to_string();
+                let raw: &[u8] = e.as_ref();
+                let trimmed = raw.trim_ascii();
+                if trimmed.is_empty() {
+                    buf.clear();
+                    continue;
+                }
+                // Text is not trimmed by the reader, so positions stay exact;
+                // point at the first non-whitespace byte
+                let leading = raw.len() - raw.trim_ascii_start().len();
+                let text_pos = source_pos(reader, event_start + leading as u64);
+                let text = String::from_utf8_lossy(trimmed).into_owned();
                 match current_tag.as_str() {
-                    "XForm" => xform = parse_xform(&text),
-                    "VertList" => vert_list = text,
-                    "PrimList" => prim_list = text,
+                    "XForm" => match try_parse_xform(&text) {
+                        Some(x) => xform = x,
+                        None => ctx.warnings.push(Lbrn2Error::InvalidXForm {
+                            pos: text_pos,
+                            value: text,
+                        }),
+                    },
+                    "VertList" => {
+                        vert_list = text;
+                        vert_list_pos = text_pos;
+                    }
+                    "PrimList" => {
+                        prim_list = text;
+                        prim_list_pos = text_pos;
+                    }
                     "Data" => data = text,
                     _ => {}
                 }
@@ -604,90 +737,119 @@ fn parse_shape_inner(
                 }
             }
             Ok(Event::Eof) => break,
-            Err(e) => return Err(format!("Error parsing Shape: {:?}", e)),
+            Err(e) => return Err(xml_error(reader, e)),
             _ => {}
         }
         buf.clear();
     }
 
-    // If this is a Text with BackupPath, use the backup path shape instead
-    if shape_type == "Text"
-        && has_backup_path
-        && let Some(bp) = backup_path_shape
-    {
-        return Ok(Some(bp));
+    if attrs.shape_type == "Text" {
+        return Ok(Some(Shape::Text(attrs.into_text(xform, backup_path_shape))));
     }
 
-    // Handle VertID/PrimID caching
-    let resolved_verts: Vec<Vec2>;
-    let resolved_vert_list: String;
-    let resolved_prims: Vec<PathPrimitive>;
-    let resolved_prim_list: String;
-
-    if !vert_list.is_empty() {
-        resolved_verts = parse_vert_list(&vert_list);
-        resolved_vert_list = vert_list.clone();
-        if let Some(vid) = vert_id {
-            vertex_cache.insert(vid, (vert_list, resolved_verts.clone()));
+    // Lists defined once under a VertID/PrimID are shared, not copied, by
+    // every later shape that refers to them
+    let (resolved_vert_list, resolved_verts) = if !vert_list.is_empty() {
+        let mut errors = Vec::new();
+        let verts = parse_vert_list_reporting(&vert_list, &mut errors);
+        for err in errors {
+            ctx.warnings.push(Lbrn2Error::MalformedVertList {
+                pos: source_pos(reader, (vert_list_pos.offset + err.offset) as u64),
+                message: err.to_string(),
+            });
         }
-    } else if let Some(vid) = vert_id {
-        if let Some((vl, verts)) = vertex_cache.get(&vid) {
-            resolved_vert_list = vl.clone();
-            resolved_verts = verts.clone();
-        } else {
-            eprintln!("Vertex data for VertID={} not found in cache", vid);
-            resolved_verts = Vec::new();
-            resolved_vert_list = String::new();
+        let shared: SharedVerts = (vert_list.into(), Arc::new(verts));
+        if let Some(vid) = attrs.vert_id {
+            ctx.vertex_cache.insert(vid, shared.clone());
         }
+        shared
+    } else if let Some(vid) = attrs.vert_id {
+        ctx.vertex_cache.get(&vid).cloned().unwrap_or_else(|| {
+            ctx.warnings.push(Lbrn2Error::DanglingVertId {
+                pos: shape_pos,
+                id: vid,
+            });
+            SharedVerts::default()
+        })
     } else {
-        resolved_verts = Vec::new();
-        resolved_vert_list = String::new();
-    }
+        SharedVerts::default()
+    };
 
-    if !prim_list.is_empty() {
-        resolved_prims = if prim_list == "LineClosed" {
+    let (resolved_prim_list, resolved_prims) = if !prim_list.is_empty() {
+        let prims = if prim_list == "LineClosed" {
             Vec::new()
         } else {
-            parse_prim_list(&prim_list)
+            let mut errors = Vec::new();
+            let prims = parse_prim_list_reporting(&prim_list, &mut errors);
+            for err in errors {
+                ctx.warnings.push(Lbrn2Error::MalformedPrimList {
+                    pos: source_pos(reader, (prim_list_pos.offset + err.offset) as u64),
+                    message: err.to_string(),
+                });
+            }
+            prims
         };
-        resolved_prim_list = prim_list.clone();
-        if let Some(pid) = prim_id {
-            primitive_cache.insert(pid, (prim_list, resolved_prims.clone()));
-        }
-    } else if let Some(pid) = prim_id {
-        if let Some((pl, prims)) = primitive_cache.get(&pid) {
-            resolved_prim_list = pl.clone();
-            resolved_prims = prims.clone();
-        } else {
-            eprintln!("Primitive data for PrimID={} not found in cache", pid);
-            resolved_prims = Vec::new();
-            resolved_prim_list = String::new();
+        let shared: SharedPrims = (prim_list.into(), Arc::new(prims));
+        if let Some(pid) = attrs.prim_id {
+            ctx.primitive_cache.insert(pid, shared.clone());
         }
+        shared
+    } else if let Some(pid) = attrs.prim_id {
+        ctx.primitive_cache.get(&pid).cloned().unwrap_or_else(|| {
+            ctx.warnings.push(Lbrn2Error::DanglingPrimId {
+                pos: shape_pos,
+                id: pid,
+            });
+            SharedPrims::default()
+        })
     } else {
-        resolved_prims = Vec::new();
-        resolved_prim_list = String::new();
-    }
+        SharedPrims::default()
+    };
+
+    let vert_id = attrs.vert_id.filter(|id| ctx.vertex_cache.contains_key(id));
+    let prim_id = attrs
+        .prim_id
+        .filter(|id| ctx.primitive_cache.contains_key(id));
+    let cut_index = attrs.cut_index;
 
     // Create the shape based on type
-    match shape_type.as_str() {
+    match attrs.shape_type.as_str() {
         "Rect" => Ok(Some(Shape::Rect(Rect {
             cut_index,
             xform,
-            w,
-            h,
-            cr,
+            w: attrs.w,
+            h: attrs.h,
+            cr: attrs.cr,
         }))),
         "Ellipse" => Ok(Some(Shape::Ellipse(Ellipse {
             cut_index,
             xform,
-            rx,
-            ry,
+            rx: attrs.rx,
+            ry: attrs.ry,
         }))),
         "Path" => {
             if resolved_verts.is_empty() {
-                eprintln!("Path shape has no vertices after resolution, skipping");
+                ctx.warnings.push(Lbrn2Error::EmptyPath { pos: shape_pos });
                 return Ok(None);
             }
+
+            let vert_count = resolved_verts.len();
+            for prim in resolved_prims.iter() {
+                let (start_idx, end_idx) = match prim {
+                    PathPrimitive::Line { start_idx, end_idx }
+                    | PathPrimitive::Bezier { start_idx, end_idx } => (*start_idx, *end_idx),
+                };
+                if start_idx >= vert_count || end_idx >= vert_count {
+                    ctx.warnings.push(Lbrn2Error::MalformedPrimList {
+                        pos: shape_pos,
+                        message: format!(
+                            "primitive {} {} references a vertex past the end of the {}-vertex list",
+                            start_idx, end_idx, vert_count
+                        ),
+                    });
+                }
+            }
+
             Ok(Some(Shape::Path(Path {
                 cut_index,
                 xform,
@@ -695,13 +857,15 @@ fn parse_shape_inner(
                 prim_list: resolved_prim_list,
                 parsed_verts: resolved_verts,
                 parsed_primitives: resolved_prims,
+                vert_id,
+                prim_id,
             })))
         }
         "Bitmap" => Ok(Some(Shape::Bitmap(Bitmap {
             cut_index,
             xform,
-            w,
-            h,
+            w: attrs.w,
+            h: attrs.h,
             data,
         }))),
         "Group" => {
@@ -718,49 +882,26 @@ fn parse_shape_inner(
     }
 }
 
-fn parse_shape_from_empty_element(
-    e: &quick_xml::events::BytesStart,
-) -> Result<Option<Shape>, String> {
-    let mut shape_type = String::new();
-    let mut cut_index: i32 = 0;
-    let mut w: f64 = 0.0;
-    let mut h: f64 = 0.0;
-    let mut cr: f64 = 0.0;
-    let mut rx: f64 = 0.0;
-    let mut ry: f64 = 0.0;
-
-    for attr in e.attributes().flatten() {
-        let key = std::str::from_utf8(attr.key.as_ref()).unwrap_or("");
-        let value = std::str::from_utf8(&attr.value).unwrap_or("");
-        match key {
-            "Type" => shape_type = value.to_string(),
-            "CutIndex" => cut_index = value.parse().unwrap_or(0),
-            "W" => w = value.parse().unwrap_or(0.0),
-            "H" => h = value.parse().unwrap_or(0.0),
-            "Cr" => cr = value.parse().unwrap_or(0.0),
-            "Rx" => rx = value.parse().unwrap_or(0.0),
-            "Ry" => ry = value.parse().unwrap_or(0.0),
-            _ => {}
-        }
-    }
-
+pub(crate) fn parse_shape_from_empty_element(e: &BytesStart) -> Option<Shape> {
+    let attrs = ShapeAttrs::from_element(e);
     let xform = XForm::identity();
 
-    match shape_type.as_str() {
-        "Rect" => Ok(Some(Shape::Rect(Rect {
-            cut_index,
+    match attrs.shape_type.as_str() {
+        "Rect" => Some(Shape::Rect(Rect {
+            cut_index: attrs.cut_index,
             xform,
-            w,
-            h,
-            cr,
-        }))),
-        "Ellipse" => Ok(Some(Shape::Ellipse(Ellipse {
-            cut_index,
+            w: attrs.w,
+            h: attrs.h,
+            cr: attrs.cr,
+        })),
+        "Ellipse" => Some(Shape::Ellipse(Ellipse {
+            cut_index: attrs.cut_index,
             xform,
-            rx,
-            ry,
-        }))),
-        _ => Ok(None),
+            rx: attrs.rx,
+            ry: attrs.ry,
+        })),
+        "Text" => Some(Shape::Text(attrs.into_text(xform, None))),
+        _ => None,
     }
 }
 
@@ -838,6 +979,42 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_try_parse_lists_report_first_error() {
+        assert_eq!(
+            try_parse_vert_list("V0 0c0x1e-3V1 2").unwrap(),
+            vec![
+                Vec2::with_control_points(0.0, 0.0, Some(0.001), None, None, None),
+                Vec2::new(1.0, 2.0),
+            ]
+        );
+
+        let err = try_parse_vert_list("V0 0V1 -V2").unwrap_err();
+        assert_eq!(err.offset, 4);
+        assert_eq!(err.item, 1);
+        assert_eq!(err.kind, ListErrorKind::InvalidNumber);
+        assert_eq!(
+            try_parse_vert_list("V0 0V2").unwrap_err().kind,
+            ListErrorKind::MissingCoordinate
+        );
+
+        let err = try_parse_prim_list("L0 1 B1").unwrap_err();
+        assert_eq!(err.offset, 5);
+        assert_eq!(
+            err.kind,
+            ListErrorKind::MissingIndex {
+                primitive: 'B',
+                found: 1
+            }
+        );
+        // Multi-byte characters are reported whole
+        let err = try_parse_prim_list("L0 1→").unwrap_err();
+        assert_eq!(err.kind, ListErrorKind::UnexpectedChar('→'));
+        assert_eq!(err.to_string(), "unexpected character '→'");
+        // Indices too large for usize are treated as missing
+        assert!(try_parse_prim_list("L0 99999999999999999999999").is_err());
+    }
+
     #[test]
     fn test_parse_circle() {
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -859,4 +1036,210 @@ mod tests {
             _ => panic!("Expected Ellipse"),
         }
     }
+
+    #[test]
+    fn test_invalid_xform_reports_warning() {
+        assert_eq!(try_parse_xform("1 0 0 1 55"), None);
+        assert_eq!(try_parse_xform("1 0 0 1 55 55 7"), None);
+        assert_eq!(parse_xform("garbage"), XForm::identity());
+
+        let xml = "<LightBurnProject>\n  <Shape Type=\"Rect\" W=\"1\" H=\"1\">\n    <XForm>1 0 0 x 0 0</XForm>\n  </Shape>\n</LightBurnProject>";
+        let (project, warnings) = parse_lbrn2_with_warnings(xml).unwrap();
+        assert_eq!(project.shapes.len(), 1);
+        assert_eq!(warnings.len(), 1);
+        match &warnings[0] {
+            Lbrn2Error::InvalidXForm { pos, value } => {
+                assert_eq!(value, "1 0 0 x 0 0");
+                assert_eq!(pos.line, 3);
+                assert_eq!(&xml[pos.offset..pos.offset + 5], "1 0 0");
+            }
+            other => panic!("Expected InvalidXForm, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dangling_vert_id_reports_shape_position() {
+        let xml = r#"<LightBurnProject>
+  <Shape Type="Path" CutIndex="0" VertID="3" PrimID="3">
+    <XForm>1 0 0 1 0 0</XForm>
+  </Shape>
+</LightBurnProject>"#;
+        let (project, warnings) = parse_lbrn2_with_warnings(xml).unwrap();
+        assert!(project.shapes.is_empty());
+        assert_eq!(warnings.len(), 3);
+        assert!(matches!(
+            warnings[0],
+            Lbrn2Error::DanglingVertId { id: 3, .. }
+        ));
+        assert!(matches!(
+            warnings[1],
+            Lbrn2Error::DanglingPrimId { id: 3, .. }
+        ));
+        assert!(matches!(warnings[2], Lbrn2Error::EmptyPath { .. }));
+        for w in &warnings {
+            assert_eq!(w.pos().line, 2);
+            assert!(xml[w.pos().offset..].starts_with("<Shape"));
+        }
+    }
+
+    #[test]
+    fn test_malformed_lists_report_offsets() {
+        let xml = r#"<LightBurnProject>
+  <Shape Type="Path" CutIndex="0">
+    <VertList>V0 0V1 zV2 2</VertList>
+    <PrimList>L0 1Q1 2L1 5</PrimList>
+  </Shape>
+</LightBurnProject>"#;
+        let (project, warnings) = parse_lbrn2_with_warnings(xml).unwrap();
+        assert_eq!(project.shapes.len(), 1);
+
+        let vert_issue = warnings
+            .iter()
+            .find(|w| matches!(w, Lbrn2Error::MalformedVertList { .. }))
+            .expect("VertList warning");
+        assert_eq!(vert_issue.pos().line, 3);
+        assert!(xml[vert_issue.pos().offset..].starts_with("V1 z"));
+
+        let prim_issues: Vec<_> = warnings
+            .iter()
+            .filter(|w| matches!(w, Lbrn2Error::MalformedPrimList { .. }))
+            .collect();
+        // One unknown primitive, one out-of-range vertex reference
+        assert_eq!(prim_issues.len(), 2);
+        assert!(xml[prim_issues[0].pos().offset..].starts_with("Q1 2"));
+    }
+
+    #[test]
+    fn test_xml_error_is_fatal() {
+        let err = parse_lbrn2_complete("<LightBurnProject>\n<Shape></Oops>").unwrap_err();
+        match err {
+            Lbrn2Error::Xml { pos, .. } => assert_eq!(pos.line, 2),
+            other => panic!("Expected Xml error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_cut_settings() {
+        let xml = r#"<LightBurnProject>
+  <CutSetting type="Scan">
+    <index Value="3"/>
+    <name Value="Fill &amp; engrave"/>
+    <minPower Value="10"/>
+    <maxPower Value="35.5"/>
+    <speed Value="150"/>
+    <numPasses Value="2"/>
+    <priority Value="1"/>
+    <tabsEnabled Value="1"/>
+    <tabSize Value="0.5"/>
+    <tabCount Value="3"/>
+    <doOutput Value="0"/>
+    <interval Value="0.1"/>
+    <kerf Value="n/a"/>
+  </CutSetting>
+  <CutSetting_Img type="Image">
+    <index Value="31"/>
+    <dpi Value="254"/>
+  </CutSetting_Img>
+</LightBurnProject>"#;
+
+        let project = parse_lbrn2_complete(xml).unwrap();
+        assert_eq!(project.cut_settings.len(), 2);
+
+        let cs = &project.cut_settings[0];
+        assert_eq!(cs.index, 3);
+        assert_eq!(cs.name, "Fill & engrave");
+        assert_eq!(cs.cut_type, CutType::Scan);
+        assert_eq!(cs.color.as_deref(), Some("#00E000"));
+        assert_eq!(cs.min_power, Some(10.0));
+        assert_eq!(cs.max_power, Some(35.5));
+        assert_eq!(cs.speed, Some(150.0));
+        assert_eq!(cs.num_passes, Some(2));
+        assert_eq!(cs.priority, Some(1));
+        assert_eq!(cs.z_offset, None);
+        assert_eq!(cs.tabs.enabled, Some(true));
+        assert_eq!(cs.tabs.size, Some(0.5));
+        assert_eq!(cs.tabs.count, Some(3));
+        assert_eq!(cs.do_output, Some(false));
+        assert_eq!(cs.params.get("interval").map(String::as_str), Some("0.1"));
+        // Unparsable known parameters are kept rather than dropped
+        assert_eq!(cs.kerf, None);
+        assert_eq!(cs.params.get("kerf").map(String::as_str), Some("n/a"));
+
+        let img = &project.cut_settings[1];
+        assert_eq!(img.index, 31);
+        assert_eq!(img.cut_type, CutType::Image);
+        assert_eq!(img.color, None);
+        assert_eq!(img.params.get("dpi").map(String::as_str), Some("254"));
+    }
+
+    #[test]
+    fn test_parse_text_keeps_metadata() {
+        let xml = r#"<LightBurnProject>
+  <Shape Type="Text" CutIndex="1" Font="Sans Serif,-1,100,5,75,1,0,0,0,0" Str="A &amp; B" H="8" LS="0.5" LnS="1" Ah="1" Av="2" Weld="1" HasBackupPath="0">
+    <XForm>1 0 0 1 10 20</XForm>
+  </Shape>
+  <Shape Type="Text" CutIndex="0" Str="x" H="5" HasBackupPath="1">
+    <BackupPath Type="Path" CutIndex="0">
+      <XForm>1 0 0 1 12 20</XForm>
+      <VertList>V0 0V1 0</VertList>
+      <PrimList>L0 1</PrimList>
+    </BackupPath>
+    <XForm>1 0 0 1 10 20</XForm>
+  </Shape>
+</LightBurnProject>"#;
+
+        let (project, warnings) = parse_lbrn2_with_warnings(xml).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(project.shapes.len(), 2);
+
+        let Shape::Text(text) = &project.shapes[0] else {
+            panic!("Expected Text, got {:?}", project.shapes[0]);
+        };
+        assert_eq!(text.text, "A & B");
+        assert_eq!(text.font_family(), "Sans Serif");
+        assert!(text.is_bold());
+        assert!(text.is_italic());
+        assert_eq!(text.h, 8.0);
+        assert_eq!(text.letter_spacing, 0.5);
+        assert_eq!(text.line_spacing, 1.0);
+        assert_eq!((text.align_h, text.align_v), (1, 2));
+        assert!(text.weld);
+        assert_eq!(text.xform.e, 10.0);
+        assert!(text.backup_path.is_none());
+
+        // The backup path is re-expressed relative to the text
+        let Shape::Text(text) = &project.shapes[1] else {
+            panic!("Expected Text, got {:?}", project.shapes[1]);
+        };
+        let outline = text.outline().expect("backup path");
+        assert_eq!(outline.xform, text.xform);
+        assert_eq!(outline.parsed_verts[0].x, 2.0);
+        assert_eq!(outline.parsed_verts[1].x, 3.0);
+    }
+
+    #[test]
+    fn test_parse_project_metadata() {
+        let xml = r#"<LightBurnProject AppVersion="1.7.08" DeviceName="JCZFiber (LMC4)" FormatVersion="1" MaterialHeight="2.5" MirrorX="True" MirrorY="False">
+  <Thumbnail Source="iVBORw0KGgo="/>
+  <UIPrefs>
+    <Optimize_ByLayer Value="0"/>
+    <Optimize_ByGroup Value="-1"/>
+  </UIPrefs>
+  <Notes ShowOnLoad="1" Notes="Use 3mm &amp; tape"/>
+</LightBurnProject>"#;
+
+        let project = parse_lbrn2_complete(xml).unwrap();
+        assert_eq!(project.device_name.as_deref(), Some("JCZFiber (LMC4)"));
+        assert_eq!(project.material_height, Some(2.5));
+        assert!(project.mirror_x);
+        assert!(!project.mirror_y);
+        assert_eq!(project.thumbnail.as_deref(), Some("iVBORw0KGgo="));
+        assert_eq!(
+            project.ui_prefs.get("Optimize_ByGroup").map(String::as_str),
+            Some("-1")
+        );
+        let notes = project.notes.expect("notes");
+        assert_eq!(notes.text, "Use 3mm & tape");
+        assert!(notes.show_on_load);
+    }
 }
@@ -1,15 +1,87 @@
-use super::types::{Path, PathPrimitive, Vec2};
+use super::types::{Path, PathPrimitive, Vec2, XForm};
+use std::sync::Arc;
 
 /// Format a number with 6 decimal places
 fn f(n: f64) -> String {
     format!("{:.6}", n)
 }
 
+/// How numbers are written into SVG output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// Digits after the decimal point (default: 6)
+    pub precision: usize,
+    /// Drop trailing zeros, e.g. `1.500000` becomes `1.5` (default: false)
+    pub trim_zeros: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            precision: 6,
+            trim_zeros: false,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn format(&self, n: f64) -> String {
+        let s = format!("{:.*}", self.precision, n);
+        if !self.trim_zeros || !s.contains('.') {
+            return s;
+        }
+        match s.trim_end_matches('0').trim_end_matches('.') {
+            "-0" => "0".to_string(),
+            trimmed => trimmed.to_string(),
+        }
+    }
+}
+
+/// Apply `m` to every vertex and control point of a path
+pub(crate) fn transform_path(path: &Path, m: &XForm) -> Path {
+    let point = |x: Option<f64>, y: Option<f64>| match (x, y) {
+        (Some(x), Some(y)) => {
+            let (x, y) = m.transform_point(x, y);
+            (Some(x), Some(y))
+        }
+        other => other,
+    };
+
+    let mut out = path.clone();
+    out.parsed_verts = Arc::new(
+        path.parsed_verts
+            .iter()
+            .map(|v| {
+                let (x, y) = m.transform_point(v.x, v.y);
+                let (c0x, c0y) = point(v.c0x, v.c0y);
+                let (c1x, c1y) = point(v.c1x, v.c1y);
+                Vec2::with_control_points(x, y, c0x, c0y, c1x, c1y)
+            })
+            .collect(),
+    );
+    // The moved vertices are no longer the ones shared under the ID
+    out.vert_id = None;
+    out.xform = XForm::identity();
+    out
+}
+
 /// Generate SVG path data (d attribute) from a Path shape
 pub fn generate_path_data(path: &Path, log: &mut Vec<String>) -> String {
+    generate_path_data_with_format(path, &NumberFormat::default(), log)
+}
+
+/// Generate SVG path data (d attribute) from a Path shape, writing numbers
+/// with the given format
+pub fn generate_path_data_with_format(
+    path: &Path,
+    fmt: &NumberFormat,
+    log: &mut Vec<String>,
+) -> String {
+    let f = |n: f64| fmt.format(n);
+
     // Handle LineClosed explicitly
-    if path.prim_list == "LineClosed" {
-        return generate_line_closed_path(path, log);
+    if path.is_line_closed() {
+        return generate_line_closed_path(path, fmt, log);
     }
 
     // Existing logic for explicit primitives
@@ -29,7 +101,7 @@ pub fn generate_path_data(path: &Path, log: &mut Vec<String>) -> String {
     let mut first_move_to_idx: Option<usize> = None;
     let mut current_last_idx: Option<usize> = None;
 
-    for prim in &path.parsed_primitives {
+    for prim in path.parsed_primitives.iter() {
         match prim {
             PathPrimitive::Line { start_idx, end_idx } => {
                 let idx0 = *start_idx;
@@ -115,7 +187,9 @@ pub fn generate_path_data(path: &Path, log: &mut Vec<String>) -> String {
     d
 }
 
-fn generate_line_closed_path(path: &Path, log: &mut Vec<String>) -> String {
+fn generate_line_closed_path(path: &Path, fmt: &NumberFormat, log: &mut Vec<String>) -> String {
+    let f = |n: f64| fmt.format(n);
+
     if path.parsed_verts.is_empty() {
         log.push(format!(
             "Path {} or parsedVerts/parsedPrimitives missing/empty, skipping.",
@@ -346,6 +420,21 @@ pub fn generate_path_data_from_parts(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_number_format() {
+        let fixed = NumberFormat::default();
+        assert_eq!(fixed.format(1.5), "1.500000");
+
+        let compact = NumberFormat {
+            precision: 3,
+            trim_zeros: true,
+        };
+        assert_eq!(compact.format(1.5), "1.5");
+        assert_eq!(compact.format(2.0), "2");
+        assert_eq!(compact.format(-0.0001), "0");
+        assert_eq!(compact.format(12.3456), "12.346");
+    }
+
     #[test]
     fn test_line_closed_with_0_vertices() {
         let mut log = Vec::new();
@@ -0,0 +1,306 @@
+//! Streaming LBRN2 reader
+//!
+//! [`Lbrn2Reader`] pulls shapes out of a document one at a time instead of
+//! building the whole project, so a file far larger than memory can be
+//! converted shape by shape. Vertex and primitive lists defined under a
+//! `VertID`/`PrimID` are parsed once and shared through `Arc` by every shape
+//! that reuses them, and bitmap data stays base64 text until
+//! [`Bitmap::decode_data`](super::types::Bitmap::decode_data) is called.
+
+use super::error::{Lbrn2Error, SourcePos};
+use super::parser::{
+    ParseContext, XmlReader, parse_cut_setting_inner, parse_project_attributes,
+    parse_project_child, parse_shape, parse_shape_from_empty_element, parse_value_params,
+    xml_error,
+};
+use super::types::{LightBurnProject, Shape};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::iter::FusedIterator;
+
+/// Byte source that remembers where the lines it has passed over start
+///
+/// Only newlines after the last [`forget_before`](Self::forget_before) mark
+/// are kept, so positions can be reported for the element being parsed
+/// without holding on to the document.
+pub(crate) struct LineCounter<R> {
+    inner: R,
+    /// Absolute offset of the next byte to be consumed
+    consumed: usize,
+    /// Absolute offsets of the newlines consumed since the last mark
+    newlines: VecDeque<usize>,
+    /// Line number at the last mark
+    base_line: usize,
+}
+
+impl<R> LineCounter<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            consumed: 0,
+            newlines: VecDeque::new(),
+            base_line: 1,
+        }
+    }
+
+    /// Line and offset of a byte already read; offsets before the last mark
+    /// resolve to the line of the mark
+    pub(crate) fn pos(&self, offset: usize) -> SourcePos {
+        let offset = offset.min(self.consumed);
+        let line = self.base_line + self.newlines.partition_point(|&nl| nl < offset);
+        SourcePos { offset, line }
+    }
+
+    /// Drop line information for everything before `offset`
+    pub(crate) fn forget_before(&mut self, offset: usize) {
+        while self.newlines.front().is_some_and(|&nl| nl < offset) {
+            self.newlines.pop_front();
+            self.base_line += 1;
+        }
+    }
+}
+
+/// Append the absolute offsets of the newlines in `bytes`, which start at
+/// offset `start`
+fn push_newlines(newlines: &mut VecDeque<usize>, start: usize, bytes: &[u8]) {
+    newlines.extend(
+        bytes
+            .iter()
+            .enumerate()
+            .filter(|&(_, &b)| b == b'\n')
+            .map(|(i, _)| start + i),
+    );
+}
+
+impl<R: Read> Read for LineCounter<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(out)?;
+        push_newlines(&mut self.newlines, self.consumed, &out[..n]);
+        self.consumed += n;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for LineCounter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The bytes about to be consumed are still buffered, so looking at
+        // them again costs no I/O
+        if let Ok(buf) = self.inner.fill_buf() {
+            push_newlines(
+                &mut self.newlines,
+                self.consumed,
+                &buf[..amt.min(buf.len())],
+            );
+        }
+        self.consumed += amt;
+        self.inner.consume(amt);
+    }
+}
+
+/// Pull parser yielding the shapes of an LBRN2 document one at a time
+///
+/// Only the shape being parsed and the lists shared by `VertID`/`PrimID`
+/// are held in memory. Project attributes, cut settings, notes and the
+/// thumbnail are collected in [`project`](Self::project) as they are passed;
+/// its `shapes` stay empty.
+///
+/// ```rust,ignore
+/// use laser_tools::lbrn2::Lbrn2Reader;
+///
+/// let mut reader = Lbrn2Reader::open("large.lbrn2")?;
+/// for shape in reader.shapes() {
+///     let shape = shape?;
+///     // process and drop the shape
+/// }
+/// let cut_settings = &reader.project().cut_settings;
+/// ```
+pub struct Lbrn2Reader<R> {
+    reader: XmlReader<R>,
+    project: LightBurnProject,
+    ctx: ParseContext,
+    buf: Vec<u8>,
+    finished: bool,
+}
+
+impl Lbrn2Reader<BufReader<File>> {
+    /// Open an LBRN2 file for streaming
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> Lbrn2Reader<R> {
+    /// Stream an LBRN2 document from any buffered source, such as
+    /// `xml.as_bytes()` or a `BufReader<File>`
+    pub fn new(source: R) -> Self {
+        Self {
+            reader: Reader::from_reader(LineCounter::new(source)),
+            project: LightBurnProject::default(),
+            ctx: ParseContext::default(),
+            buf: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// The next top-level shape, or `None` at the end of the document
+    ///
+    /// Malformed XML ends the stream with an error; every later call
+    /// returns `Ok(None)`.
+    pub fn next_shape(&mut self) -> Result<Option<Shape>, Lbrn2Error> {
+        if self.finished {
+            return Ok(None);
+        }
+        let next = self.read_shape();
+        if !matches!(next, Ok(Some(_))) {
+            self.finished = true;
+        }
+        next
+    }
+
+    /// Iterate over the remaining shapes
+    pub fn shapes(&mut self) -> Shapes<'_, R> {
+        Shapes { reader: self }
+    }
+
+    /// Everything but the shapes that has been read so far
+    ///
+    /// Metadata written after the shapes, such as `<Notes>`, is only
+    /// present once the shapes have been exhausted.
+    pub fn project(&self) -> &LightBurnProject {
+        &self.project
+    }
+
+    /// Take the recoverable problems found since the last call
+    pub fn take_warnings(&mut self) -> Vec<Lbrn2Error> {
+        std::mem::take(&mut self.ctx.warnings)
+    }
+
+    /// Consume the reader, returning the metadata read so far along with
+    /// the lists shared by `VertID`/`PrimID`
+    pub fn into_project(mut self) -> LightBurnProject {
+        self.ctx.share_into(&mut self.project);
+        self.project
+    }
+
+    fn read_shape(&mut self) -> Result<Option<Shape>, Lbrn2Error> {
+        loop {
+            self.buf.clear();
+            let event_start = self.reader.buffer_position();
+            // Nothing before a top-level event is ever reported on again
+            self.reader.get_mut().forget_before(event_start as usize);
+
+            match self.reader.read_event_into(&mut self.buf) {
+                Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                    b"LightBurnProject" => parse_project_attributes(e, &mut self.project),
+                    b"CutSetting" | b"CutSetting_Img" => {
+                        let cs = parse_cut_setting_inner(&mut self.reader, e)?;
+                        self.project.cut_settings.push(cs);
+                    }
+                    b"UIPrefs" => self.project.ui_prefs = parse_value_params(&mut self.reader)?,
+                    b"Notes" | b"Thumbnail" => parse_project_child(e, &mut self.project),
+                    b"Shape" => {
+                        if let Some(shape) =
+                            parse_shape(&mut self.reader, e, event_start, &mut self.ctx)?
+                        {
+                            return Ok(Some(shape));
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Empty(ref e)) => match e.name().as_ref() {
+                    // A project with no content may be written self-closing
+                    b"LightBurnProject" => parse_project_attributes(e, &mut self.project),
+                    b"Notes" | b"Thumbnail" => parse_project_child(e, &mut self.project),
+                    b"Shape" => {
+                        if let Some(shape) = parse_shape_from_empty_element(e) {
+                            return Ok(Some(shape));
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) => return Ok(None),
+                Err(e) => return Err(xml_error(&self.reader, e)),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Iterator over the shapes of an [`Lbrn2Reader`]
+pub struct Shapes<'a, R> {
+    reader: &'a mut Lbrn2Reader<R>,
+}
+
+impl<R: BufRead> Iterator for Shapes<'_, R> {
+    type Item = Result<Shape, Lbrn2Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_shape().transpose()
+    }
+}
+
+impl<R: BufRead> FusedIterator for Shapes<'_, R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const SHARED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<LightBurnProject AppVersion="1.7.08" FormatVersion="1">
+    <CutSetting type="Cut">
+        <index Value="0"/>
+    </CutSetting>
+    <Shape Type="Path" CutIndex="0" VertID="0" PrimID="0">
+        <XForm>1 0 0 1 0 0</XForm>
+        <VertList>V0 0V10 0V10 10</VertList>
+        <PrimList>L0 1L1 2</PrimList>
+    </Shape>
+    <Shape Type="Path" CutIndex="0" VertID="0" PrimID="0">
+        <XForm>1 0 0 1 20 0</XForm>
+    </Shape>
+    <Notes ShowOnLoad="0" Notes="done"/>
+</LightBurnProject>"#;
+
+    #[test]
+    fn test_shapes_share_lists_by_id() {
+        let mut reader = Lbrn2Reader::new(SHARED.as_bytes());
+        let shapes: Vec<Shape> = reader.shapes().collect::<Result<_, _>>().unwrap();
+        assert_eq!(shapes.len(), 2);
+        assert_eq!(reader.project().cut_settings.len(), 1);
+        assert!(reader.project().shapes.is_empty());
+        assert_eq!(reader.project().notes.as_ref().unwrap().text, "done");
+
+        let (Shape::Path(a), Shape::Path(b)) = (&shapes[0], &shapes[1]) else {
+            panic!("expected two paths");
+        };
+        assert!(Arc::ptr_eq(&a.parsed_verts, &b.parsed_verts));
+        assert!(Arc::ptr_eq(&a.parsed_primitives, &b.parsed_primitives));
+        assert_eq!(b.xform.e, 20.0);
+    }
+
+    #[test]
+    fn test_stream_matches_string_parse_and_stops_after_error() {
+        // A tiny buffer forces events to straddle refills
+        let source = BufReader::with_capacity(7, SHARED.as_bytes());
+        let mut reader = Lbrn2Reader::new(source);
+        let streamed: Vec<Shape> = reader.shapes().collect::<Result<_, _>>().unwrap();
+        let mut project = reader.into_project();
+        project.shapes = streamed;
+        assert_eq!(project, super::super::parse_lbrn2(SHARED).unwrap());
+
+        let broken = "<LightBurnProject>\n<Shape Type=\"Rect\" W=\"1\" H=\"1\"/>\n</Oops>";
+        let mut reader = Lbrn2Reader::new(broken.as_bytes());
+        assert!(matches!(reader.next_shape(), Ok(Some(Shape::Rect(_)))));
+        let err = reader.next_shape().unwrap_err();
+        assert_eq!(err.pos().line, 3);
+        assert!(reader.next_shape().unwrap().is_none());
+    }
+}
@@ -0,0 +1,484 @@
+//! Job statistics for quoting
+//!
+//! Measures cut length, area, travel and run time for each layer.
+
+use super::bounds::{Bounds, get_transformed_bounds};
+use super::gcode::layer_order;
+use super::geometry::{Point, distance, even_odd_holes, integrate, shape_bezpaths};
+use super::path::NumberFormat;
+use super::svg::flatten_groups;
+use super::types::{Ellipse, LightBurnProject, Shape, XForm};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Options for [`project_stats_with_options`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsOptions {
+    /// Speed of rapid moves between contours in mm/s (default: 100)
+    pub travel_speed: f64,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self {
+            travel_speed: 100.0,
+        }
+    }
+}
+
+/// Measurements of one cut setting's shapes
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerStats {
+    pub cut_index: i32,
+    /// Name of the cut setting, empty when it has none
+    pub name: String,
+    /// Whether the layer is sent to the laser (`doOutput`)
+    pub output: bool,
+    /// Shapes on the layer, with groups counted by their members
+    pub shapes: usize,
+    /// Connected contours the shapes break into
+    pub contours: usize,
+    /// Length of one pass in mm
+    pub length: f64,
+    /// Area enclosed by the closed contours in mm², less the holes: contours
+    /// inside an odd number of others on the layer
+    pub area: f64,
+    /// Workspace bounds (Y up), `None` when nothing on the layer has extent
+    pub bounds: Option<Bounds>,
+    pub passes: u32,
+    /// Speed in mm/s, when the cut setting has one
+    pub speed: Option<f64>,
+    /// Seconds spent burning all passes, when the speed is known
+    pub time: Option<f64>,
+}
+
+/// Measurements of a whole project, see [`project_stats`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectStats {
+    /// Layers in burn order
+    pub layers: Vec<LayerStats>,
+    /// Length of the rapid moves between contours in mm
+    pub travel_length: f64,
+    /// Seconds spent on rapid moves
+    pub travel_time: f64,
+    /// Seconds for the whole job, when every output layer has a speed
+    pub estimated_time: Option<f64>,
+}
+
+impl ProjectStats {
+    /// Shapes on all layers
+    pub fn shapes(&self) -> usize {
+        self.layers.iter().map(|l| l.shapes).sum()
+    }
+
+    /// Cut length of one pass over all layers in mm
+    pub fn length(&self) -> f64 {
+        self.layers.iter().map(|l| l.length).sum()
+    }
+
+    /// Area enclosed on all layers in mm²
+    pub fn area(&self) -> f64 {
+        self.layers.iter().map(|l| l.area).sum()
+    }
+
+    /// Machine-readable form of the statistics
+    ///
+    /// Lengths are in mm, areas in mm² and times in seconds; values that are
+    /// not known are `null`.
+    pub fn to_json(&self) -> String {
+        let fmt = NumberFormat {
+            precision: 3,
+            trim_zeros: true,
+        };
+        let num = |n: f64| fmt.format(n);
+        let opt = |n: Option<f64>| n.map_or("null".to_string(), num);
+
+        let layers: Vec<String> = self
+            .layers
+            .iter()
+            .map(|l| {
+                let bounds = l.bounds.map_or("null".to_string(), |b| {
+                    format!(
+                        "{{\"min_x\": {}, \"min_y\": {}, \"max_x\": {}, \"max_y\": {}}}",
+                        num(b.min_x),
+                        num(b.min_y),
+                        num(b.max_x),
+                        num(b.max_y)
+                    )
+                });
+                format!(
+                    "    {{\"cut_index\": {}, \"name\": {}, \"output\": {}, \"shapes\": {}, \
+                     \"contours\": {}, \"length\": {}, \"area\": {}, \"bounds\": {}, \
+                     \"passes\": {}, \"speed\": {}, \"time\": {}}}",
+                    l.cut_index,
+                    json_string(&l.name),
+                    l.output,
+                    l.shapes,
+                    l.contours,
+                    num(l.length),
+                    num(l.area),
+                    bounds,
+                    l.passes,
+                    opt(l.speed),
+                    opt(l.time)
+                )
+            })
+            .collect();
+        let layers = if layers.is_empty() {
+            "[]".to_string()
+        } else {
+            format!("[\n{}\n  ]", layers.join(",\n"))
+        };
+
+        format!(
+            "{{\n  \"layers\": {},\n  \"shapes\": {},\n  \"length\": {},\n  \"area\": {},\n  \
+             \"travel_length\": {},\n  \"travel_time\": {},\n  \"estimated_time\": {}\n}}\n",
+            layers,
+            self.shapes(),
+            num(self.length()),
+            num(self.area()),
+            num(self.travel_length),
+            num(self.travel_time),
+            opt(self.estimated_time)
+        )
+    }
+}
+
+impl fmt::Display for ProjectStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fmt = NumberFormat {
+            precision: 2,
+            trim_zeros: true,
+        };
+        let num = |n: f64| fmt.format(n);
+
+        for l in &self.layers {
+            write!(f, "Layer C{:02}", l.cut_index)?;
+            if !l.name.is_empty() {
+                write!(f, " ({})", l.name)?;
+            }
+            if !l.output {
+                write!(f, " [output off]")?;
+            }
+            writeln!(f, ": {} shapes, {} contours", l.shapes, l.contours)?;
+            write!(f, "  Length: {} mm", num(l.length))?;
+            if l.passes > 1 {
+                write!(f, " x {} passes", l.passes)?;
+            }
+            writeln!(f)?;
+            writeln!(f, "  Area:   {} mm²", num(l.area))?;
+            if let Some(b) = l.bounds {
+                writeln!(
+                    f,
+                    "  Bounds: X{} Y{} to X{} Y{} ({} x {} mm)",
+                    num(b.min_x),
+                    num(b.min_y),
+                    num(b.max_x),
+                    num(b.max_y),
+                    num(b.width()),
+                    num(b.height())
+                )?;
+            }
+            match (l.speed, l.time) {
+                (Some(speed), Some(time)) => writeln!(
+                    f,
+                    "  Time:   {} at {} mm/s",
+                    format_duration(time),
+                    num(speed)
+                )?,
+                _ => writeln!(f, "  Time:   unknown (no speed)")?,
+            }
+        }
+
+        writeln!(
+            f,
+            "Total: {} shapes, {} mm cut, {} mm² enclosed",
+            self.shapes(),
+            num(self.length()),
+            num(self.area())
+        )?;
+        writeln!(
+            f,
+            "Travel: {} mm, {}",
+            num(self.travel_length),
+            format_duration(self.travel_time)
+        )?;
+        match self.estimated_time {
+            Some(time) => writeln!(f, "Estimated time: {}", format_duration(time)),
+            None => writeln!(f, "Estimated time: unknown (a layer has no speed)"),
+        }
+    }
+}
+
+/// Seconds as `1h 02m 03s`, `2m 05s` or `12.3s`
+fn format_duration(seconds: f64) -> String {
+    if seconds < 60.0 {
+        return format!("{:.1}s", seconds);
+    }
+    let total = seconds.round() as u64;
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else {
+        format!("{}m {:02}s", m, s)
+    }
+}
+
+/// JSON string literal with quotes, backslashes and control characters escaped
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Perimeter of an ellipse under its transform, integrated over the angle
+///
+/// The Bezier outline every other export draws is a close approximation;
+/// this is the length of the true ellipse.
+fn ellipse_length(ellipse: &Ellipse) -> f64 {
+    let m = &ellipse.xform;
+    let speed = |t: f64| {
+        let (dx, dy) = (-ellipse.rx * t.sin(), ellipse.ry * t.cos());
+        (m.a * dx + m.c * dy).hypot(m.b * dx + m.d * dy)
+    };
+    integrate(&speed, 0.0, std::f64::consts::TAU)
+}
+
+/// Area of an ellipse under its transform
+fn ellipse_area(ellipse: &Ellipse) -> f64 {
+    let m = &ellipse.xform;
+    std::f64::consts::PI * ellipse.rx * ellipse.ry * (m.a * m.d - m.b * m.c).abs()
+}
+
+/// Measure a project with the default [`StatsOptions`]
+pub fn project_stats(project: &LightBurnProject) -> ProjectStats {
+    project_stats_with_options(project, &StatsOptions::default())
+}
+
+/// Measure a project
+///
+/// Lengths are exact for lines, and integrated numerically for Bezier
+/// segments and ellipses. The time estimate assumes the laser holds the
+/// layer speed throughout and ignores acceleration, so it is a lower bound.
+/// Scan and fill layers are estimated as tracing their outlines, the way
+/// the G-code export burns them.
+pub fn project_stats_with_options(
+    project: &LightBurnProject,
+    options: &StatsOptions,
+) -> ProjectStats {
+    let mirrored = project.with_mirror_applied();
+    let project = mirrored.as_ref();
+
+    let mut leaves = Vec::new();
+    flatten_groups(&project.shapes, None, &mut leaves);
+    let used: BTreeSet<i32> = leaves.iter().map(Shape::cut_index).collect();
+
+    let mut layers = Vec::new();
+    let mut position: Point = (0.0, 0.0);
+    let mut travel_length = 0.0;
+    let mut travel = |position: &mut Point, to: Point| {
+        travel_length += distance(*position, to);
+        *position = to;
+    };
+
+    for index in layer_order(project, &used) {
+        let cs = project.cut_settings.iter().find(|cs| cs.index == index);
+        let mut layer = LayerStats {
+            cut_index: index,
+            name: cs.map(|cs| cs.name.clone()).unwrap_or_default(),
+            output: cs.and_then(|cs| cs.do_output) != Some(false),
+            shapes: 0,
+            contours: 0,
+            length: 0.0,
+            area: 0.0,
+            bounds: None,
+            passes: cs.and_then(|cs| cs.num_passes).unwrap_or(1).max(1),
+            speed: cs.and_then(|cs| cs.speed).filter(|&speed| speed > 0.0),
+            time: None,
+        };
+
+        // Start and end of every contour, in burn order
+        let mut ends: Vec<(Point, Point)> = Vec::new();
+        // Closed contours, flattened for the nesting test, with their area
+        let mut closed: Vec<(Vec<Point>, f64)> = Vec::new();
+        for shape in leaves.iter().filter(|s| s.cut_index() == index) {
+            layer.shapes += 1;
+            if let Some(b) = get_transformed_bounds(shape) {
+                // Bounds are Y-down; flip back to LightBurn's Y-up workspace
+                let b = Bounds::new(b.min_x, -b.max_y, b.max_x, -b.min_y);
+                match &mut layer.bounds {
+                    None => layer.bounds = Some(b),
+                    Some(acc) => acc.expand(&b),
+                }
+            }
+
+            let paths = shape_bezpaths(shape, &XForm::identity(), &mut Vec::new());
+            layer.contours += paths.len();
+            for path in &paths {
+                if let (Some(first), Some(last)) = (path.segments.first(), path.segments.last()) {
+                    ends.push((first.start(), last.end()));
+                }
+            }
+            if let Shape::Ellipse(ellipse) = shape {
+                layer.length += ellipse_length(ellipse);
+            } else {
+                layer.length += paths.iter().map(|p| p.length()).sum::<f64>();
+            }
+            for path in paths.iter().filter(|p| p.closed) {
+                let area = match shape {
+                    Shape::Ellipse(ellipse) => ellipse_area(ellipse),
+                    _ => path.signed_area().abs(),
+                };
+                closed.push((path.flatten(0.01).points, area));
+            }
+        }
+        let rings: Vec<(i32, &[Point])> = closed
+            .iter()
+            .map(|(ring, _)| (index, ring.as_slice()))
+            .collect();
+        layer.area = even_odd_holes(&rings)
+            .into_iter()
+            .zip(&closed)
+            .map(|(hole, &(_, area))| if hole { -area } else { area })
+            .sum();
+
+        if layer.output {
+            for _ in 0..layer.passes {
+                for &(start, end) in &ends {
+                    travel(&mut position, start);
+                    position = end;
+                }
+            }
+        }
+        layer.time = layer
+            .speed
+            .map(|speed| layer.length * layer.passes as f64 / speed);
+        layers.push(layer);
+    }
+    travel(&mut position, (0.0, 0.0));
+
+    let travel_time = travel_length / options.travel_speed.max(1e-9);
+    let estimated_time = layers
+        .iter()
+        .filter(|l| l.output)
+        .map(|l| l.time)
+        .sum::<Option<f64>>()
+        .map(|burn| burn + travel_time);
+
+    ProjectStats {
+        layers,
+        travel_length,
+        travel_time,
+        estimated_time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::{square, translate};
+    use crate::lbrn2::types::{CutSetting, Rect};
+
+    fn project() -> LightBurnProject {
+        LightBurnProject {
+            cut_settings: vec![CutSetting {
+                index: 0,
+                name: "Cut \"outer\"".to_string(),
+                speed: Some(10.0),
+                num_passes: Some(2),
+                ..Default::default()
+            }],
+            shapes: vec![
+                Shape::Rect(Rect {
+                    cut_index: 0,
+                    xform: translate(10.0, 10.0),
+                    w: 20.0,
+                    h: 10.0,
+                    cr: 0.0,
+                }),
+                Shape::Ellipse(Ellipse {
+                    cut_index: 1,
+                    xform: XForm {
+                        a: 2.0,
+                        ..translate(50.0, 0.0)
+                    },
+                    rx: 5.0,
+                    ry: 5.0,
+                }),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layer_measurements() {
+        let stats = project_stats(&project());
+        assert_eq!(stats.layers.len(), 2);
+
+        let rect = &stats.layers[0];
+        assert_eq!((rect.cut_index, rect.shapes, rect.contours), (0, 1, 1));
+        assert_eq!(rect.length, 60.0);
+        assert_eq!(rect.area, 200.0);
+        assert_eq!(rect.bounds, Some(Bounds::new(0.0, 5.0, 20.0, 15.0)));
+        assert_eq!(rect.time, Some(12.0));
+
+        // A 20 x 10 ellipse: Ramanujan's perimeter is good to 1e-5 here
+        let ellipse = &stats.layers[1];
+        let (a, b) = (10.0f64, 5.0f64);
+        let h = ((a - b) / (a + b)).powi(2);
+        let ramanujan =
+            std::f64::consts::PI * (a + b) * (1.0 + 3.0 * h / (10.0 + (4.0 - 3.0 * h).sqrt()));
+        assert!((ellipse.length - ramanujan).abs() < 1e-4);
+        assert!((ellipse.area - std::f64::consts::PI * 50.0).abs() < 1e-9);
+        assert_eq!(ellipse.time, None);
+        assert_eq!(stats.estimated_time, None);
+    }
+
+    #[test]
+    fn test_holes_are_subtracted_from_the_area() {
+        // A washer: a 10 mm square cut out of a 20 mm one, and an island
+        // inside the hole that counts again
+        let project = LightBurnProject {
+            shapes: vec![
+                square(0.0, 0.0, 20.0, 0),
+                square(0.0, 0.0, 10.0, 0),
+                square(0.0, 0.0, 4.0, 0),
+            ],
+            ..Default::default()
+        };
+        let stats = project_stats(&project);
+        assert_eq!(stats.layers[0].area, 400.0 - 100.0 + 16.0);
+    }
+
+    #[test]
+    fn test_travel_follows_burn_order() {
+        let mut project = project();
+        project.shapes.truncate(1);
+        let stats = project_stats_with_options(&project, &StatsOptions { travel_speed: 5.0 });
+
+        // Origin to the rect's first corner at (0, 5), no move for the second
+        // pass, and home at the end
+        assert_eq!(stats.travel_length, 10.0);
+        assert_eq!(stats.travel_time, 2.0);
+        assert_eq!(stats.estimated_time, Some(14.0));
+
+        let json = stats.to_json();
+        assert!(json.contains("\"name\": \"Cut \\\"outer\\\"\""));
+        assert!(
+            json.contains("\"bounds\": {\"min_x\": 0, \"min_y\": 5, \"max_x\": 20, \"max_y\": 15}")
+        );
+        assert!(json.contains("\"estimated_time\": 14\n"));
+        assert!(stats.to_string().contains("Estimated time: 14.0s"));
+    }
+}
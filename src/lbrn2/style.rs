@@ -1,19 +1,44 @@
-use super::types::CutSetting;
+use super::types::{CutSetting, CutType};
+
+/// Layer colors LightBurn assigns to cut indices 0..=29
+const LIGHTBURN_PALETTE: [&str; 30] = [
+    "#000000", "#0000FF", "#FF0000", "#00E000", "#D0D000", "#FF8000", "#00E0E0", "#FF00FF",
+    "#B4B4B4", "#0000A0", "#A00000", "#00A000", "#A0A000", "#C08000", "#00A0FF", "#A000A0",
+    "#808080", "#7D87B9", "#BB7784", "#4A6FE3", "#D33F6A", "#8CD78C", "#F0B98D", "#F6C4E1",
+    "#FA9ED4", "#500A78", "#B45A00", "#004754", "#86FA88", "#FFDB66",
+];
 
 const DEFAULT_COLORS: [&str; 8] = [
     "#000000", "#FF0000", "#00AA00", "#0000FF", "#FF9900", "#9900FF", "#00AAAA", "#AAAA00",
 ];
 
-/// Get the SVG style string for a given cut index
-pub fn get_cut_setting_style(cut_index: i32, cut_settings: Option<&[CutSetting]>) -> String {
-    let cut_settings = match cut_settings {
-        Some(cs) if !cs.is_empty() => cs,
-        _ => return "stroke:#000000;stroke-width:0.050000mm;fill:none".to_string(),
-    };
+/// Get the color LightBurn displays for a cut index, if it is a palette layer
+pub fn lightburn_layer_color(cut_index: i32) -> Option<&'static str> {
+    usize::try_from(cut_index)
+        .ok()
+        .and_then(|i| LIGHTBURN_PALETTE.get(i))
+        .copied()
+}
 
-    let cs = cut_settings.iter().find(|cs| cs.index == cut_index);
+/// Get the cut index LightBurn uses for a palette color (`#RRGGBB`, any case)
+pub fn lightburn_layer_index(color: &str) -> Option<i32> {
+    LIGHTBURN_PALETTE
+        .iter()
+        .position(|c| c.eq_ignore_ascii_case(color))
+        .map(|i| i as i32)
+}
+
+/// Get the display color (`#RRGGBB`) for a given cut index
+///
+/// Uses the cut setting's own color, then a fallback palette for cut
+/// settings without one; unknown layers are black.
+pub fn get_cut_setting_color(cut_index: i32, cut_settings: Option<&[CutSetting]>) -> String {
+    let cs = cut_settings
+        .unwrap_or_default()
+        .iter()
+        .find(|cs| cs.index == cut_index);
 
-    let color = match cs {
+    match cs {
         Some(cs) if cs.color.is_some() => cs.color.as_ref().unwrap().clone(),
         Some(cs) => {
             let palette_idx = if cs.index >= 0 {
@@ -24,8 +49,28 @@ pub fn get_cut_setting_style(cut_index: i32, cut_settings: Option<&[CutSetting]>
             DEFAULT_COLORS[palette_idx].to_string()
         }
         None => "#000000".to_string(),
+    }
+}
+
+/// Get the SVG style string for a given cut index
+///
+/// Scan and Offset Fill layers engrave the enclosed area, so they are drawn
+/// as even-odd fills in the layer color; every other layer is a thin stroke.
+pub fn get_cut_setting_style(cut_index: i32, cut_settings: Option<&[CutSetting]>) -> String {
+    let cut_settings = match cut_settings {
+        Some(cs) if !cs.is_empty() => cs,
+        _ => return "stroke:#000000;stroke-width:0.050000mm;fill:none".to_string(),
     };
 
+    let cs = cut_settings.iter().find(|cs| cs.index == cut_index);
+    let color = get_cut_setting_color(cut_index, Some(cut_settings));
+
+    if let Some(cs) = cs
+        && matches!(cs.cut_type, CutType::Scan | CutType::OffsetFill)
+    {
+        return format!("fill:{};fill-rule:evenodd;stroke:none", color);
+    }
+
     let stroke_width = cs
         .and_then(|cs| cs.stroke_width.as_ref())
         .cloned()
@@ -61,6 +106,7 @@ mod tests {
             name: "cut1".to_string(),
             color: Some("#123456".to_string()),
             stroke_width: Some("0.2mm".to_string()),
+            ..Default::default()
         }];
         assert_eq!(
             get_cut_setting_style(1, Some(&cs)),
@@ -75,6 +121,7 @@ mod tests {
             name: "cut2".to_string(),
             color: Some("#654321".to_string()),
             stroke_width: None,
+            ..Default::default()
         }];
         assert_eq!(
             get_cut_setting_style(2, Some(&cs)),
@@ -89,6 +136,7 @@ mod tests {
             name: "cut3".to_string(),
             color: None,
             stroke_width: None,
+            ..Default::default()
         }];
         // DEFAULT_COLORS[3] = "#0000FF"
         assert_eq!(
@@ -104,6 +152,7 @@ mod tests {
             name: "cut4".to_string(),
             color: None,
             stroke_width: Some("0.3mm".to_string()),
+            ..Default::default()
         }];
         // DEFAULT_COLORS[4] = "#FF9900"
         assert_eq!(
@@ -119,6 +168,7 @@ mod tests {
             name: "cut5".to_string(),
             color: None,
             stroke_width: None,
+            ..Default::default()
         }];
         // Should fallback to DEFAULT_COLORS[0]
         assert_eq!(
@@ -127,6 +177,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lightburn_layer_color() {
+        assert_eq!(lightburn_layer_color(0), Some("#000000"));
+        assert_eq!(lightburn_layer_color(1), Some("#0000FF"));
+        assert_eq!(lightburn_layer_color(29), Some("#FFDB66"));
+        assert_eq!(lightburn_layer_color(30), None);
+        assert_eq!(lightburn_layer_color(-1), None);
+        assert_eq!(lightburn_layer_index("#ff0000"), Some(2));
+        assert_eq!(lightburn_layer_index("#123456"), None);
+    }
+
+    #[test]
+    fn test_fill_layers_are_filled() {
+        let cs = vec![
+            CutSetting {
+                index: 0,
+                color: Some("#000000".to_string()),
+                ..Default::default()
+            },
+            CutSetting {
+                index: 1,
+                cut_type: CutType::Scan,
+                color: Some("#0000FF".to_string()),
+                ..Default::default()
+            },
+            CutSetting {
+                index: 2,
+                cut_type: CutType::OffsetFill,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            get_cut_setting_style(0, Some(&cs)),
+            "stroke:#000000;stroke-width:0.050000mm;fill:none"
+        );
+        assert_eq!(
+            get_cut_setting_style(1, Some(&cs)),
+            "fill:#0000FF;fill-rule:evenodd;stroke:none"
+        );
+        assert_eq!(
+            get_cut_setting_style(2, Some(&cs)),
+            "fill:#00AA00;fill-rule:evenodd;stroke:none"
+        );
+    }
+
     #[test]
     fn test_no_matching_cut_setting() {
         let cs = vec![CutSetting {
@@ -134,6 +229,7 @@ mod tests {
             name: "cut6".to_string(),
             color: Some("#111111".to_string()),
             stroke_width: None,
+            ..Default::default()
         }];
         assert_eq!(
             get_cut_setting_style(99, Some(&cs)),
@@ -1,213 +1,861 @@
-use super::bounds::get_transformed_bounds;
-use super::path::generate_path_data;
-use super::style::get_cut_setting_style;
-use super::types::{CutSetting, LightBurnProject, Shape, XForm};
-
-/// Format a number with 6 decimal places, treating -0 as 0
-fn f(n: f64) -> String {
-    // Handle -0.0 case
-    let n = if n == 0.0 { 0.0 } else { n };
-    format!("{:.6}", n)
-}
-
-/// Format the transformation matrix for SVG (with Y-axis flip)
-fn format_matrix(xform: &XForm) -> String {
-    format!(
-        "matrix({} {} {} {} {} {})",
-        f(xform.a),
-        f(-xform.b),
-        f(xform.c),
-        f(-xform.d),
-        f(xform.e),
-        f(-xform.f)
+use super::bitmap::{
+    decode_bitmap_data, encode_bitmap_data, is_web_image, mime_extension, reencode_as_png,
+    sniff_image_mime,
+};
+use super::bounds::{Bounds, get_transformed_bounds};
+use super::geometry::FLIP_Y;
+use super::path::{NumberFormat, generate_path_data_with_format, transform_path};
+use super::style::{get_cut_setting_color, get_cut_setting_style};
+use super::types::{
+    CutSetting, Ellipse, LightBurnProject, Path, PathPrimitive, Rect, Shape, Text, Vec2, XForm,
+};
+use quick_xml::escape::escape;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Cubic Bezier handle length for approximating a quarter circle
+const KAPPA: f64 = 0.552_284_749_830_793_6;
+
+/// Units used for the document `width`/`height`
+///
+/// Coordinates and the viewBox are always in millimetres; the unit only
+/// changes the physical size the document declares.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SvgUnits {
+    #[default]
+    Millimeters,
+    Inches,
+    /// CSS pixels at the given resolution (96 in browsers)
+    Pixels {
+        dpi: f64,
+    },
+}
+
+impl SvgUnits {
+    fn convert_mm(&self, mm: f64) -> f64 {
+        match self {
+            SvgUnits::Millimeters => mm,
+            SvgUnits::Inches => mm / 25.4,
+            SvgUnits::Pixels { dpi } => mm / 25.4 * dpi,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            SvgUnits::Millimeters => "mm",
+            SvgUnits::Inches => "in",
+            SvgUnits::Pixels { .. } => "",
+        }
+    }
+}
+
+/// Options for SVG export
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgExportOptions {
+    /// Digits after the decimal point (default: 6)
+    pub precision: usize,
+    /// Drop trailing zeros from numbers for more compact output (default: false)
+    pub trim_zeros: bool,
+    /// Units for the document width/height (default: mm)
+    pub units: SvgUnits,
+    /// Space added around the content on every side, in mm (default: 0)
+    pub margin: f64,
+    /// Extend the viewBox to include the bed origin instead of cropping to
+    /// the content (default: false)
+    pub keep_bed_origin: bool,
+    /// Write absolute coordinates instead of `transform="matrix(...)"`;
+    /// rects and ellipses become paths, bitmaps keep their matrix (default: false)
+    pub bake_transforms: bool,
+    /// Flip Y so LightBurn's Y-up workspace displays upright (default: true)
+    pub flip_y: bool,
+    /// One Inkscape layer per cut setting (default: false)
+    pub group_by_layer: bool,
+    /// Side length of the placeholder document written for projects
+    /// without shapes, in mm (default: 100)
+    pub empty_size: f64,
+    /// Re-encode bitmaps browsers cannot display (BMP) as PNG (default: false)
+    pub reencode_bitmaps: bool,
+    /// Reference bitmaps as separate files under this relative directory
+    /// instead of inlining base64 data URIs (default: None)
+    ///
+    /// The files are returned by [`lbrn2_to_svg_with_assets`]; the caller
+    /// writes them next to the SVG.
+    pub bitmap_dir: Option<String>,
+    /// Write the path data of paths sharing a `VertID`/`PrimID` once in
+    /// `<defs>` and draw each instance with `<use>`; ignored when baking
+    /// transforms (default: false)
+    pub reuse_shared_paths: bool,
+}
+
+/// A file referenced from the SVG that has to be written next to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgAsset {
+    /// Path relative to the SVG, as used in the `href`
+    pub path: String,
+    pub mime: &'static str,
+    pub data: Vec<u8>,
+}
+
+impl Default for SvgExportOptions {
+    fn default() -> Self {
+        Self {
+            precision: 6,
+            trim_zeros: false,
+            units: SvgUnits::Millimeters,
+            margin: 0.0,
+            keep_bed_origin: false,
+            bake_transforms: false,
+            flip_y: true,
+            group_by_layer: false,
+            empty_size: 100.0,
+            reencode_bitmaps: false,
+            bitmap_dir: None,
+            reuse_shared_paths: false,
+        }
+    }
+}
+
+/// Outline of a rectangle centered on the origin, with rounded corners
+/// approximated by cubic Beziers
+pub(crate) fn rect_outline(rect: &Rect) -> Path {
+    let (x0, y0) = (-rect.w / 2.0, -rect.h / 2.0);
+    let (x1, y1) = (rect.w / 2.0, rect.h / 2.0);
+    let r = rect.cr.min(rect.w / 2.0).min(rect.h / 2.0).max(0.0);
+
+    let mut verts = Vec::new();
+    let mut prims = Vec::new();
+
+    if r == 0.0 {
+        verts.extend([
+            Vec2::new(x0, y0),
+            Vec2::new(x1, y0),
+            Vec2::new(x1, y1),
+            Vec2::new(x0, y1),
+        ]);
+        for i in 0..4 {
+            prims.push(PathPrimitive::Line {
+                start_idx: i,
+                end_idx: (i + 1) % 4,
+            });
+        }
+    } else {
+        let k = r * (1.0 - KAPPA);
+        // Each side is a straight run followed by a corner arc; control
+        // points are (c0 of the side's end, c1 of the next side's start)
+        let sides = [
+            ((x0 + r, y0), (x1 - r, y0), (x1 - k, y0), (x1, y0 + k)),
+            ((x1, y0 + r), (x1, y1 - r), (x1, y1 - k), (x1 - k, y1)),
+            ((x1 - r, y1), (x0 + r, y1), (x0 + k, y1), (x0, y1 - k)),
+            ((x0, y1 - r), (x0, y0 + r), (x0, y0 + k), (x0 + k, y0)),
+        ];
+        for (start, end, c0, _) in sides {
+            verts.push(Vec2::new(start.0, start.1));
+            verts.push(Vec2::with_control_points(
+                end.0,
+                end.1,
+                Some(c0.0),
+                Some(c0.1),
+                None,
+                None,
+            ));
+        }
+        for (i, (_, _, _, c1)) in sides.iter().enumerate() {
+            let next = (2 * i + 2) % 8;
+            verts[next].c1x = Some(c1.0);
+            verts[next].c1y = Some(c1.1);
+            prims.push(PathPrimitive::Line {
+                start_idx: 2 * i,
+                end_idx: 2 * i + 1,
+            });
+            prims.push(PathPrimitive::Bezier {
+                start_idx: 2 * i + 1,
+                end_idx: next,
+            });
+        }
+    }
+
+    Path::from_parts(rect.cut_index, rect.xform, verts, prims)
+}
+
+/// Outline of an ellipse centered on the origin as four cubic Beziers
+pub(crate) fn ellipse_outline(ellipse: &Ellipse) -> Path {
+    let (rx, ry) = (ellipse.rx, ellipse.ry);
+    let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+    let c = |x, y, c0: (f64, f64), c1: (f64, f64)| {
+        Vec2::with_control_points(x, y, Some(c0.0), Some(c0.1), Some(c1.0), Some(c1.1))
+    };
+
+    Path::from_parts(
+        ellipse.cut_index,
+        ellipse.xform,
+        vec![
+            c(rx, 0.0, (rx, ky), (rx, -ky)),
+            c(0.0, ry, (-kx, ry), (kx, ry)),
+            c(-rx, 0.0, (-rx, -ky), (-rx, ky)),
+            c(0.0, -ry, (kx, -ry), (-kx, -ry)),
+        ],
+        (0..4)
+            .map(|i| PathPrimitive::Bezier {
+                start_idx: i,
+                end_idx: (i + 1) % 4,
+            })
+            .collect(),
+    )
+}
+
+/// Flatten groups into their leaf shapes, composing each group's transform
+/// into its children and keeping draw order
+pub(crate) fn flatten_groups(shapes: &[Shape], parent: Option<&XForm>, out: &mut Vec<Shape>) {
+    for shape in shapes {
+        let mut shape = shape.clone();
+        if let Some(parent) = parent {
+            *shape.xform_mut() = parent.compose(shape.xform());
+        }
+
+        match &shape {
+            Shape::Group(group) => flatten_groups(&group.children, Some(&group.xform), out),
+            _ => out.push(shape),
+        }
+    }
+}
+
+/// Identity of the vertex and primitive lists a path draws
+type GeometryKey = (*const Vec<Vec2>, *const Vec<PathPrimitive>, bool);
+
+fn geometry_key(path: &Path) -> GeometryKey {
+    (
+        Arc::as_ptr(&path.parsed_verts),
+        Arc::as_ptr(&path.parsed_primitives),
+        path.is_line_closed(),
     )
 }
 
-/// Convert a shape to an SVG element string
-fn shape_to_svg_element(
-    shape: &Shape,
-    cut_settings: Option<&[CutSetting]>,
-    log: &mut Vec<String>,
-) -> String {
-    let transform = format_matrix(shape.xform());
-    let style = get_cut_setting_style(shape.cut_index(), cut_settings);
-
-    match shape {
-        Shape::Rect(rect) => {
-            let x = -rect.w / 2.0;
-            let y = -rect.h / 2.0;
-
-            // Match TS attribute order: x, y, width, height, [rx, ry], style, transform
-            let mut el = format!(
-                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
-                f(x),
-                f(y),
-                f(rect.w),
-                f(rect.h)
-            );
-
-            if rect.cr > 0.0 {
-                el.push_str(&format!(" rx=\"{}\" ry=\"{}\"", f(rect.cr), f(rect.cr)));
+/// Count how many paths, including text outlines, draw each geometry
+fn count_geometry(shapes: &[Shape], counts: &mut HashMap<GeometryKey, usize>) {
+    for shape in shapes {
+        match shape {
+            Shape::Path(path)
+            | Shape::Text(Text {
+                backup_path: Some(path),
+                ..
+            }) => *counts.entry(geometry_key(path)).or_default() += 1,
+            Shape::Group(group) => count_geometry(&group.children, counts),
+            _ => {}
+        }
+    }
+}
+
+struct SvgWriter<'a> {
+    options: &'a SvgExportOptions,
+    fmt: NumberFormat,
+    cut_settings: Option<&'a [CutSetting]>,
+    log: Vec<String>,
+    assets: Vec<SvgAsset>,
+    /// Number of paths drawing each geometry, when reusing shared paths
+    geometry_uses: HashMap<GeometryKey, usize>,
+    /// `<defs>` id of each shared geometry written so far
+    geometry_ids: HashMap<GeometryKey, String>,
+    defs: Vec<String>,
+}
+
+impl SvgWriter<'_> {
+    /// Format a number with the configured precision, treating -0 as 0
+    fn f(&self, n: f64) -> String {
+        // Handle -0.0 case
+        let n = if n == 0.0 { 0.0 } else { n };
+        self.fmt.format(n)
+    }
+
+    /// Transform from LightBurn coordinates to SVG user space
+    fn output_xform(&self, xform: &XForm) -> XForm {
+        if self.options.flip_y {
+            FLIP_Y.compose(xform)
+        } else {
+            *xform
+        }
+    }
+
+    fn format_xform(&self, m: &XForm) -> String {
+        format!(
+            "matrix({} {} {} {} {} {})",
+            self.f(m.a),
+            self.f(m.b),
+            self.f(m.c),
+            self.f(m.d),
+            self.f(m.e),
+            self.f(m.f)
+        )
+    }
+
+    /// Format the transformation matrix for SVG (with Y-axis flip)
+    fn format_matrix(&self, xform: &XForm) -> String {
+        self.format_xform(&self.output_xform(xform))
+    }
+
+    /// Format a group transformation matrix for SVG
+    ///
+    /// Children already carry the Y flip in their own matrices, so the group is
+    /// written conjugated by the flip (F * G * F) instead of flipping a second time.
+    fn format_group_matrix(&self, xform: &XForm) -> String {
+        if self.options.flip_y {
+            self.format_xform(&FLIP_Y.compose(xform).compose(&FLIP_Y))
+        } else {
+            self.format_xform(xform)
+        }
+    }
+
+    /// Build the `href` for a bitmap's base64 `Data`
+    ///
+    /// The MIME type comes from the decoded magic bytes, falling back to PNG
+    /// when the data cannot be decoded or recognized.
+    fn bitmap_href(&mut self, data: &str) -> String {
+        let bytes = match decode_bitmap_data(data) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.log.push(e);
+                return format!("data:image/png;base64,{}", data);
+            }
+        };
+        let mut mime = sniff_image_mime(&bytes).unwrap_or("image/png");
+        let mut reencoded = None;
+
+        if self.options.reencode_bitmaps && !is_web_image(mime) {
+            match reencode_as_png(&bytes) {
+                Ok(png) => {
+                    mime = "image/png";
+                    reencoded = Some(png);
+                }
+                Err(e) => self.log.push(e),
             }
+        }
+
+        if let Some(dir) = &self.options.bitmap_dir {
+            let dir = dir.trim_end_matches('/');
+            let name = format!("bitmap-{}.{}", self.assets.len() + 1, mime_extension(mime));
+            let path = if dir.is_empty() {
+                name
+            } else {
+                format!("{}/{}", dir, name)
+            };
+            self.assets.push(SvgAsset {
+                path: path.clone(),
+                mime,
+                data: reencoded.unwrap_or(bytes),
+            });
+            return escape(path.as_str()).into_owned();
+        }
+
+        match reencoded {
+            Some(png) => format!("data:{};base64,{}", mime, encode_bitmap_data(&png)),
+            None => format!("data:{};base64,{}", mime, data),
+        }
+    }
+
+    /// `href` of the `<defs>` entry for a path drawn more than once, writing
+    /// the entry the first time; `None` when the path is drawn once or has
+    /// no valid primitives
+    fn shared_path_href(&mut self, path: &Path) -> Option<String> {
+        let key = geometry_key(path);
+        if self.geometry_uses.get(&key).is_none_or(|&uses| uses < 2) {
+            return None;
+        }
+        if let Some(id) = self.geometry_ids.get(&key) {
+            return Some(format!("#{}", id));
+        }
+
+        let mut log = Vec::new();
+        let d = generate_path_data_with_format(path, &self.fmt, &mut log);
+        if d.is_empty() {
+            return None;
+        }
+        self.log.append(&mut log);
+
+        let id = format!("path-{}", self.defs.len() + 1);
+        self.defs.push(format!("<path id=\"{}\" d=\"{}\"/>", id, d));
+        self.geometry_ids.insert(key, id.clone());
+        Some(format!("#{}", id))
+    }
 
-            el.push_str(&format!(
-                " style=\"{}\" transform=\"{}\"/>",
-                style, transform
+    /// Write a rect, ellipse or path as a `<path>` in absolute coordinates
+    fn baked_path_element(&mut self, shape: &Shape, style: &str) -> String {
+        let outline = match shape {
+            Shape::Rect(rect) => rect_outline(rect),
+            Shape::Ellipse(ellipse) => ellipse_outline(ellipse),
+            Shape::Path(path) => path.clone(),
+            _ => return String::new(),
+        };
+        let outline = transform_path(&outline, &self.output_xform(shape.xform()));
+
+        let d = generate_path_data_with_format(&outline, &self.fmt, &mut self.log);
+        if d.is_empty() {
+            self.log
+                .push("Path shape with no valid primitives".to_string());
+            return String::new();
+        }
+
+        format!("<path d=\"{}\" style=\"{}\"/>", d, style)
+    }
+
+    /// Write a text without glyph outlines as a `<text>` element
+    ///
+    /// The glyphs are drawn Y-down by the renderer, so the matrix carries an
+    /// extra flip to keep them upright, and the text is always filled in the
+    /// layer color. Transforms are never baked into text.
+    fn text_element(&mut self, text: &Text) -> String {
+        if text.text.is_empty() {
+            self.log.push("Text shape with no string".to_string());
+            return String::new();
+        }
+        self.log.push(format!(
+            "Text \"{}\" has no BackupPath; written as <text>, rendering depends on installed fonts",
+            text.text
+        ));
+
+        let matrix = self.format_xform(&self.output_xform(&text.xform).compose(&FLIP_Y));
+        let anchor = match text.align_h {
+            1 => "middle",
+            2 => "end",
+            _ => "start",
+        };
+        let baseline = match text.align_v {
+            1 => "middle",
+            2 => "hanging",
+            3 => "text-after-edge",
+            _ => "alphabetic",
+        };
+        let color = get_cut_setting_color(text.cut_index, self.cut_settings);
+
+        let mut attrs = format!(
+            "font-family=\"{}\" font-size=\"{}\"",
+            escape(text.font_family()),
+            self.f(text.h)
+        );
+        if text.is_bold() {
+            attrs.push_str(" font-weight=\"bold\"");
+        }
+        if text.is_italic() {
+            attrs.push_str(" font-style=\"italic\"");
+        }
+        if text.letter_spacing != 0.0 {
+            attrs.push_str(&format!(
+                " letter-spacing=\"{}\"",
+                self.f(text.letter_spacing)
             ));
-            el
         }
-        Shape::Ellipse(ellipse) => {
-            if (ellipse.rx - ellipse.ry).abs() < 1e-10 {
-                // Circle - match TS attribute order: cx, cy, r, style, transform
+
+        // One <tspan> per line, each starting back at x=0
+        let lines: Vec<&str> = text.text.lines().collect();
+        let content = if lines.len() == 1 {
+            escape(lines[0]).into_owned()
+        } else {
+            let dy = text.h + text.line_spacing;
+            lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    let dy = if i == 0 { 0.0 } else { dy };
+                    format!(
+                        "<tspan x=\"0\" dy=\"{}\">{}</tspan>",
+                        self.f(dy),
+                        escape(*line)
+                    )
+                })
+                .collect()
+        };
+
+        format!(
+            "<text x=\"0\" y=\"0\" {} text-anchor=\"{}\" dominant-baseline=\"{}\" style=\"fill:{};stroke:none\" transform=\"{}\">{}</text>",
+            attrs, anchor, baseline, color, matrix, content
+        )
+    }
+
+    /// Convert a shape to an SVG element string
+    fn shape_to_svg_element(&mut self, shape: &Shape) -> String {
+        let transform = self.format_matrix(shape.xform());
+        let style = get_cut_setting_style(shape.cut_index(), self.cut_settings);
+
+        match shape {
+            Shape::Rect(_) | Shape::Ellipse(_) if self.options.bake_transforms => {
+                self.baked_path_element(shape, &style)
+            }
+            Shape::Rect(rect) => {
+                let x = -rect.w / 2.0;
+                let y = -rect.h / 2.0;
+
+                // Match TS attribute order: x, y, width, height, [rx, ry], style, transform
+                let mut el = format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+                    self.f(x),
+                    self.f(y),
+                    self.f(rect.w),
+                    self.f(rect.h)
+                );
+
+                if rect.cr > 0.0 {
+                    el.push_str(&format!(
+                        " rx=\"{}\" ry=\"{}\"",
+                        self.f(rect.cr),
+                        self.f(rect.cr)
+                    ));
+                }
+
+                el.push_str(&format!(
+                    " style=\"{}\" transform=\"{}\"/>",
+                    style, transform
+                ));
+                el
+            }
+            Shape::Ellipse(ellipse) => {
+                if (ellipse.rx - ellipse.ry).abs() < 1e-10 {
+                    // Circle - match TS attribute order: cx, cy, r, style, transform
+                    format!(
+                        "<circle cx=\"0\" cy=\"0\" r=\"{}\" style=\"{}\" transform=\"{}\"/>",
+                        self.f(ellipse.rx),
+                        style,
+                        transform
+                    )
+                } else {
+                    // Ellipse - match TS attribute order: cx, cy, rx, ry, style, transform
+                    format!(
+                        "<ellipse cx=\"0\" cy=\"0\" rx=\"{}\" ry=\"{}\" style=\"{}\" transform=\"{}\"/>",
+                        self.f(ellipse.rx),
+                        self.f(ellipse.ry),
+                        style,
+                        transform
+                    )
+                }
+            }
+            Shape::Path(path) => {
+                if path.parsed_verts.is_empty() {
+                    self.log.push("Path shape with no vertices".to_string());
+                    return String::new();
+                }
+
+                if self.options.bake_transforms {
+                    return self.baked_path_element(shape, &style);
+                }
+
+                if let Some(href) = self.shared_path_href(path) {
+                    return format!(
+                        "<use xlink:href=\"{}\" style=\"{}\" transform=\"{}\"/>",
+                        href, style, transform
+                    );
+                }
+
+                let d = generate_path_data_with_format(path, &self.fmt, &mut self.log);
+                if d.is_empty() {
+                    self.log
+                        .push("Path shape with no valid primitives".to_string());
+                    return String::new();
+                }
+
+                // Match TS attribute order: d, style, transform
                 format!(
-                    "<circle cx=\"0\" cy=\"0\" r=\"{}\" style=\"{}\" transform=\"{}\"/>",
-                    f(ellipse.rx),
-                    style,
-                    transform
+                    "<path d=\"{}\" style=\"{}\" transform=\"{}\"/>",
+                    d, style, transform
                 )
-            } else {
-                // Ellipse - match TS attribute order: cx, cy, rx, ry, style, transform
+            }
+            Shape::Bitmap(bitmap) => {
+                if bitmap.data.is_empty() {
+                    self.log.push("Bitmap shape missing Data".to_string());
+                    return String::new();
+                }
+
+                // Raster data cannot be baked, so bitmaps always keep their matrix
+                let href = self.bitmap_href(&bitmap.data);
+                // Match TS attribute order: x, y, width, height, xlink:href, transform
                 format!(
-                    "<ellipse cx=\"0\" cy=\"0\" rx=\"{}\" ry=\"{}\" style=\"{}\" transform=\"{}\"/>",
-                    f(ellipse.rx),
-                    f(ellipse.ry),
-                    style,
+                    "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" xlink:href=\"{}\" transform=\"{}\"/>",
+                    self.f(-bitmap.w / 2.0),
+                    self.f(-bitmap.h / 2.0),
+                    self.f(bitmap.w),
+                    self.f(bitmap.h),
+                    href,
                     transform
                 )
             }
-        }
-        Shape::Path(path) => {
-            if path.parsed_verts.is_empty() {
-                log.push("Path shape with no vertices".to_string());
-                return String::new();
-            }
+            Shape::Text(text) => match text.outline() {
+                Some(outline) => self.shape_to_svg_element(&Shape::Path(outline)),
+                None => self.text_element(text),
+            },
+            Shape::Group(group) => {
+                if group.children.is_empty() {
+                    self.log.push("Group shape with no children".to_string());
+                    return String::new();
+                }
 
-            let d = generate_path_data(path, log);
-            if d.is_empty() {
-                log.push("Path shape with no valid primitives".to_string());
-                return String::new();
-            }
+                // If only one child, flatten transform into the child
+                if group.children.len() == 1 {
+                    let mut child = group.children[0].clone();
+                    let child_xform = child.xform();
 
-            // Match TS attribute order: d, style, transform
-            format!(
-                "<path d=\"{}\" style=\"{}\" transform=\"{}\"/>",
-                d, style, transform
-            )
-        }
-        Shape::Bitmap(bitmap) => {
-            if bitmap.data.is_empty() {
-                log.push("Bitmap shape missing Data".to_string());
-                return String::new();
-            }
+                    // Compose transforms: group.XForm * child.XForm
+                    let composed = group.xform.compose(child_xform);
+                    *child.xform_mut() = composed;
+
+                    return self.shape_to_svg_element(&child);
+                }
+
+                if self.options.bake_transforms {
+                    // Push the group transform down so children are absolute
+                    let group_content: Vec<String> = group
+                        .children
+                        .iter()
+                        .map(|child| {
+                            let mut child = child.clone();
+                            *child.xform_mut() = group.xform.compose(child.xform());
+                            self.shape_to_svg_element(&child)
+                        })
+                        .filter(|s| !s.is_empty())
+                        .collect();
+
+                    return format!("<g>\n    {}\n</g>", group_content.join("\n    "));
+                }
 
-            let href = format!("data:image/png;base64,{}", bitmap.data);
-            // Match TS attribute order: x, y, width, height, xlink:href, transform
-            format!(
-                "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" xlink:href=\"{}\" transform=\"{}\"/>",
-                f(-bitmap.w / 2.0),
-                f(-bitmap.h / 2.0),
-                f(bitmap.w),
-                f(bitmap.h),
-                href,
-                transform
-            )
-        }
-        Shape::Group(group) => {
-            if group.children.is_empty() {
-                log.push("Group shape with no children".to_string());
-                return String::new();
+                // Otherwise, wrap in <g>
+                let group_content: Vec<String> = group
+                    .children
+                    .iter()
+                    .map(|child| self.shape_to_svg_element(child))
+                    .filter(|s| !s.is_empty())
+                    .collect();
+
+                format!(
+                    "<g transform=\"{}\">\n    {}\n</g>",
+                    self.format_group_matrix(&group.xform),
+                    group_content.join("\n    ")
+                )
             }
+        }
+    }
 
-            // If only one child, flatten transform into the child
-            if group.children.len() == 1 {
-                let mut child = group.children[0].clone();
-                let child_xform = child.xform();
+    /// Wrap each cut setting's shapes in an Inkscape layer group
+    ///
+    /// Layers follow the order of the project's cut settings; shapes on a cut
+    /// index without a CutSetting get trailing layers of their own, by index.
+    fn layer_groups(&mut self, project: &LightBurnProject) -> Vec<String> {
+        let mut leaves = Vec::new();
+        flatten_groups(&project.shapes, None, &mut leaves);
 
-                // Compose transforms: group.XForm * child.XForm
-                let composed = group.xform.compose(child_xform);
-                *child.xform_mut() = composed;
+        let mut layers: Vec<(i32, String)> = project
+            .cut_settings
+            .iter()
+            .map(|cs| (cs.index, cs.name.clone()))
+            .collect();
+        let mut orphans: Vec<i32> = leaves
+            .iter()
+            .map(|shape| shape.cut_index())
+            .filter(|index| !layers.iter().any(|(i, _)| i == index))
+            .collect();
+        orphans.sort_unstable();
+        orphans.dedup();
+        layers.extend(orphans.into_iter().map(|index| (index, String::new())));
 
-                return shape_to_svg_element(&child, cut_settings, log);
+        layers
+            .into_iter()
+            .map(|(index, name)| {
+                let label = if name.is_empty() {
+                    format!("C{:02}", index)
+                } else {
+                    name
+                };
+                let elements: Vec<String> = leaves
+                    .iter()
+                    .filter(|shape| shape.cut_index() == index)
+                    .map(|shape| self.shape_to_svg_element(shape))
+                    .filter(|s| !s.is_empty())
+                    .collect();
+
+                let mut group = format!(
+                    "<g id=\"layer-{}\" inkscape:groupmode=\"layer\" inkscape:label=\"{}\">",
+                    index,
+                    escape(label.as_str())
+                );
+                for el in elements {
+                    group.push_str("\n        ");
+                    group.push_str(&el);
+                }
+                group.push_str("\n    </g>");
+                group
+            })
+            .collect()
+    }
+
+    /// Bounds of all shapes in SVG user space, adjusted by the options
+    fn view_bounds(&self, project: &LightBurnProject) -> Bounds {
+        let mut min_x = f64::INFINITY;
+        let mut min_y = f64::INFINITY;
+        let mut max_x = f64::NEG_INFINITY;
+        let mut max_y = f64::NEG_INFINITY;
+
+        for shape in &project.shapes {
+            if let Some(bounds) = get_transformed_bounds(shape) {
+                min_x = min_x.min(bounds.min_x);
+                min_y = min_y.min(bounds.min_y);
+                max_x = max_x.max(bounds.max_x);
+                max_y = max_y.max(bounds.max_y);
             }
+        }
 
-            // Otherwise, wrap in <g>
-            let group_content: Vec<String> = group
-                .children
-                .iter()
-                .map(|child| shape_to_svg_element(child, cut_settings, log))
-                .filter(|s| !s.is_empty())
-                .collect();
+        if !min_x.is_finite() || !min_y.is_finite() || !max_x.is_finite() || !max_y.is_finite() {
+            min_x = 0.0;
+            min_y = -100.0;
+            max_x = 100.0;
+            max_y = 0.0;
+        }
 
-            format!(
-                "<g transform=\"{}\">\n    {}\n</g>",
-                transform,
-                group_content.join("\n    ")
-            )
+        // get_transformed_bounds works in the flipped space
+        if !self.options.flip_y {
+            (min_y, max_y) = (-max_y, -min_y);
         }
+
+        if self.options.keep_bed_origin {
+            min_x = min_x.min(0.0);
+            min_y = min_y.min(0.0);
+            max_x = max_x.max(0.0);
+            max_y = max_y.max(0.0);
+        }
+
+        let m = self.options.margin;
+        Bounds::new(min_x - m, min_y - m, max_x + m, max_y + m)
+    }
+
+    /// Format a length in mm as a width/height attribute in the output unit
+    fn length(&self, mm: f64) -> String {
+        let units = self.options.units;
+        format!("{}{}", self.f(units.convert_mm(mm)), units.suffix())
     }
 }
 
 /// Convert a LightBurnProject to SVG string
 pub fn lbrn2_to_svg(project: &LightBurnProject) -> String {
-    if project.shapes.is_empty() {
-        return r#"<svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="100mm" viewBox="0 0 100 100"><text>No shapes found</text></svg>"#.to_string();
-    }
+    lbrn2_to_svg_with_options(project, &SvgExportOptions::default())
+}
 
-    let cut_settings = if project.cut_settings.is_empty() {
-        None
-    } else {
-        Some(project.cut_settings.as_slice())
+/// Convert a LightBurnProject to SVG string with one Inkscape layer (`<g>`)
+/// per cut setting
+///
+/// Groups are flattened so every shape lands in the layer of its own cut
+/// index; draw order is kept within each layer.
+pub fn lbrn2_to_svg_layered(project: &LightBurnProject) -> String {
+    let options = SvgExportOptions {
+        group_by_layer: true,
+        ..Default::default()
     };
+    lbrn2_to_svg_with_options(project, &options)
+}
 
-    let mut log: Vec<String> = Vec::new();
-
-    let svg_elements: Vec<String> = project
-        .shapes
-        .iter()
-        .map(|s| shape_to_svg_element(s, cut_settings, &mut log))
-        .filter(|s| !s.is_empty())
-        .collect();
+/// Convert a LightBurnProject to SVG string using the given options
+///
+/// When `bitmap_dir` is set the referenced files are not written; use
+/// [`lbrn2_to_svg_with_assets`] to get them.
+pub fn lbrn2_to_svg_with_options(project: &LightBurnProject, options: &SvgExportOptions) -> String {
+    lbrn2_to_svg_with_assets(project, options).0
+}
 
-    // Compute viewBox to encompass all shapes
-    let mut min_x = f64::INFINITY;
-    let mut min_y = f64::INFINITY;
-    let mut max_x = f64::NEG_INFINITY;
-    let mut max_y = f64::NEG_INFINITY;
+/// Convert a LightBurnProject to SVG string, also returning the bitmap
+/// files the SVG references when `bitmap_dir` is set
+///
+/// Projects with `MirrorX`/`MirrorY` set are drawn mirrored, the way the
+/// job will come out of the laser.
+pub fn lbrn2_to_svg_with_assets(
+    project: &LightBurnProject,
+    options: &SvgExportOptions,
+) -> (String, Vec<SvgAsset>) {
+    let mirrored = project.with_mirror_applied();
+    let project = mirrored.as_ref();
+    let mut writer = SvgWriter {
+        options,
+        fmt: NumberFormat {
+            precision: options.precision,
+            trim_zeros: options.trim_zeros,
+        },
+        cut_settings: if project.cut_settings.is_empty() {
+            None
+        } else {
+            Some(project.cut_settings.as_slice())
+        },
+        log: Vec::new(),
+        assets: Vec::new(),
+        geometry_uses: HashMap::new(),
+        geometry_ids: HashMap::new(),
+        defs: Vec::new(),
+    };
+    if options.reuse_shared_paths && !options.bake_transforms {
+        count_geometry(&project.shapes, &mut writer.geometry_uses);
+    }
 
-    for shape in &project.shapes {
-        if let Some(bounds) = get_transformed_bounds(shape) {
-            min_x = min_x.min(bounds.min_x);
-            min_y = min_y.min(bounds.min_y);
-            max_x = max_x.max(bounds.max_x);
-            max_y = max_y.max(bounds.max_y);
-        }
+    if project.shapes.is_empty() {
+        let compact = NumberFormat {
+            precision: options.precision,
+            trim_zeros: true,
+        };
+        let size = compact.format(options.empty_size);
+        let length = format!(
+            "{}{}",
+            compact.format(options.units.convert_mm(options.empty_size)),
+            options.units.suffix()
+        );
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}"><text>No shapes found</text></svg>"#,
+            length, length, size, size
+        );
+        return (svg, Vec::new());
     }
 
-    if !min_x.is_finite() || !min_y.is_finite() || !max_x.is_finite() || !max_y.is_finite() {
-        min_x = 0.0;
-        min_y = -100.0;
-        max_x = 100.0;
-        max_y = 0.0;
+    let mut svg_elements: Vec<String> = if options.group_by_layer {
+        writer.layer_groups(project)
+    } else {
+        project
+            .shapes
+            .iter()
+            .map(|s| writer.shape_to_svg_element(s))
+            .filter(|s| !s.is_empty())
+            .collect()
+    };
+
+    if !writer.defs.is_empty() {
+        let defs = format!(
+            "<defs>\n        {}\n    </defs>",
+            writer.defs.join("\n        ")
+        );
+        svg_elements.insert(0, defs);
     }
 
-    let w = max_x - min_x;
-    let h = max_y - min_y;
-    let svg_width = format!("{}mm", f(w));
-    let svg_height = format!("{}mm", f(h));
-    let view_box = format!("{} {} {} {}", f(min_x), f(min_y), f(w), f(h));
+    // Compute viewBox to encompass all shapes
+    let bounds = writer.view_bounds(project);
+    let w = bounds.width();
+    let h = bounds.height();
+    let svg_width = writer.length(w);
+    let svg_height = writer.length(h);
+    let view_box = format!(
+        "{} {} {} {}",
+        writer.f(bounds.min_x),
+        writer.f(bounds.min_y),
+        writer.f(w),
+        writer.f(h)
+    );
 
-    if !log.is_empty() {
-        eprintln!("SVG Conversion Warnings: {:?}", log);
+    if !writer.log.is_empty() {
+        eprintln!("SVG Conversion Warnings: {:?}", writer.log);
     }
 
-    format!(
+    let namespaces = if options.group_by_layer {
+        r#" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape""#
+    } else {
+        ""
+    };
+
+    let svg = format!(
         r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
-<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="{}" viewBox="{}">
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"{} width="{}" height="{}" viewBox="{}">
     {}
 </svg>"#,
+        namespaces,
         svg_width,
         svg_height,
         view_box,
         svg_elements.join("\n    ")
-    )
+    );
+    (svg, writer.assets)
 }
@@ -0,0 +1,654 @@
+//! SVG to LBRN2 conversion
+//!
+//! Imports SVG artwork with usvg, inverting the mapping the SVG export uses.
+
+use super::bitmap::encode_bitmap_data;
+use super::style::{lightburn_layer_color, lightburn_layer_index};
+use super::types::*;
+use super::writer::{format_prim_list, format_vert_list};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use usvg::tiny_skia_path::PathSegment;
+
+/// Number of layers LightBurn offers in its palette
+const LAYER_COUNT: i32 = 30;
+
+/// Options for SVG import
+#[derive(Debug, Clone)]
+pub struct SvgImportOptions {
+    /// Resolution used to convert px and unitless lengths (default: 96)
+    pub dpi: f32,
+    /// Explicit color to cut index assignments, keyed by `#RRGGBB`
+    pub color_layers: BTreeMap<String, i32>,
+    /// Cut index for shapes without a solid stroke or fill color (default: 0)
+    pub default_cut_index: i32,
+}
+
+impl Default for SvgImportOptions {
+    fn default() -> Self {
+        Self {
+            dpi: 96.0,
+            color_layers: BTreeMap::new(),
+            default_cut_index: 0,
+        }
+    }
+}
+
+impl SvgImportOptions {
+    /// Assign every shape drawn in `color` to `cut_index`
+    ///
+    /// Colors that are not `#RGB` or `#RRGGBB` are ignored.
+    pub fn with_layer(mut self, color: &str, cut_index: i32) -> Self {
+        if let Some(color) = normalize_color(color) {
+            self.color_layers.insert(color, cut_index);
+        }
+        self
+    }
+}
+
+/// Normalize a hex color to uppercase `#RRGGBB`, accepting `#RGB` shorthand
+/// and a missing `#`
+pub fn normalize_color(color: &str) -> Option<String> {
+    let hex = color.trim().trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        6 => Some(format!("#{}", hex.to_ascii_uppercase())),
+        3 => Some(format!(
+            "#{}",
+            hex.chars()
+                .flat_map(|c| [c, c])
+                .collect::<String>()
+                .to_ascii_uppercase()
+        )),
+        _ => None,
+    }
+}
+
+/// Round to 6 decimals (the precision of the SVG writer), treating -0 as 0
+pub(crate) fn round(n: f64) -> f64 {
+    let n = (n * 1e6).round() / 1e6;
+    if n == 0.0 { 0.0 } else { n }
+}
+
+fn to_xform(ts: usvg::Transform) -> XForm {
+    XForm {
+        a: ts.sx as f64,
+        b: ts.ky as f64,
+        c: ts.kx as f64,
+        d: ts.sy as f64,
+        e: ts.tx as f64,
+        f: ts.ty as f64,
+    }
+}
+
+fn solid_color(paint: &usvg::Paint) -> Option<String> {
+    match paint {
+        usvg::Paint::Color(c) => Some(format!("#{:02X}{:02X}{:02X}", c.red, c.green, c.blue)),
+        _ => None,
+    }
+}
+
+/// Parse a `viewBox` attribute into (x, y, width, height)
+fn parse_view_box(value: &str) -> Option<(f64, f64, f64, f64)> {
+    let nums: Vec<f64> = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().ok())
+        .collect::<Option<_>>()?;
+    match nums.as_slice() {
+        &[x, y, w, h] if w > 0.0 && h > 0.0 => Some((x, y, w, h)),
+        _ => None,
+    }
+}
+
+/// Translation usvg applied to map the root `viewBox` onto the canvas
+///
+/// usvg folds the viewBox into a canvas transform and discards its origin.
+/// Undoing the translation keeps user-space coordinates, so shapes land back
+/// where `lbrn2_to_svg` placed them instead of at the viewBox corner.
+fn view_box_translation(root: usvg::roxmltree::Node, size: usvg::Size) -> (f64, f64) {
+    let Some((vx, vy, vw, vh)) = root.attribute("viewBox").and_then(parse_view_box) else {
+        return (0.0, 0.0);
+    };
+
+    let mut aspect = root
+        .attribute("preserveAspectRatio")
+        .unwrap_or("xMidYMid")
+        .split_whitespace()
+        .filter(|t| *t != "defer");
+    let align = aspect.next().unwrap_or("xMidYMid");
+    let slice = aspect.any(|t| t == "slice");
+
+    let sx = size.width() as f64 / vw;
+    let sy = size.height() as f64 / vh;
+    let (sx, sy) = if align == "none" {
+        (sx, sy)
+    } else {
+        let s = if slice { sx.max(sy) } else { sx.min(sy) };
+        (s, s)
+    };
+
+    let x = -vx * sx;
+    let y = -vy * sy;
+    let free_w = size.width() as f64 - vw * sx;
+    let free_h = size.height() as f64 - vh * sy;
+
+    let tx = if align.starts_with("xMid") {
+        x + free_w / 2.0
+    } else if align.starts_with("xMax") {
+        x + free_w
+    } else {
+        x
+    };
+    let ty = if align.contains("YMid") {
+        y + free_h / 2.0
+    } else if align.contains("YMax") {
+        y + free_h
+    } else {
+        y
+    };
+    (tx, ty)
+}
+
+/// Hands out cut indices to colors and remembers which layers are in use
+struct LayerTable<'a> {
+    options: &'a SvgImportOptions,
+    by_color: BTreeMap<String, i32>,
+    used: BTreeMap<i32, CutType>,
+    image_index: Option<i32>,
+}
+
+impl<'a> LayerTable<'a> {
+    fn new(options: &'a SvgImportOptions) -> Self {
+        Self {
+            options,
+            by_color: BTreeMap::new(),
+            used: BTreeMap::new(),
+            image_index: None,
+        }
+    }
+
+    /// Lowest palette index not claimed by the options or an earlier color
+    fn free_index(&self) -> Option<i32> {
+        (0..LAYER_COUNT).find(|i| {
+            !self.used.contains_key(i)
+                && *i != self.options.default_cut_index
+                && !self.options.color_layers.values().any(|v| v == i)
+        })
+    }
+
+    fn index_for_color(&mut self, color: Option<String>) -> i32 {
+        let index = match color {
+            None => self.options.default_cut_index,
+            Some(color) => {
+                if let Some(&index) = self.options.color_layers.get(&color) {
+                    index
+                } else if let Some(&index) = self.by_color.get(&color) {
+                    index
+                } else {
+                    // Prefer the layer LightBurn itself shows in this color,
+                    // unless another color already claimed it
+                    let index = lightburn_layer_index(&color)
+                        .filter(|i| {
+                            !self.by_color.values().any(|v| v == i)
+                                && !self.options.color_layers.values().any(|v| v == i)
+                        })
+                        .or_else(|| self.free_index())
+                        .unwrap_or(self.options.default_cut_index);
+                    self.by_color.insert(color, index);
+                    index
+                }
+            }
+        };
+        self.used.entry(index).or_default();
+        index
+    }
+
+    fn index_for_image(&mut self) -> i32 {
+        if let Some(index) = self.image_index {
+            return index;
+        }
+        let index = self.free_index().unwrap_or(self.options.default_cut_index);
+        self.used.insert(index, CutType::Image);
+        self.image_index = Some(index);
+        index
+    }
+
+    fn into_cut_settings(self) -> Vec<CutSetting> {
+        self.used
+            .into_iter()
+            .map(|(index, cut_type)| {
+                let mut cs = CutSetting::new(index);
+                cs.name = format!("C{:02}", index);
+                cs.color = lightburn_layer_color(index).map(str::to_string);
+                cs.cut_type = cut_type;
+                cs
+            })
+            .collect()
+    }
+}
+
+/// Append a cubic from vertex `from`, returning the index of the new vertex
+pub(crate) fn push_cubic(
+    verts: &mut Vec<Vec2>,
+    prims: &mut Vec<PathPrimitive>,
+    from: usize,
+    c0: (f64, f64),
+    c1: (f64, f64),
+    to: (f64, f64),
+) -> usize {
+    verts[from].c0x = Some(round(c0.0));
+    verts[from].c0y = Some(round(c0.1));
+    verts.push(Vec2::with_control_points(
+        round(to.0),
+        round(to.1),
+        None,
+        None,
+        Some(round(c1.0)),
+        Some(round(c1.1)),
+    ));
+    prims.push(PathPrimitive::Bezier {
+        start_idx: from,
+        end_idx: verts.len() - 1,
+    });
+    verts.len() - 1
+}
+
+/// Convert path segments into LightBurn vertices and primitives
+///
+/// Quadratic segments are elevated to cubics. Closing a subpath either
+/// merges a trailing vertex that sits on the start point or adds a line back
+/// to it, matching how LightBurn stores closed outlines.
+fn convert_segments(
+    data: &usvg::tiny_skia_path::Path,
+    xform: &XForm,
+) -> (Vec<Vec2>, Vec<PathPrimitive>) {
+    let point = |p: usvg::tiny_skia_path::Point| xform.transform_point(p.x as f64, p.y as f64);
+
+    let mut verts: Vec<Vec2> = Vec::new();
+    let mut prims: Vec<PathPrimitive> = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut current: Option<usize> = None;
+
+    for segment in data.segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                let (x, y) = point(p);
+                verts.push(Vec2::new(round(x), round(y)));
+                start = Some(verts.len() - 1);
+                current = start;
+            }
+            PathSegment::LineTo(p) => {
+                let Some(from) = current else { continue };
+                let (x, y) = point(p);
+                verts.push(Vec2::new(round(x), round(y)));
+                prims.push(PathPrimitive::Line {
+                    start_idx: from,
+                    end_idx: verts.len() - 1,
+                });
+                current = Some(verts.len() - 1);
+            }
+            PathSegment::QuadTo(c, p) => {
+                let Some(from) = current else { continue };
+                let p0 = (verts[from].x, verts[from].y);
+                let c = point(c);
+                let p1 = point(p);
+                let c0 = (
+                    p0.0 + 2.0 / 3.0 * (c.0 - p0.0),
+                    p0.1 + 2.0 / 3.0 * (c.1 - p0.1),
+                );
+                let c1 = (
+                    p1.0 + 2.0 / 3.0 * (c.0 - p1.0),
+                    p1.1 + 2.0 / 3.0 * (c.1 - p1.1),
+                );
+                current = Some(push_cubic(&mut verts, &mut prims, from, c0, c1, p1));
+            }
+            PathSegment::CubicTo(c0, c1, p) => {
+                let Some(from) = current else { continue };
+                let (c0, c1, p1) = (point(c0), point(c1), point(p));
+                current = Some(push_cubic(&mut verts, &mut prims, from, c0, c1, p1));
+            }
+            PathSegment::Close => {
+                let (Some(first), Some(last)) = (start, current) else {
+                    continue;
+                };
+                if first != last {
+                    let closes_on_start = (verts[last].x - verts[first].x).abs() < 1e-6
+                        && (verts[last].y - verts[first].y).abs() < 1e-6;
+
+                    if closes_on_start && last == verts.len() - 1 {
+                        // Re-point the final primitive at the start vertex
+                        let dup = verts.pop().unwrap();
+                        verts[first].c1x = dup.c1x;
+                        verts[first].c1y = dup.c1y;
+                        if let Some(
+                            PathPrimitive::Line { end_idx, .. }
+                            | PathPrimitive::Bezier { end_idx, .. },
+                        ) = prims.last_mut()
+                        {
+                            *end_idx = first;
+                        }
+                    } else {
+                        prims.push(PathPrimitive::Line {
+                            start_idx: last,
+                            end_idx: first,
+                        });
+                    }
+                }
+                current = start;
+            }
+        }
+    }
+
+    (verts, prims)
+}
+
+struct Importer<'a> {
+    layers: LayerTable<'a>,
+    /// Maps usvg canvas pixels to LightBurn millimetres with Y up
+    canvas: XForm,
+    shapes: Vec<Shape>,
+    /// Text elements usvg managed to outline
+    text_nodes: usize,
+    warnings: Vec<String>,
+}
+
+impl Importer<'_> {
+    fn import_group(&mut self, group: &usvg::Group) {
+        for node in group.children() {
+            match node {
+                usvg::Node::Group(g) => self.import_group(g),
+                usvg::Node::Path(p) => self.import_path(p),
+                usvg::Node::Image(i) => self.import_image(i),
+                usvg::Node::Text(t) => {
+                    self.text_nodes += 1;
+                    self.import_group(t.flattened());
+                }
+            }
+        }
+    }
+
+    fn import_path(&mut self, path: &usvg::Path) {
+        if !path.is_visible() {
+            return;
+        }
+
+        let xform = self.canvas.compose(&to_xform(path.abs_transform()));
+        let (verts, prims) = convert_segments(path.data(), &xform);
+        if verts.is_empty() || prims.is_empty() {
+            self.warnings.push(format!(
+                "Path '{}' has no drawable segments, skipping",
+                path.id()
+            ));
+            return;
+        }
+
+        // Laser layers follow the outline color, falling back to the fill
+        let color = path
+            .stroke()
+            .and_then(|s| solid_color(s.paint()))
+            .or_else(|| path.fill().and_then(|f| solid_color(f.paint())));
+        let cut_index = self.layers.index_for_color(color);
+
+        self.shapes.push(Shape::Path(Path {
+            cut_index,
+            xform: XForm::identity(),
+            vert_list: format_vert_list(&verts).into(),
+            prim_list: format_prim_list(&prims).into(),
+            parsed_verts: Arc::new(verts),
+            parsed_primitives: Arc::new(prims),
+            vert_id: None,
+            prim_id: None,
+        }));
+    }
+
+    fn import_image(&mut self, image: &usvg::Image) {
+        let data = match image.kind() {
+            usvg::ImageKind::JPEG(data)
+            | usvg::ImageKind::PNG(data)
+            | usvg::ImageKind::GIF(data)
+            | usvg::ImageKind::WEBP(data) => data,
+            usvg::ImageKind::SVG(_) => {
+                self.warnings.push(format!(
+                    "Image '{}' embeds an SVG document, skipping",
+                    image.id()
+                ));
+                return;
+            }
+        };
+
+        // usvg places the image at (0, 0)..size; LightBurn centers bitmaps
+        let size = image.size();
+        let (w, h) = (size.width() as f64, size.height() as f64);
+        let centered = XForm {
+            e: w / 2.0,
+            f: h / 2.0,
+            ..XForm::identity()
+        };
+        let mut xform = self
+            .canvas
+            .compose(&to_xform(image.abs_transform()))
+            .compose(&centered);
+
+        // Move the scale into W/H so the bitmap is sized in millimetres
+        let sx = xform.a.hypot(xform.b);
+        let sy = xform.c.hypot(xform.d);
+        if sx == 0.0 || sy == 0.0 {
+            return;
+        }
+        xform.a /= sx;
+        xform.b /= sx;
+        xform.c /= sy;
+        xform.d /= sy;
+
+        let cut_index = self.layers.index_for_image();
+        self.shapes.push(Shape::Bitmap(Bitmap {
+            cut_index,
+            xform: XForm {
+                a: round(xform.a),
+                b: round(xform.b),
+                c: round(xform.c),
+                d: round(xform.d),
+                e: round(xform.e),
+                f: round(xform.f),
+            },
+            w: round(w * sx),
+            h: round(h * sy),
+            data: encode_bitmap_data(data),
+        }));
+    }
+}
+
+/// Convert an SVG document to a LightBurnProject
+pub fn svg_to_lbrn2(svg: &str, options: &SvgImportOptions) -> Result<LightBurnProject, String> {
+    svg_to_lbrn2_with_warnings(svg, options).map(|(project, _)| project)
+}
+
+/// Convert an SVG document to a LightBurnProject, also returning the list of
+/// elements that could not be imported
+pub fn svg_to_lbrn2_with_warnings(
+    svg: &str,
+    options: &SvgImportOptions,
+) -> Result<(LightBurnProject, Vec<String>), String> {
+    let usvg_options = usvg::Options {
+        dpi: options.dpi,
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &usvg_options)
+        .map_err(|e| format!("Failed to parse SVG: {}", e))?;
+
+    // usvg keeps neither the viewBox origin nor text it cannot outline, so
+    // look at the source document for those
+    let source_opt = usvg::roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let source = usvg::roxmltree::Document::parse_with_options(svg, source_opt)
+        .map_err(|e| format!("Failed to parse SVG: {}", e))?;
+
+    // Canvas pixels -> user units -> millimetres, with Y pointing up
+    let (tx, ty) = view_box_translation(source.root_element(), tree.size());
+    let mm_per_px = 25.4 / options.dpi as f64;
+    let canvas = XForm {
+        a: mm_per_px,
+        b: 0.0,
+        c: 0.0,
+        d: -mm_per_px,
+        e: -tx * mm_per_px,
+        f: ty * mm_per_px,
+    };
+
+    let mut importer = Importer {
+        layers: LayerTable::new(options),
+        canvas,
+        shapes: Vec::new(),
+        text_nodes: 0,
+        warnings: Vec::new(),
+    };
+    importer.import_group(tree.root());
+
+    let text_elements = source
+        .descendants()
+        .filter(|n| n.has_tag_name(("http://www.w3.org/2000/svg", "text")))
+        .count();
+    if text_elements > importer.text_nodes {
+        importer.warnings.push(format!(
+            "{} text element(s) skipped: no font available to outline them",
+            text_elements - importer.text_nodes
+        ));
+    }
+
+    let project = LightBurnProject {
+        app_version: String::new(),
+        format_version: "1".to_string(),
+        cut_settings: importer.layers.into_cut_settings(),
+        shapes: importer.shapes,
+        ..Default::default()
+    };
+    Ok((project, importer.warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(svg: &str) -> LightBurnProject {
+        svg_to_lbrn2(svg, &SvgImportOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_normalize_color() {
+        assert_eq!(normalize_color("#ff0000"), Some("#FF0000".to_string()));
+        assert_eq!(normalize_color("0f0"), Some("#00FF00".to_string()));
+        assert_eq!(normalize_color("#12345"), None);
+        assert_eq!(normalize_color("red"), None);
+    }
+
+    #[test]
+    fn test_rect_becomes_closed_path_in_millimetres() {
+        let project = import(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="50mm" viewBox="0 -50 100 50">
+                <rect x="10" y="-20" width="30" height="10" style="stroke:#FF0000;fill:none"/>
+            </svg>"##,
+        );
+
+        assert_eq!(project.shapes.len(), 1);
+        let Shape::Path(path) = &project.shapes[0] else {
+            panic!("expected a path");
+        };
+        assert_eq!(path.cut_index, 2);
+        assert_eq!(path.parsed_verts.len(), 4);
+        assert_eq!(path.parsed_primitives.len(), 4);
+
+        // Y is flipped back, so the rect spans y = 10..20 in LightBurn space
+        let ys: Vec<f64> = path.parsed_verts.iter().map(|v| v.y).collect();
+        let xs: Vec<f64> = path.parsed_verts.iter().map(|v| v.x).collect();
+        assert!(
+            ys.iter()
+                .all(|y| (*y - 10.0).abs() < 1e-3 || (*y - 20.0).abs() < 1e-3)
+        );
+        assert!(
+            xs.iter()
+                .all(|x| (*x - 10.0).abs() < 1e-3 || (*x - 40.0).abs() < 1e-3)
+        );
+
+        // Last primitive closes back onto the first vertex
+        assert!(matches!(
+            path.parsed_primitives.last(),
+            Some(PathPrimitive::Line { end_idx: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_circle_becomes_bezier_path() {
+        let project = import(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="20mm" height="20mm" viewBox="0 0 20 20">
+                <circle cx="10" cy="10" r="5" fill="#0000FF"/>
+            </svg>"##,
+        );
+        let Shape::Path(path) = &project.shapes[0] else {
+            panic!("expected a path");
+        };
+        assert_eq!(path.cut_index, 1);
+        assert!(
+            path.parsed_primitives
+                .iter()
+                .all(|p| matches!(p, PathPrimitive::Bezier { .. }))
+        );
+        assert!(
+            path.parsed_verts
+                .iter()
+                .all(|v| v.c0x.is_some() && v.c1x.is_some())
+        );
+    }
+
+    #[test]
+    fn test_color_table_and_auto_allocation() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <line x1="0" y1="0" x2="10" y2="0" stroke="#123456"/>
+            <line x1="0" y1="1" x2="10" y2="1" stroke="#654321"/>
+            <line x1="0" y1="2" x2="10" y2="2" stroke="#123456"/>
+            <line x1="0" y1="3" x2="10" y2="3" stroke="#000000"/>
+        </svg>"##;
+        let options = SvgImportOptions::default().with_layer("#654321", 7);
+        let project = svg_to_lbrn2(svg, &options).unwrap();
+
+        let indices: Vec<i32> = project.shapes.iter().map(|s| s.cut_index()).collect();
+        assert_eq!(indices, vec![1, 7, 1, 0]);
+
+        let layers: Vec<(i32, &str)> = project
+            .cut_settings
+            .iter()
+            .map(|cs| (cs.index, cs.name.as_str()))
+            .collect();
+        assert_eq!(layers, vec![(0, "C00"), (1, "C01"), (7, "C07")]);
+    }
+
+    #[test]
+    fn test_embedded_image_becomes_bitmap() {
+        // 1x1 transparent PNG
+        let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=";
+        let svg = format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="20mm" height="20mm" viewBox="0 0 20 20">
+                <image x="5" y="5" width="10" height="4" preserveAspectRatio="none" xlink:href="data:image/png;base64,{}"/>
+            </svg>"##,
+            png
+        );
+        let (project, warnings) =
+            svg_to_lbrn2_with_warnings(&svg, &SvgImportOptions::default()).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        let Shape::Bitmap(bitmap) = &project.shapes[0] else {
+            panic!("expected a bitmap");
+        };
+        assert_eq!(bitmap.data, png);
+        assert!((bitmap.w - 10.0).abs() < 1e-4);
+        assert!((bitmap.h - 4.0).abs() < 1e-4);
+        // Centered on (10, 7) in SVG space, i.e. (10, -7) with Y up
+        assert!((bitmap.xform.e - 10.0).abs() < 1e-4);
+        assert!((bitmap.xform.f + 7.0).abs() < 1e-4);
+        assert_eq!(project.cut_settings[0].cut_type, CutType::Image);
+    }
+}
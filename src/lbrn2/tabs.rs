@@ -0,0 +1,400 @@
+//! Holding tabs
+//!
+//! Leaves short uncut bridges in closed contours so parts stay in the sheet.
+
+use super::geometry::{
+    BezPath, Leaf, Point, Segment, collect_leaves, even_odd_holes, splice_leaves,
+};
+use super::types::{LightBurnProject, Shape, TabSettings, XForm};
+
+/// Options for [`add_tabs`] and [`add_layer_tabs`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabOptions {
+    /// Width of each gap in mm (default: 0.5)
+    pub size: f64,
+    /// Tabs per contour, or the least when `spacing` is set (default: 4)
+    pub count: u32,
+    /// Distance between tabs in mm; longer contours get more tabs, at least
+    /// `count` and at most `count_max` (default: none)
+    pub spacing: Option<f64>,
+    /// Most tabs per contour when `spacing` is set (default: none)
+    pub count_max: Option<u32>,
+    /// Tab centers as fractions of each contour's length from its start,
+    /// replacing the evenly spaced tabs (default: none)
+    pub positions: Option<Vec<f64>>,
+    /// Leave contours without tabs when their bounds are smaller than this
+    /// in both directions, in mm (default: 0)
+    pub min_size: f64,
+    /// Only put tabs on outer contours, not on holes (default: false)
+    pub outer_only: bool,
+    /// Maximum curve flattening error in mm when telling holes from outer
+    /// contours (default: 0.01)
+    pub tolerance: f64,
+}
+
+impl Default for TabOptions {
+    fn default() -> Self {
+        Self {
+            size: 0.5,
+            count: 4,
+            spacing: None,
+            count_max: None,
+            positions: None,
+            min_size: 0.0,
+            outer_only: false,
+            tolerance: 0.01,
+        }
+    }
+}
+
+impl TabOptions {
+    /// These options with the tab parameters a cut setting specifies
+    pub fn with_settings(&self, tabs: &TabSettings) -> TabOptions {
+        TabOptions {
+            size: tabs.size.unwrap_or(self.size),
+            count: tabs.count.unwrap_or(self.count),
+            spacing: tabs.spacing.or(self.spacing),
+            count_max: tabs.count_max.or(self.count_max),
+            outer_only: tabs.skip_inner.unwrap_or(self.outer_only),
+            ..self.clone()
+        }
+    }
+
+    /// Arc lengths of the tab centers on a contour `length` mm long
+    fn centers(&self, length: f64) -> Vec<f64> {
+        if let Some(positions) = &self.positions {
+            return positions
+                .iter()
+                .map(|f| f.rem_euclid(1.0) * length)
+                .collect();
+        }
+        let mut count = self.count;
+        if let Some(spacing) = self.spacing.filter(|&s| s > 0.0) {
+            count = count.max((length / spacing).ceil() as u32);
+            if let Some(max) = self.count_max {
+                count = count.min(max.max(self.count));
+            }
+        }
+        // Half a step in from the start, so the seam is never in a gap
+        let step = length / count as f64;
+        (0..count).map(|i| (i as f64 + 0.5) * step).collect()
+    }
+}
+
+/// What [`add_tabs`] did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TabReport {
+    /// Gaps cut
+    pub tabs: usize,
+    /// Closed contours that got tabs
+    pub contours: usize,
+    /// Closed contours on tabbed layers left whole: smaller than
+    /// `min_size`, holes with `outer_only`, or too short for their gaps
+    pub skipped: usize,
+}
+
+/// Piece of `contour` between arc lengths `from` and `to`, where
+/// `0 <= from < to <= length`
+fn stretch(contour: &BezPath, lengths: &[f64], from: f64, to: f64, out: &mut Vec<Segment>) {
+    let mut start = 0.0;
+    for (segment, &length) in contour.segments.iter().zip(lengths) {
+        let end = start + length;
+        if end > from && start < to && length > 0.0 {
+            let t0 = if from > start {
+                segment.parameter_at(from - start)
+            } else {
+                0.0
+            };
+            let t1 = if to < end {
+                segment.parameter_at(to - start)
+            } else {
+                1.0
+            };
+            out.push(if t0 == 0.0 && t1 == 1.0 {
+                *segment
+            } else {
+                segment.subsegment(t0, t1)
+            });
+        }
+        start = end;
+    }
+}
+
+/// Open pieces of a closed contour left between gaps `size` mm wide
+/// centered on `centers`, or `None` when the gaps would swallow it
+fn cut_gaps(contour: &BezPath, centers: &[f64], size: f64) -> Option<Vec<BezPath>> {
+    let lengths: Vec<f64> = contour.segments.iter().map(Segment::length).collect();
+    let total: f64 = lengths.iter().sum();
+    if centers.is_empty() || centers.len() as f64 * size >= total {
+        return None;
+    }
+    let mut starts: Vec<f64> = centers
+        .iter()
+        .map(|c| (c - size / 2.0).rem_euclid(total))
+        .collect();
+    starts.sort_by(f64::total_cmp);
+
+    let mut pieces = Vec::new();
+    for (i, &gap) in starts.iter().enumerate() {
+        let from = gap + size;
+        let next = starts.get(i + 1).copied().unwrap_or(starts[0] + total);
+        if next - from <= 1e-9 {
+            continue;
+        }
+        // A piece may run on past the seam into the start of the contour
+        let mut segments = Vec::new();
+        let from = from.rem_euclid(total);
+        let to = from + (next - (gap + size));
+        if to <= total {
+            stretch(contour, &lengths, from, to, &mut segments);
+        } else {
+            stretch(contour, &lengths, from, total, &mut segments);
+            stretch(contour, &lengths, 0.0, to - total, &mut segments);
+        }
+        if !segments.is_empty() {
+            pieces.push(BezPath {
+                segments,
+                closed: false,
+            });
+        }
+    }
+    Some(pieces)
+}
+
+/// Put tabs on the closed contours of every layer
+///
+/// Shapes that get tabs are replaced by paths in their own coordinates,
+/// keeping their transform. Open contours and bitmaps are left as they are.
+/// Also returns warnings about primitives that refer to missing vertices.
+pub fn add_tabs(
+    project: &LightBurnProject,
+    options: &TabOptions,
+) -> (LightBurnProject, TabReport, Vec<String>) {
+    add_tabs_by_layer(project, |_| Some(options.clone()))
+}
+
+/// Put tabs on the layers whose cut setting enables them, using the cut
+/// setting's tab size, count, spacing and hole skipping over `defaults`
+///
+/// Everything else works as in [`add_tabs`].
+pub fn add_layer_tabs(
+    project: &LightBurnProject,
+    defaults: &TabOptions,
+) -> (LightBurnProject, TabReport, Vec<String>) {
+    add_tabs_by_layer(project, |cut_index| {
+        let setting = project
+            .cut_settings
+            .iter()
+            .find(|cs| cs.index == cut_index)?;
+        (setting.tabs.enabled == Some(true)).then(|| defaults.with_settings(&setting.tabs))
+    })
+}
+
+fn add_tabs_by_layer(
+    project: &LightBurnProject,
+    options_for: impl Fn(i32) -> Option<TabOptions>,
+) -> (LightBurnProject, TabReport, Vec<String>) {
+    let mut log = Vec::new();
+    let mut leaves: Vec<Leaf> = Vec::new();
+    collect_leaves(&project.shapes, &XForm::identity(), &mut leaves, &mut log);
+    let mut report = TabReport::default();
+
+    // Closed contours flattened for telling holes from outer contours
+    let rings: Vec<Vec<Vec<Point>>> = leaves
+        .iter()
+        .map(|leaf| {
+            let tolerance = options_for(leaf.shape.cut_index()).map_or(0.01, |o| o.tolerance);
+            leaf.contours
+                .iter()
+                .map(|c| match c.closed {
+                    true => c.flatten(tolerance).points,
+                    false => Vec::new(),
+                })
+                .collect()
+        })
+        .collect();
+    let layered: Vec<(i32, &[Point])> = leaves
+        .iter()
+        .zip(&rings)
+        .flat_map(|(leaf, rings)| {
+            let cut_index = leaf.shape.cut_index();
+            rings.iter().map(move |ring| (cut_index, ring.as_slice()))
+        })
+        .collect();
+    let mut flags = even_odd_holes(&layered).into_iter();
+    let holes: Vec<Vec<bool>> = rings
+        .iter()
+        .map(|rings| flags.by_ref().take(rings.len()).collect())
+        .collect();
+
+    let replacements = leaves.iter().enumerate().map(|(l, leaf)| {
+        let options = options_for(leaf.shape.cut_index())?;
+        let mut tabbed = false;
+        let mut contours = Vec::new();
+        for (c, contour) in leaf.contours.iter().enumerate() {
+            if !contour.closed {
+                contours.push(contour.clone());
+                continue;
+            }
+            let points = &rings[l][c];
+            let (min, max) = points.iter().fold(
+                (
+                    (f64::INFINITY, f64::INFINITY),
+                    (f64::NEG_INFINITY, f64::NEG_INFINITY),
+                ),
+                |(min, max), p| {
+                    (
+                        (min.0.min(p.0), min.1.min(p.1)),
+                        (max.0.max(p.0), max.1.max(p.1)),
+                    )
+                },
+            );
+            let small = max.0 - min.0 < options.min_size && max.1 - min.1 < options.min_size;
+            let pieces = if small || (options.outer_only && holes[l][c]) {
+                None
+            } else {
+                let centers = options.centers(contour.length());
+                cut_gaps(contour, &centers, options.size).map(|pieces| (centers.len(), pieces))
+            };
+            match pieces {
+                Some((tabs, pieces)) => {
+                    report.tabs += tabs;
+                    report.contours += 1;
+                    tabbed = true;
+                    contours.extend(pieces);
+                }
+                None => {
+                    report.skipped += 1;
+                    contours.push(contour.clone());
+                }
+            }
+        }
+        if !tabbed {
+            return None;
+        }
+        leaf.path(&contours).map(|shape| vec![shape])
+    });
+    let mut replacements: Vec<Option<Vec<Shape>>> = replacements.collect();
+
+    let mut project = project.clone();
+    splice_leaves(&mut project.shapes, &mut replacements.drain(..));
+    (project, report, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::testing::{contours, square};
+    use crate::lbrn2::types::{CutSetting, Ellipse};
+
+    #[test]
+    fn test_even_tabs_split_contours() {
+        let circle = Shape::Ellipse(Ellipse {
+            cut_index: 0,
+            xform: XForm::identity(),
+            rx: 10.0,
+            ry: 10.0,
+        });
+        let project = LightBurnProject {
+            shapes: vec![square(30.0, 0.0, 20.0, 0), circle],
+            ..Default::default()
+        };
+        let options = TabOptions {
+            size: 1.0,
+            ..Default::default()
+        };
+
+        let (tabbed, report, _) = add_tabs(&project, &options);
+        assert_eq!(
+            report,
+            TabReport {
+                tabs: 8,
+                contours: 2,
+                skipped: 0
+            }
+        );
+        let pieces = contours(&tabbed.shapes);
+        assert_eq!(pieces.len(), 8);
+        assert!(pieces.iter().all(|p| !p.closed));
+
+        // Each side of the square loses its middle millimeter
+        assert_eq!(pieces[0].start(), Some((30.5, -10.0)));
+        assert_eq!(pieces[0].end(), Some((40.0, -0.5)));
+        let square_length: f64 = pieces[..4].iter().map(BezPath::length).sum();
+        assert!((square_length - 76.0).abs() < 1e-9);
+
+        // Gaps on curves are as wide as on lines
+        let circle_length: f64 = pieces[4..].iter().map(BezPath::length).sum();
+        let circumference = project.shapes[1].to_bezpath(&XForm::identity())[0].length();
+        assert!((circumference - circle_length - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_positions_and_skipped_contours() {
+        // A frame with a hole, and a part too small for tabs
+        let project = LightBurnProject {
+            shapes: vec![
+                square(0.0, 0.0, 20.0, 0),
+                square(0.0, 0.0, 10.0, 0),
+                square(30.0, 0.0, 2.0, 0),
+            ],
+            ..Default::default()
+        };
+        let options = TabOptions {
+            size: 2.0,
+            positions: Some(vec![0.0, 0.5]),
+            min_size: 5.0,
+            outer_only: true,
+            ..Default::default()
+        };
+
+        let (tabbed, report, _) = add_tabs(&project, &options);
+        assert_eq!(
+            report,
+            TabReport {
+                tabs: 2,
+                contours: 1,
+                skipped: 2
+            }
+        );
+        assert_eq!(tabbed.shapes[1..], project.shapes[1..]);
+
+        // Tabs on the start corner and the one opposite
+        let pieces = tabbed.shapes[0].to_bezpath(&XForm::identity());
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].start(), Some((9.0, 10.0)));
+        assert_eq!(pieces[0].end(), Some((-10.0, -9.0)));
+        assert_eq!(pieces[1].start(), Some((-9.0, -10.0)));
+        assert_eq!(pieces[1].end(), Some((10.0, 9.0)));
+    }
+
+    #[test]
+    fn test_layer_settings_choose_tabbed_layers() {
+        let mut on = CutSetting::new(0);
+        on.tabs = TabSettings {
+            enabled: Some(true),
+            size: Some(1.0),
+            spacing: Some(10.0),
+            count: Some(2),
+            count_max: Some(6),
+            skip_inner: None,
+        };
+        let mut other = square(50.0, 0.0, 20.0, 0);
+        if let Shape::Rect(rect) = &mut other {
+            rect.cut_index = 1;
+        }
+        let project = LightBurnProject {
+            cut_settings: vec![on, CutSetting::new(1)],
+            shapes: vec![square(0.0, 0.0, 20.0, 0), other, square(0.0, 40.0, 2.0, 0)],
+            ..Default::default()
+        };
+
+        let (tabbed, report, _) = add_layer_tabs(&project, &TabOptions::default());
+        // Spacing asks for 8 tabs on the large square, capped at 6; the
+        // small one gets the minimum count
+        assert_eq!(report.tabs, 8);
+        assert_eq!(report.contours, 2);
+        assert_eq!(tabbed.shapes[1], project.shapes[1]);
+        assert_eq!(tabbed.shapes[0].to_bezpath(&XForm::identity()).len(), 6);
+    }
+}
@@ -0,0 +1,37 @@
+//! Shapes shared by the unit tests
+
+use super::geometry::{BezPath, Point};
+use super::types::{Rect, Shape, XForm};
+
+/// Identity transform moved by (e, f)
+pub(crate) fn translate(e: f64, f: f64) -> XForm {
+    XForm {
+        e,
+        f,
+        ..XForm::identity()
+    }
+}
+
+/// Square rectangle centered on (x, y)
+pub(crate) fn square(x: f64, y: f64, size: f64, cut_index: i32) -> Shape {
+    Shape::Rect(Rect {
+        cut_index,
+        xform: translate(x, y),
+        w: size,
+        h: size,
+        cr: 0.0,
+    })
+}
+
+/// Counterclockwise square ring with its lower left corner at (x, y)
+pub(crate) fn square_ring(x: f64, y: f64, size: f64) -> Vec<Point> {
+    vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)]
+}
+
+/// World-space contours of every shape
+pub(crate) fn contours(shapes: &[Shape]) -> Vec<BezPath> {
+    shapes
+        .iter()
+        .flat_map(|s| s.to_bezpath(&XForm::identity()))
+        .collect()
+}
@@ -1,3 +1,10 @@
+use super::bitmap::{decode_bitmap_data, reencode_as_png, sniff_image_mime};
+use super::bounds::{Bounds, get_transformed_bounds};
+use super::writer::{format_prim_list, format_vert_list};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
 /// 2D vertex with optional Bezier control points
 #[derive(Debug, Clone, PartialEq)]
 pub struct Vec2 {
@@ -82,6 +89,22 @@ impl XForm {
         }
     }
 
+    /// Inverse transform, or `None` when the matrix is singular
+    pub fn inverse(&self) -> Option<XForm> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        Some(XForm {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
     /// Transform a point
     pub fn transform_point(&self, x: f64, y: f64) -> (f64, f64) {
         (
@@ -91,13 +114,97 @@ impl XForm {
     }
 }
 
+/// Kind of operation a cut setting performs (the `type` attribute)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CutType {
+    #[default]
+    Cut,
+    Scan,
+    Image,
+    OffsetFill,
+    /// Any type this crate does not know about, kept verbatim
+    Other(String),
+}
+
+impl CutType {
+    pub fn from_attr(value: &str) -> Self {
+        match value {
+            "Cut" => CutType::Cut,
+            "Scan" => CutType::Scan,
+            "Image" => CutType::Image,
+            "Offset Fill" => CutType::OffsetFill,
+            other => CutType::Other(other.to_string()),
+        }
+    }
+
+    /// The value LightBurn writes in the `type` attribute
+    pub fn as_str(&self) -> &str {
+        match self {
+            CutType::Cut => "Cut",
+            CutType::Scan => "Scan",
+            CutType::Image => "Image",
+            CutType::OffsetFill => "Offset Fill",
+            CutType::Other(s) => s,
+        }
+    }
+}
+
+/// Holding tab parameters of a cut setting
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TabSettings {
+    /// `tabsEnabled`
+    pub enabled: Option<bool>,
+    /// `tabSize`: gap width in mm
+    pub size: Option<f64>,
+    /// `tabSpacing`: distance between tabs in mm
+    pub spacing: Option<f64>,
+    /// `tabCount`: minimum number of tabs per shape
+    pub count: Option<u32>,
+    /// `tabCountMax`: maximum number of tabs per shape
+    pub count_max: Option<u32>,
+    /// `skipInnerTabs`: only put tabs on outer contours
+    pub skip_inner: Option<bool>,
+}
+
 /// Cut setting for laser operations
-#[derive(Debug, Clone)]
+///
+/// Numeric parameters are `None` when the file does not specify them. Any
+/// child parameter not mapped to a field is kept in `params`, keyed by its
+/// element name.
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct CutSetting {
     pub index: i32,
     pub name: String,
     pub color: Option<String>,
     pub stroke_width: Option<String>,
+    pub cut_type: CutType,
+    /// Minimum power in percent
+    pub min_power: Option<f64>,
+    /// Maximum power in percent
+    pub max_power: Option<f64>,
+    /// Speed in mm/s
+    pub speed: Option<f64>,
+    pub num_passes: Option<u32>,
+    pub priority: Option<i32>,
+    /// Z offset in mm
+    pub z_offset: Option<f64>,
+    /// Kerf offset in mm
+    pub kerf: Option<f64>,
+    pub tabs: TabSettings,
+    /// `doOutput`: whether the layer is sent to the laser
+    pub do_output: Option<bool>,
+    /// `hide`: whether the layer is hidden in the workspace
+    pub hide: Option<bool>,
+    pub params: BTreeMap<String, String>,
+}
+
+impl CutSetting {
+    pub fn new(index: i32) -> Self {
+        Self {
+            index,
+            ..Default::default()
+        }
+    }
 }
 
 /// Path primitive intermediate representation
@@ -108,7 +215,7 @@ pub enum PathPrimitive {
 }
 
 /// Rectangle shape
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Rect {
     pub cut_index: i32,
     pub xform: XForm,
@@ -118,7 +225,7 @@ pub struct Rect {
 }
 
 /// Ellipse shape
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ellipse {
     pub cut_index: i32,
     pub xform: XForm,
@@ -127,18 +234,54 @@ pub struct Ellipse {
 }
 
 /// Path shape with vertices and primitives
-#[derive(Debug, Clone)]
+///
+/// Paths that reuse another shape's `VertID`/`PrimID` share the same
+/// allocations and keep the ID, so the writer can emit the list once. Use
+/// [`LightBurnProject::set_shared_verts`] to edit every instance at once, or
+/// `Arc::make_mut` and clear the ID to edit one path on its own.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Path {
     pub cut_index: i32,
     pub xform: XForm,
-    pub vert_list: String,
-    pub prim_list: String,
-    pub parsed_verts: Vec<Vec2>,
-    pub parsed_primitives: Vec<PathPrimitive>,
+    pub vert_list: Arc<str>,
+    pub prim_list: Arc<str>,
+    pub parsed_verts: Arc<Vec<Vec2>>,
+    pub parsed_primitives: Arc<Vec<PathPrimitive>>,
+    /// `VertID` the vertices are shared under
+    pub vert_id: Option<i32>,
+    /// `PrimID` the primitives are shared under
+    pub prim_id: Option<i32>,
+}
+
+impl Path {
+    /// Path from parsed vertices and primitives, without raw list text
+    pub fn from_parts(
+        cut_index: i32,
+        xform: XForm,
+        verts: Vec<Vec2>,
+        prims: Vec<PathPrimitive>,
+    ) -> Self {
+        Self {
+            cut_index,
+            xform,
+            vert_list: Arc::from(""),
+            prim_list: Arc::from(""),
+            parsed_verts: Arc::new(verts),
+            parsed_primitives: Arc::new(prims),
+            vert_id: None,
+            prim_id: None,
+        }
+    }
+
+    /// Whether the PrimList is the `LineClosed` shorthand: straight lines
+    /// through every vertex, back to the first
+    pub fn is_line_closed(&self) -> bool {
+        &*self.prim_list == "LineClosed"
+    }
 }
 
 /// Bitmap/image shape
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Bitmap {
     pub cut_index: i32,
     pub xform: XForm,
@@ -147,8 +290,76 @@ pub struct Bitmap {
     pub data: String, // Base64 encoded image data
 }
 
+impl Bitmap {
+    /// Decode the image bytes; the parser keeps only the base64 text, so
+    /// nothing is decoded until this is called
+    pub fn decode_data(&self) -> Result<Vec<u8>, String> {
+        decode_bitmap_data(&self.data)
+    }
+}
+
+/// Text shape
+///
+/// LightBurn renders text itself from these attributes; files saved with
+/// `HasBackupPath="1"` also carry the rendered glyph outlines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub cut_index: i32,
+    pub xform: XForm,
+    /// `Str`: the text itself
+    pub text: String,
+    /// `Font`: Qt font description, e.g. `Sans Serif,-1,100,5,50,0,0,0,0,0`
+    pub font: String,
+    /// `H`: character height in mm
+    pub h: f64,
+    /// `LS`: letter spacing
+    pub letter_spacing: f64,
+    /// `LnS`: line spacing
+    pub line_spacing: f64,
+    /// `Ah`: horizontal alignment (0 left, 1 center, 2 right)
+    pub align_h: i32,
+    /// `Av`: vertical alignment (0 baseline, 1 middle, 2 top, 3 bottom)
+    pub align_v: i32,
+    /// `Weld`: merge overlapping glyphs into one outline
+    pub weld: bool,
+    /// Glyph outlines from `BackupPath`, in the text's local coordinates;
+    /// its own `xform` and `cut_index` are ignored in favour of the text's
+    pub backup_path: Option<Path>,
+}
+
+impl Text {
+    /// The glyph outlines as a path placed like the text, if the file
+    /// carried them
+    pub fn outline(&self) -> Option<Path> {
+        self.backup_path.as_ref().map(|bp| Path {
+            cut_index: self.cut_index,
+            xform: self.xform,
+            ..bp.clone()
+        })
+    }
+
+    /// Font family: the first field of the Qt font description
+    pub fn font_family(&self) -> &str {
+        self.font.split(',').next().unwrap_or("").trim()
+    }
+
+    /// Whether the Qt font description asks for a bold weight
+    pub fn is_bold(&self) -> bool {
+        self.font_field(4).is_some_and(|weight| weight >= 75.0)
+    }
+
+    /// Whether the Qt font description asks for an italic style
+    pub fn is_italic(&self) -> bool {
+        self.font_field(5).is_some_and(|italic| italic != 0.0)
+    }
+
+    fn font_field(&self, index: usize) -> Option<f64> {
+        self.font.split(',').nth(index)?.trim().parse().ok()
+    }
+}
+
 /// Group of shapes
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Group {
     pub cut_index: i32,
     pub xform: XForm,
@@ -156,12 +367,13 @@ pub struct Group {
 }
 
 /// All possible shape types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Shape {
     Rect(Rect),
     Ellipse(Ellipse),
     Path(Path),
     Bitmap(Bitmap),
+    Text(Text),
     Group(Group),
 }
 
@@ -172,6 +384,7 @@ impl Shape {
             Shape::Ellipse(e) => &e.xform,
             Shape::Path(p) => &p.xform,
             Shape::Bitmap(b) => &b.xform,
+            Shape::Text(t) => &t.xform,
             Shape::Group(g) => &g.xform,
         }
     }
@@ -182,6 +395,7 @@ impl Shape {
             Shape::Ellipse(e) => &mut e.xform,
             Shape::Path(p) => &mut p.xform,
             Shape::Bitmap(b) => &mut b.xform,
+            Shape::Text(t) => &mut t.xform,
             Shape::Group(g) => &mut g.xform,
         }
     }
@@ -192,16 +406,161 @@ impl Shape {
             Shape::Ellipse(e) => e.cut_index,
             Shape::Path(p) => p.cut_index,
             Shape::Bitmap(b) => b.cut_index,
+            Shape::Text(t) => t.cut_index,
             Shape::Group(g) => g.cut_index,
         }
     }
 }
 
+/// Project notes (`<Notes>`)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Notes {
+    pub text: String,
+    /// `ShowOnLoad`: LightBurn pops the notes up when the file is opened
+    pub show_on_load: bool,
+}
+
 /// Parsed LightBurn project file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct LightBurnProject {
     pub app_version: String,
     pub format_version: String,
+    /// `DeviceName`: the laser profile the file was saved with
+    pub device_name: Option<String>,
+    /// `MaterialHeight` in mm
+    pub material_height: Option<f64>,
+    /// `MirrorX`: the job is output mirrored left to right
+    pub mirror_x: bool,
+    /// `MirrorY`: the job is output mirrored top to bottom
+    pub mirror_y: bool,
+    pub notes: Option<Notes>,
+    /// `Source` of `<Thumbnail>`: base64 encoded preview image
+    pub thumbnail: Option<String>,
+    /// Children of `<UIPrefs>`, keyed by element name
+    pub ui_prefs: BTreeMap<String, String>,
     pub cut_settings: Vec<CutSetting>,
     pub shapes: Vec<Shape>,
+    /// Vertex lists defined under a `VertID`, shared with every path that
+    /// carries the ID
+    pub shared_verts: BTreeMap<i32, Arc<Vec<Vec2>>>,
+    /// Primitive lists defined under a `PrimID`, shared with every path that
+    /// carries the ID
+    pub shared_prims: BTreeMap<i32, Arc<Vec<PathPrimitive>>>,
+}
+
+impl LightBurnProject {
+    /// The embedded thumbnail as PNG bytes, re-encoded if it was stored in
+    /// another format; `None` when the file has no thumbnail
+    pub fn thumbnail_png(&self) -> Option<Result<Vec<u8>, String>> {
+        let data = self.thumbnail.as_deref()?;
+        Some(decode_bitmap_data(data).and_then(|bytes| {
+            if sniff_image_mime(&bytes) == Some("image/png") {
+                Ok(bytes)
+            } else {
+                reencode_as_png(&bytes)
+            }
+        }))
+    }
+
+    /// Replace the vertices shared under `id` in the table and in every path
+    /// that carries it, returning how many paths changed
+    pub fn set_shared_verts(&mut self, id: i32, verts: Vec<Vec2>) -> usize {
+        let verts = Arc::new(verts);
+        let vert_list: Arc<str> = format_vert_list(&verts).into();
+        self.shared_verts.insert(id, verts.clone());
+        for_each_path_mut(&mut self.shapes, &mut |path| {
+            if path.vert_id != Some(id) {
+                return false;
+            }
+            path.parsed_verts = verts.clone();
+            path.vert_list = vert_list.clone();
+            true
+        })
+    }
+
+    /// Replace the primitives shared under `id` in the table and in every
+    /// path that carries it, returning how many paths changed
+    pub fn set_shared_prims(&mut self, id: i32, prims: Vec<PathPrimitive>) -> usize {
+        let prims = Arc::new(prims);
+        let prim_list: Arc<str> = format_prim_list(&prims).into();
+        self.shared_prims.insert(id, prims.clone());
+        for_each_path_mut(&mut self.shapes, &mut |path| {
+            if path.prim_id != Some(id) {
+                return false;
+            }
+            path.parsed_primitives = prims.clone();
+            path.prim_list = prim_list.clone();
+            true
+        })
+    }
+
+    /// The project with `MirrorX`/`MirrorY` baked into the shapes
+    ///
+    /// Shapes are mirrored about the center of their combined bounds, so the
+    /// job stays where it is on the bed. Borrows the project unchanged when
+    /// neither flag is set.
+    pub fn with_mirror_applied(&self) -> Cow<'_, LightBurnProject> {
+        if !self.mirror_x && !self.mirror_y {
+            return Cow::Borrowed(self);
+        }
+
+        let mut bounds: Option<Bounds> = None;
+        for shape in &self.shapes {
+            if let Some(b) = get_transformed_bounds(shape) {
+                match &mut bounds {
+                    None => bounds = Some(b),
+                    Some(acc) => acc.expand(&b),
+                }
+            }
+        }
+
+        let mut project = self.clone();
+        project.mirror_x = false;
+        project.mirror_y = false;
+        let Some(bounds) = bounds else {
+            return Cow::Owned(project);
+        };
+
+        // Bounds are Y-down; flip back to LightBurn's Y-up workspace
+        let cx = (bounds.min_x + bounds.max_x) / 2.0;
+        let cy = -(bounds.min_y + bounds.max_y) / 2.0;
+        let mut mirror = XForm::identity();
+        if self.mirror_x {
+            mirror.a = -1.0;
+            mirror.e = 2.0 * cx;
+        }
+        if self.mirror_y {
+            mirror.d = -1.0;
+            mirror.f = 2.0 * cy;
+        }
+
+        for shape in &mut project.shapes {
+            *shape.xform_mut() = mirror.compose(shape.xform());
+        }
+        Cow::Owned(project)
+    }
+}
+
+/// Call `f` on every path, including those inside groups and text backup
+/// paths, counting the calls that return true
+fn for_each_path_mut(shapes: &mut [Shape], f: &mut impl FnMut(&mut Path) -> bool) -> usize {
+    let mut changed = 0;
+    for shape in shapes {
+        let path = match shape {
+            Shape::Path(path) => path,
+            Shape::Text(Text {
+                backup_path: Some(path),
+                ..
+            }) => path,
+            Shape::Group(group) => {
+                changed += for_each_path_mut(&mut group.children, f);
+                continue;
+            }
+            _ => continue,
+        };
+        if f(path) {
+            changed += 1;
+        }
+    }
+    changed
 }
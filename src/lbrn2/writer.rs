@@ -0,0 +1,436 @@
+//! LBRN2 serialization
+//!
+//! Writes a [`LightBurnProject`] back out as an LBRN2 document that
+//! LightBurn can open and that [`parse_lbrn2`](super::parse_lbrn2) reads
+//! back into the same model.
+
+use super::types::*;
+use quick_xml::escape::escape;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// FormatVersion written when the project does not carry one
+const DEFAULT_FORMAT_VERSION: &str = "1";
+
+/// Format a number using the shortest representation that parses back to
+/// the same value, treating -0 as 0
+fn num(n: f64) -> String {
+    let n = if n == 0.0 { 0.0 } else { n };
+    format!("{}", n)
+}
+
+/// Encode vertices into VertList syntax, e.g. `V1 2c0x3c0y4V5 6`
+pub fn format_vert_list(verts: &[Vec2]) -> String {
+    let mut out = String::new();
+    for v in verts {
+        let _ = write!(out, "V{} {}", num(v.x), num(v.y));
+        if let Some(c0x) = v.c0x {
+            let _ = write!(out, "c0x{}", num(c0x));
+        }
+        if let Some(c0y) = v.c0y {
+            let _ = write!(out, "c0y{}", num(c0y));
+        }
+        if let Some(c1x) = v.c1x {
+            let _ = write!(out, "c1x{}", num(c1x));
+        }
+        if let Some(c1y) = v.c1y {
+            let _ = write!(out, "c1y{}", num(c1y));
+        }
+    }
+    out
+}
+
+/// Encode primitives into PrimList syntax, e.g. `L0 1B1 2`
+pub fn format_prim_list(prims: &[PathPrimitive]) -> String {
+    let mut out = String::new();
+    for prim in prims {
+        let _ = match prim {
+            PathPrimitive::Line { start_idx, end_idx } => {
+                write!(out, "L{} {}", start_idx, end_idx)
+            }
+            PathPrimitive::Bezier { start_idx, end_idx } => {
+                write!(out, "B{} {}", start_idx, end_idx)
+            }
+        };
+    }
+    out
+}
+
+fn format_xform(xform: &XForm) -> String {
+    format!(
+        "{} {} {} {} {} {}",
+        num(xform.a),
+        num(xform.b),
+        num(xform.c),
+        num(xform.d),
+        num(xform.e),
+        num(xform.f)
+    )
+}
+
+fn write_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+}
+
+fn write_value_param(out: &mut String, depth: usize, key: &str, value: &str) {
+    write_indent(out, depth);
+    let _ = writeln!(out, "<{} Value=\"{}\"/>", key, escape(value));
+}
+
+fn write_cut_setting(out: &mut String, cs: &CutSetting) {
+    fn opt<T: ToString>(out: &mut String, key: &str, value: Option<T>) {
+        if let Some(v) = value {
+            write_value_param(out, 2, key, &v.to_string());
+        }
+    }
+    fn opt_num(out: &mut String, key: &str, value: Option<f64>) {
+        opt(out, key, value.map(num));
+    }
+    fn opt_bool(out: &mut String, key: &str, value: Option<bool>) {
+        opt(out, key, value.map(|b| if b { "1" } else { "0" }));
+    }
+
+    // LightBurn stores image layer settings in a differently named element
+    let element = if cs.cut_type == CutType::Image {
+        "CutSetting_Img"
+    } else {
+        "CutSetting"
+    };
+
+    write_indent(out, 1);
+    let _ = writeln!(
+        out,
+        "<{} type=\"{}\">",
+        element,
+        escape(cs.cut_type.as_str())
+    );
+
+    write_value_param(out, 2, "index", &cs.index.to_string());
+    if !cs.name.is_empty() {
+        write_value_param(out, 2, "name", &cs.name);
+    }
+    opt_num(out, "minPower", cs.min_power);
+    opt_num(out, "maxPower", cs.max_power);
+    opt_num(out, "speed", cs.speed);
+    opt_num(out, "kerf", cs.kerf);
+    opt_num(out, "zOffset", cs.z_offset);
+    opt(out, "numPasses", cs.num_passes);
+    opt(out, "priority", cs.priority);
+    opt_bool(out, "tabsEnabled", cs.tabs.enabled);
+    opt_num(out, "tabSize", cs.tabs.size);
+    opt_num(out, "tabSpacing", cs.tabs.spacing);
+    opt(out, "tabCount", cs.tabs.count);
+    opt(out, "tabCountMax", cs.tabs.count_max);
+    opt_bool(out, "skipInnerTabs", cs.tabs.skip_inner);
+    opt_bool(out, "doOutput", cs.do_output);
+    opt_bool(out, "hide", cs.hide);
+
+    for (key, value) in &cs.params {
+        write_value_param(out, 2, key, value);
+    }
+
+    write_indent(out, 1);
+    let _ = writeln!(out, "</{}>", element);
+}
+
+/// The path that last defined each `VertID`/`PrimID` in the output
+///
+/// A path whose lists match the current definition of its IDs is written
+/// with the IDs alone; one that differs redefines them, as LightBurn does.
+#[derive(Default)]
+struct SharedLists<'a> {
+    verts: HashMap<i32, &'a Path>,
+    prims: HashMap<i32, &'a Path>,
+}
+
+impl<'a> SharedLists<'a> {
+    /// ID attributes for `path`, and whether its VertList and PrimList still
+    /// have to be written
+    fn claim(&mut self, path: &'a Path) -> (String, bool, bool) {
+        let mut attrs = String::new();
+        let mut write_verts = true;
+        let mut write_prims = true;
+
+        if let Some(id) = path.vert_id.filter(|_| !path.parsed_verts.is_empty()) {
+            let _ = write!(attrs, " VertID=\"{}\"", id);
+            match self.verts.get(&id) {
+                Some(defined) if defined.parsed_verts == path.parsed_verts => write_verts = false,
+                _ => {
+                    self.verts.insert(id, path);
+                }
+            }
+        }
+
+        let has_prims = path.is_line_closed() || !path.parsed_primitives.is_empty();
+        if let Some(id) = path.prim_id.filter(|_| has_prims) {
+            let _ = write!(attrs, " PrimID=\"{}\"", id);
+            match self.prims.get(&id) {
+                Some(defined)
+                    if defined.is_line_closed() == path.is_line_closed()
+                        && defined.parsed_primitives == path.parsed_primitives =>
+                {
+                    write_prims = false
+                }
+                _ => {
+                    self.prims.insert(id, path);
+                }
+            }
+        }
+
+        (attrs, write_verts, write_prims)
+    }
+}
+
+fn write_shape<'a>(out: &mut String, shape: &'a Shape, depth: usize, shared: &mut SharedLists<'a>) {
+    write_indent(out, depth);
+
+    match shape {
+        Shape::Rect(rect) => {
+            let _ = writeln!(
+                out,
+                "<Shape Type=\"Rect\" CutIndex=\"{}\" W=\"{}\" H=\"{}\" Cr=\"{}\">",
+                rect.cut_index,
+                num(rect.w),
+                num(rect.h),
+                num(rect.cr)
+            );
+            write_xform(out, &rect.xform, depth + 1);
+        }
+        Shape::Ellipse(ellipse) => {
+            let _ = writeln!(
+                out,
+                "<Shape Type=\"Ellipse\" CutIndex=\"{}\" Rx=\"{}\" Ry=\"{}\">",
+                ellipse.cut_index,
+                num(ellipse.rx),
+                num(ellipse.ry)
+            );
+            write_xform(out, &ellipse.xform, depth + 1);
+        }
+        Shape::Path(path) => {
+            let (ids, write_verts, write_prims) = shared.claim(path);
+            let _ = writeln!(
+                out,
+                "<Shape Type=\"Path\" CutIndex=\"{}\"{}>",
+                path.cut_index, ids
+            );
+            write_xform(out, &path.xform, depth + 1);
+            write_path_lists(out, path, depth + 1, write_verts, write_prims);
+        }
+        Shape::Bitmap(bitmap) => {
+            let _ = writeln!(
+                out,
+                "<Shape Type=\"Bitmap\" CutIndex=\"{}\" W=\"{}\" H=\"{}\" Data=\"{}\">",
+                bitmap.cut_index,
+                num(bitmap.w),
+                num(bitmap.h),
+                escape(bitmap.data.as_str())
+            );
+            write_xform(out, &bitmap.xform, depth + 1);
+        }
+        Shape::Text(text) => {
+            let _ = writeln!(
+                out,
+                "<Shape Type=\"Text\" CutIndex=\"{}\" Font=\"{}\" Str=\"{}\" H=\"{}\" LS=\"{}\" LnS=\"{}\" Ah=\"{}\" Av=\"{}\" Weld=\"{}\" HasBackupPath=\"{}\">",
+                text.cut_index,
+                escape(text.font.as_str()),
+                // Keep line breaks, which attribute normalization would turn into spaces
+                escape(text.text.as_str()).replace('\n', "&#10;"),
+                num(text.h),
+                num(text.letter_spacing),
+                num(text.line_spacing),
+                text.align_h,
+                text.align_v,
+                if text.weld { 1 } else { 0 },
+                if text.backup_path.is_some() { 1 } else { 0 }
+            );
+
+            // LightBurn places the backup path with the text's own transform
+            if let Some(bp) = &text.backup_path {
+                let (ids, write_verts, write_prims) = shared.claim(bp);
+                write_indent(out, depth + 1);
+                let _ = writeln!(
+                    out,
+                    "<BackupPath Type=\"Path\" CutIndex=\"{}\"{}>",
+                    text.cut_index, ids
+                );
+                write_xform(out, &text.xform, depth + 2);
+                write_path_lists(out, bp, depth + 2, write_verts, write_prims);
+                write_indent(out, depth + 1);
+                out.push_str("</BackupPath>\n");
+            }
+            write_xform(out, &text.xform, depth + 1);
+        }
+        Shape::Group(group) => {
+            let _ = writeln!(
+                out,
+                "<Shape Type=\"Group\" CutIndex=\"{}\">",
+                group.cut_index
+            );
+            write_xform(out, &group.xform, depth + 1);
+
+            write_indent(out, depth + 1);
+            out.push_str("<Children>\n");
+            for child in &group.children {
+                write_shape(out, child, depth + 2, shared);
+            }
+            write_indent(out, depth + 1);
+            out.push_str("</Children>\n");
+        }
+    }
+
+    write_indent(out, depth);
+    out.push_str("</Shape>\n");
+}
+
+fn write_path_lists(
+    out: &mut String,
+    path: &Path,
+    depth: usize,
+    write_verts: bool,
+    write_prims: bool,
+) {
+    if write_verts {
+        write_indent(out, depth);
+        let _ = writeln!(
+            out,
+            "<VertList>{}</VertList>",
+            format_vert_list(&path.parsed_verts)
+        );
+    }
+    if !write_prims {
+        return;
+    }
+
+    let prim_list = if path.is_line_closed() {
+        "LineClosed".to_string()
+    } else {
+        format_prim_list(&path.parsed_primitives)
+    };
+    if !prim_list.is_empty() {
+        write_indent(out, depth);
+        let _ = writeln!(out, "<PrimList>{}</PrimList>", prim_list);
+    }
+}
+
+fn write_xform(out: &mut String, xform: &XForm, depth: usize) {
+    write_indent(out, depth);
+    let _ = writeln!(out, "<XForm>{}</XForm>", format_xform(xform));
+}
+
+/// Serialize a LightBurnProject to an LBRN2 XML string
+///
+/// An empty `app_version` is omitted; an empty `format_version` is written
+/// as the current format, since LightBurn requires it.
+pub fn write_lbrn2(project: &LightBurnProject) -> String {
+    let format_version = if project.format_version.is_empty() {
+        DEFAULT_FORMAT_VERSION
+    } else {
+        &project.format_version
+    };
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<LightBurnProject");
+    if !project.app_version.is_empty() {
+        let _ = write!(
+            out,
+            " AppVersion=\"{}\"",
+            escape(project.app_version.as_str())
+        );
+    }
+    if let Some(device) = &project.device_name {
+        let _ = write!(out, " DeviceName=\"{}\"", escape(device.as_str()));
+    }
+    let _ = write!(out, " FormatVersion=\"{}\"", escape(format_version));
+    if let Some(height) = project.material_height {
+        let _ = write!(out, " MaterialHeight=\"{}\"", num(height));
+    }
+    let _ = writeln!(
+        out,
+        " MirrorX=\"{}\" MirrorY=\"{}\">",
+        if project.mirror_x { "True" } else { "False" },
+        if project.mirror_y { "True" } else { "False" }
+    );
+
+    if let Some(thumbnail) = &project.thumbnail {
+        write_indent(&mut out, 1);
+        let _ = writeln!(
+            out,
+            "<Thumbnail Source=\"{}\"/>",
+            escape(thumbnail.as_str())
+        );
+    }
+
+    if !project.ui_prefs.is_empty() {
+        write_indent(&mut out, 1);
+        out.push_str("<UIPrefs>\n");
+        for (key, value) in &project.ui_prefs {
+            write_value_param(&mut out, 2, key, value);
+        }
+        write_indent(&mut out, 1);
+        out.push_str("</UIPrefs>\n");
+    }
+
+    for cs in &project.cut_settings {
+        write_cut_setting(&mut out, cs);
+    }
+
+    let mut shared = SharedLists::default();
+    for shape in &project.shapes {
+        write_shape(&mut out, shape, 1, &mut shared);
+    }
+
+    if let Some(notes) = &project.notes {
+        write_indent(&mut out, 1);
+        let _ = writeln!(
+            out,
+            "<Notes ShowOnLoad=\"{}\" Notes=\"{}\"/>",
+            if notes.show_on_load { 1 } else { 0 },
+            escape(notes.text.as_str()).replace('\n', "&#10;")
+        );
+    }
+
+    out.push_str("</LightBurnProject>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lbrn2::parser::{parse_prim_list, parse_vert_list};
+
+    #[test]
+    fn test_format_vert_list_round_trip() {
+        let verts = vec![
+            Vec2::new(49.0, -0.5),
+            Vec2::with_control_points(0.1, 1e-7, Some(1.0), None, Some(-2.25), Some(3.0)),
+        ];
+        let encoded = format_vert_list(&verts);
+        assert_eq!(encoded, "V49 -0.5V0.1 0.0000001c0x1c1x-2.25c1y3");
+        assert_eq!(parse_vert_list(&encoded), verts);
+    }
+
+    #[test]
+    fn test_format_prim_list_round_trip() {
+        let prims = vec![
+            PathPrimitive::Line {
+                start_idx: 0,
+                end_idx: 1,
+            },
+            PathPrimitive::Bezier {
+                start_idx: 1,
+                end_idx: 0,
+            },
+        ];
+        let encoded = format_prim_list(&prims);
+        assert_eq!(encoded, "L0 1B1 0");
+        assert_eq!(parse_prim_list(&encoded), prims);
+    }
+
+    #[test]
+    fn test_num_drops_negative_zero() {
+        assert_eq!(num(-0.0), "0");
+        assert_eq!(num(55.647533), "55.647533");
+    }
+}
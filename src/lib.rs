@@ -5,6 +5,19 @@
 //! ## Features
 //!
 //! - **LBRN2 to SVG**: Convert LightBurn LBRN2 project files to SVG format
+//! - **SVG to LBRN2**: Import SVG artwork into a LightBurn project, one layer per color
+//! - **LBRN2 to DXF**: Export projects as DXF with one layer per cut setting
+//! - **DXF to LBRN2**: Import DXF drawings, one cut setting per DXF layer
+//! - **LBRN2 to G-code**: Generate GRBL G-code from a LightBurn project
+//! - **Kerf compensation**: Offset closed contours so parts cut to their drawn size
+//! - **Boolean operations**: Unite, subtract, intersect or xor closed shapes and weld layers
+//! - **Path joining**: Chain open fragments whose ends meet into continuous contours
+//! - **Duplicate removal**: Drop repeated shapes and trim overlapping collinear lines so each edge is cut once
+//! - **Holding tabs**: Leave uncut bridges in closed contours, per layer from the cut settings or evenly spaced
+//! - **Cut order optimization**: Reorder shapes to cut holes first and shorten travel
+//! - **Job statistics**: Cut length, area, bounds and run time per layer for quoting
+//! - **Streaming reader**: Read large LBRN2 files shape by shape with `Lbrn2Reader`
+//! - **LBRN2 writing**: Serialize a `LightBurnProject` back to an LBRN2 file
 //! - **Image Vectorization**: Convert raster images to SVG with separate cut/engrave layers
 //!
 //! ## Example - LBRN2 Conversion
@@ -32,5 +45,5 @@ pub mod lbrn2;
 pub mod vectorize;
 
 // Re-export commonly used items
-pub use lbrn2::{LightBurnProject, lbrn2_to_svg, parse_lbrn2};
+pub use lbrn2::{LightBurnProject, lbrn2_to_svg, parse_lbrn2, svg_to_lbrn2, write_lbrn2};
 pub use vectorize::{VectorizeOptions, VectorizeResult, vectorize_image, vectorize_image_file};
@@ -1,7 +1,20 @@
-use clap::{Parser, Subcommand};
-use laser_tools::lbrn2::{lbrn2_to_svg, parse_lbrn2};
+use clap::{Args, Parser, Subcommand};
+use laser_tools::lbrn2::svg_import::normalize_color;
+use laser_tools::lbrn2::{
+    DxfExportOptions, DxfImportOptions, GcodeOptions, JoinStyle, LaserMode, LightBurnProject,
+    LoopDirection, OffsetOptions, OrderOptions, Removal, StatsOptions, SvgExportOptions,
+    SvgImportOptions, SvgUnits, TabOptions, add_layer_tabs, add_tabs, close_paths,
+    compensate_kerf_with_warnings, dxf_to_lbrn2_with_warnings, join_paths,
+    lbrn2_to_dxf_with_options, lbrn2_to_gcode_with_warnings, lbrn2_to_svg,
+    lbrn2_to_svg_with_assets, optimize_cut_order, parse_lbrn2_with_warnings,
+    project_stats_with_options, remove_duplicates_with_report, svg_to_lbrn2_with_warnings,
+    weld_layers_with_warnings, write_lbrn2,
+};
 use laser_tools::vectorize::{VectorizeOptions, vectorize_image_file};
+use std::fmt::Display;
 use std::fs;
+use std::io;
+use std::path::Path;
 use std::process;
 
 #[derive(Parser)]
@@ -12,6 +25,35 @@ struct Cli {
     command: Commands,
 }
 
+/// Kerf compensation shared by the conversion commands
+#[derive(Args)]
+struct KerfArgs {
+    /// Offset closed contours by half this kerf width in mm, growing parts
+    /// and shrinking holes
+    #[arg(long, value_name = "MM")]
+    kerf: Option<f64>,
+    /// Corner style for --kerf: round, miter or bevel (default: round)
+    #[arg(long, default_value = "round", value_parser = ["round", "miter", "bevel"])]
+    join: String,
+}
+
+/// Cut order optimization shared by the conversion commands
+#[derive(Args)]
+struct OrderArgs {
+    /// Reorder shapes on each layer to shorten travel, cutting inner
+    /// contours before the contours around them
+    #[arg(long)]
+    optimize: bool,
+    /// With --optimize, start closed contours at the vertex nearest the
+    /// previous cut
+    #[arg(long)]
+    choose_starts: bool,
+    /// With --optimize, direction of closed contours: keep, cw or ccw
+    /// (default: keep)
+    #[arg(long, default_value = "keep", value_parser = ["keep", "cw", "ccw"])]
+    direction: String,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Convert LightBurn LBRN2 files to SVG
@@ -20,6 +62,212 @@ enum Commands {
         input: String,
         /// Output SVG file path
         output: String,
+        /// Group shapes into one Inkscape layer per cut setting
+        #[arg(long)]
+        layers: bool,
+        /// Digits after the decimal point (default: 6)
+        #[arg(long, default_value = "6")]
+        precision: usize,
+        /// Drop trailing zeros from numbers
+        #[arg(long)]
+        trim_zeros: bool,
+        /// Document units: mm, in or px (default: mm)
+        #[arg(long, default_value = "mm", value_parser = ["mm", "in", "px"])]
+        units: String,
+        /// Resolution used with --units px (default: 96)
+        #[arg(long, default_value = "96")]
+        dpi: f64,
+        /// Space around the content in mm (default: 0)
+        #[arg(long, default_value = "0")]
+        margin: f64,
+        /// Keep the bed origin in view instead of cropping to the content
+        #[arg(long)]
+        bed_origin: bool,
+        /// Write absolute coordinates instead of transform matrices
+        #[arg(long)]
+        bake: bool,
+        /// Keep LightBurn's Y-up coordinates instead of flipping for SVG
+        #[arg(long)]
+        no_flip_y: bool,
+        /// Re-encode bitmaps browsers cannot display (BMP) as PNG
+        #[arg(long)]
+        reencode_bitmaps: bool,
+        /// Write bitmaps as files in this directory (relative to the output)
+        /// instead of inlining them
+        #[arg(long, value_name = "DIR")]
+        bitmap_dir: Option<String>,
+        /// Write paths that share a VertID/PrimID once and reference them
+        /// with <use>
+        #[arg(long)]
+        reuse_paths: bool,
+        #[command(flatten)]
+        kerf: KerfArgs,
+        #[command(flatten)]
+        order: OrderArgs,
+    },
+    /// Convert SVG files to LightBurn LBRN2
+    #[command(name = "svg")]
+    Svg {
+        /// Input SVG file path
+        input: String,
+        /// Output LBRN2 file path
+        output: String,
+        /// Assign a color to a cut index, e.g. --layer "#FF0000=2" (repeatable)
+        #[arg(short, long = "layer", value_name = "COLOR=INDEX", value_parser = parse_layer_mapping)]
+        layers: Vec<(String, i32)>,
+        /// Resolution for px and unitless lengths (default: 96)
+        #[arg(long, default_value = "96")]
+        dpi: f32,
+        #[command(flatten)]
+        kerf: KerfArgs,
+        #[command(flatten)]
+        order: OrderArgs,
+    },
+    /// Convert LightBurn LBRN2 files to DXF, or DXF files to LBRN2/SVG
+    ///
+    /// The direction follows the input extension; a DXF input is written as
+    /// SVG when the output ends in .svg and as LBRN2 otherwise.
+    #[command(name = "dxf")]
+    Dxf {
+        /// Input LBRN2 or DXF file path
+        input: String,
+        /// Output DXF, LBRN2 or SVG file path
+        output: String,
+        /// Write curves as polylines instead of splines
+        #[arg(long)]
+        flatten: bool,
+        /// Maximum curve flattening error in mm (default: 0.01)
+        #[arg(long, default_value = "0.01")]
+        tolerance: f64,
+        #[command(flatten)]
+        kerf: KerfArgs,
+        #[command(flatten)]
+        order: OrderArgs,
+    },
+    /// Generate GRBL G-code from LightBurn LBRN2 files
+    #[command(name = "gcode")]
+    Gcode {
+        /// Input LBRN2 file path
+        input: String,
+        /// Output G-code file path
+        output: String,
+        /// S value for 100% power, GRBL's $30 (default: 1000)
+        #[arg(long, default_value = "1000")]
+        s_max: f64,
+        /// Maximum curve flattening error in mm (default: 0.01)
+        #[arg(long, default_value = "0.01")]
+        tolerance: f64,
+        /// Laser mode for layers without constant power: m3 or m4 (default: m4)
+        #[arg(long, default_value = "m4", value_parser = ["m3", "m4"])]
+        mode: String,
+        /// File whose contents replace the default header
+        #[arg(long, value_name = "FILE")]
+        header: Option<String>,
+        /// File whose contents replace the default footer
+        #[arg(long, value_name = "FILE")]
+        footer: Option<String>,
+        #[command(flatten)]
+        kerf: KerfArgs,
+        #[command(flatten)]
+        order: OrderArgs,
+    },
+    /// Weld the closed shapes on each layer of an LBRN2 file into one outline
+    ///
+    /// The output is written as SVG when it ends in .svg and as LBRN2
+    /// otherwise.
+    #[command(name = "weld")]
+    Weld {
+        /// Input LBRN2 file path
+        input: String,
+        /// Output LBRN2 or SVG file path
+        output: String,
+        /// Maximum curve flattening error in mm (default: 0.01)
+        #[arg(long, default_value = "0.01")]
+        tolerance: f64,
+    },
+    /// Join open paths in an LBRN2 file whose ends meet into continuous
+    /// contours
+    ///
+    /// The output is written as SVG when it ends in .svg and as LBRN2
+    /// otherwise.
+    #[command(name = "join")]
+    Join {
+        /// Input LBRN2 file path
+        input: String,
+        /// Output LBRN2 or SVG file path
+        output: String,
+        /// Largest gap between endpoints that still joins, in mm (default: 0.01)
+        #[arg(long, default_value = "0.01")]
+        tolerance: f64,
+        /// Only close paths whose own ends nearly meet
+        #[arg(long)]
+        close_only: bool,
+    },
+    /// Remove duplicate shapes and overlapping lines so each edge is cut once
+    ///
+    /// The output is written as SVG when it ends in .svg and as LBRN2
+    /// otherwise.
+    #[command(name = "dedupe")]
+    Dedupe {
+        /// Input LBRN2 file path
+        input: String,
+        /// Output LBRN2 or SVG file path
+        output: String,
+        /// Largest distance between lines that still counts as overlapping, in mm (default: 0.01)
+        #[arg(long, default_value = "0.01")]
+        tolerance: f64,
+        /// List every shape and overlap removed
+        #[arg(long)]
+        report: bool,
+    },
+    /// Add holding tabs to the closed contours of an LBRN2 file
+    ///
+    /// Tabs are evenly spaced unless --at gives their positions. With
+    /// --from-layers only layers whose cut setting enables tabs get them,
+    /// with the cut setting's size, count and spacing. The output is written
+    /// as SVG when it ends in .svg and as LBRN2 otherwise.
+    #[command(name = "tabs")]
+    Tabs {
+        /// Input LBRN2 file path
+        input: String,
+        /// Output LBRN2 or SVG file path
+        output: String,
+        /// Width of each gap in mm (default: 0.5)
+        #[arg(long, default_value = "0.5")]
+        size: f64,
+        /// Tabs per contour, or the least with --spacing (default: 4)
+        #[arg(long, default_value = "4")]
+        count: u32,
+        /// Distance between tabs in mm, adding tabs to longer contours
+        #[arg(long, value_name = "MM")]
+        spacing: Option<f64>,
+        /// Most tabs per contour with --spacing
+        #[arg(long, value_name = "N")]
+        max_count: Option<u32>,
+        /// Tab centers as fractions of each contour's length, e.g. --at 0.25,0.75
+        #[arg(long, value_name = "FRACTION", value_delimiter = ',')]
+        at: Vec<f64>,
+        /// Leave contours smaller than this in both directions without tabs, in mm
+        #[arg(long, value_name = "MM", default_value = "0")]
+        min_size: f64,
+        /// Only put tabs on outer contours, not on holes
+        #[arg(long)]
+        outer_only: bool,
+        /// Only put tabs on layers whose cut setting enables them
+        #[arg(long)]
+        from_layers: bool,
+    },
+    /// Report cut length, area, bounds and estimated time per layer
+    #[command(name = "stats")]
+    Stats {
+        /// Input LBRN2 file path
+        input: String,
+        /// Print JSON instead of a human readable summary
+        #[arg(long)]
+        json: bool,
+        /// Speed of travel moves in mm/s (default: 100)
+        #[arg(long, default_value = "100")]
+        travel_speed: f64,
     },
     /// Convert raster images to SVG with cut/engrave layers
     #[command(name = "image")]
@@ -44,8 +292,154 @@ fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Lbrn2 { input, output } => {
-            run_lbrn2_conversion(&input, &output);
+        Commands::Lbrn2 {
+            input,
+            output,
+            layers,
+            precision,
+            trim_zeros,
+            units,
+            dpi,
+            margin,
+            bed_origin,
+            bake,
+            no_flip_y,
+            reencode_bitmaps,
+            bitmap_dir,
+            reuse_paths,
+            kerf,
+            order,
+        } => {
+            let options = SvgExportOptions {
+                precision,
+                trim_zeros,
+                units: match units.as_str() {
+                    "in" => SvgUnits::Inches,
+                    "px" => SvgUnits::Pixels { dpi },
+                    _ => SvgUnits::Millimeters,
+                },
+                margin,
+                keep_bed_origin: bed_origin,
+                bake_transforms: bake,
+                flip_y: !no_flip_y,
+                group_by_layer: layers,
+                reencode_bitmaps,
+                bitmap_dir,
+                reuse_shared_paths: reuse_paths,
+                ..Default::default()
+            };
+            run_lbrn2_conversion(&input, &output, &options, &kerf, &order);
+        }
+        Commands::Svg {
+            input,
+            output,
+            layers,
+            dpi,
+            kerf,
+            order,
+        } => {
+            run_svg_import(&input, &output, layers, dpi, &kerf, &order);
+        }
+        Commands::Dxf {
+            input,
+            output,
+            flatten,
+            tolerance,
+            kerf,
+            order,
+        } => {
+            if has_extension(&input, "dxf") {
+                run_dxf_import(&input, &output, &kerf, &order);
+            } else {
+                let options = DxfExportOptions {
+                    splines: !flatten,
+                    tolerance,
+                };
+                run_dxf_export(&input, &output, &options, &kerf, &order);
+            }
+        }
+        Commands::Gcode {
+            input,
+            output,
+            s_max,
+            tolerance,
+            mode,
+            header,
+            footer,
+            kerf,
+            order,
+        } => {
+            let mut options = GcodeOptions {
+                s_max,
+                tolerance,
+                laser_mode: if mode == "m3" {
+                    LaserMode::Constant
+                } else {
+                    LaserMode::Dynamic
+                },
+                ..Default::default()
+            };
+            if let Some(path) = header {
+                options.header = read_template(&path);
+            }
+            if let Some(path) = footer {
+                options.footer = read_template(&path);
+            }
+            run_gcode_generation(&input, &output, &options, &kerf, &order);
+        }
+        Commands::Weld {
+            input,
+            output,
+            tolerance,
+        } => {
+            run_weld(&input, &output, tolerance);
+        }
+        Commands::Join {
+            input,
+            output,
+            tolerance,
+            close_only,
+        } => {
+            run_join(&input, &output, tolerance, close_only);
+        }
+        Commands::Dedupe {
+            input,
+            output,
+            tolerance,
+            report,
+        } => {
+            run_dedupe(&input, &output, tolerance, report);
+        }
+        Commands::Tabs {
+            input,
+            output,
+            size,
+            count,
+            spacing,
+            max_count,
+            at,
+            min_size,
+            outer_only,
+            from_layers,
+        } => {
+            let options = TabOptions {
+                size,
+                count,
+                spacing,
+                count_max: max_count,
+                positions: (!at.is_empty()).then_some(at),
+                min_size,
+                outer_only,
+                ..Default::default()
+            };
+            run_tabs(&input, &output, &options, from_layers);
+        }
+        Commands::Stats {
+            input,
+            json,
+            travel_speed,
+        } => {
+            run_stats(&input, json, &StatsOptions { travel_speed });
         }
         Commands::Image {
             input,
@@ -59,26 +453,227 @@ fn main() {
     }
 }
 
-fn run_lbrn2_conversion(input_path: &str, output_path: &str) {
-    let lbrn2_content = match fs::read_to_string(input_path) {
+fn run_lbrn2_conversion(
+    input_path: &str,
+    output_path: &str,
+    options: &SvgExportOptions,
+    kerf: &KerfArgs,
+    order: &OrderArgs,
+) {
+    let project = load_project(input_path);
+    let project = apply_kerf(project, kerf, input_path);
+    let project = apply_order(project, order);
+
+    let (svg, assets) = lbrn2_to_svg_with_assets(&project, options);
+
+    let output_dir = Path::new(output_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    for asset in &assets {
+        let asset_path = output_dir.join(&asset.path);
+        let written = asset_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&asset_path, &asset.data));
+        if let Err(e) = written {
+            eprintln!(
+                "Error writing bitmap file '{}': {}",
+                asset_path.display(),
+                e
+            );
+            process::exit(4);
+        }
+    }
+
+    match fs::write(output_path, &svg) {
+        Ok(_) => {
+            println!(
+                "Successfully converted '{}' to '{}'",
+                input_path, output_path
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
+fn run_dxf_export(
+    input_path: &str,
+    output_path: &str,
+    options: &DxfExportOptions,
+    kerf: &KerfArgs,
+    order: &OrderArgs,
+) {
+    let project = load_project(input_path);
+    let project = apply_kerf(project, kerf, input_path);
+    let project = apply_order(project, order);
+
+    let (dxf, warnings) = lbrn2_to_dxf_with_options(&project, options);
+    print_warnings(input_path, &warnings);
+
+    match fs::write(output_path, &dxf) {
+        Ok(_) => {
+            println!(
+                "Successfully converted '{}' to '{}'",
+                input_path, output_path
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
+/// Apply `--kerf` when given, warning about shapes left as they are
+fn apply_kerf(project: LightBurnProject, args: &KerfArgs, input_path: &str) -> LightBurnProject {
+    let Some(kerf) = args.kerf else {
+        return project;
+    };
+    let options = OffsetOptions {
+        join: match args.join.as_str() {
+            "miter" => JoinStyle::Miter,
+            "bevel" => JoinStyle::Bevel,
+            _ => JoinStyle::Round,
+        },
+        ..Default::default()
+    };
+    let (project, warnings) = compensate_kerf_with_warnings(&project, kerf, &options);
+    print_warnings(input_path, &warnings);
+    project
+}
+
+/// Apply `--optimize` when given, reporting the travel saved
+fn apply_order(project: LightBurnProject, args: &OrderArgs) -> LightBurnProject {
+    if !args.optimize {
+        return project;
+    }
+    let options = OrderOptions {
+        choose_starts: args.choose_starts,
+        loop_direction: match args.direction.as_str() {
+            "cw" => LoopDirection::Clockwise,
+            "ccw" => LoopDirection::CounterClockwise,
+            _ => LoopDirection::Keep,
+        },
+        ..Default::default()
+    };
+    let (project, report) = optimize_cut_order(&project, &options);
+    println!(
+        "Optimized cut order: travel {:.1} mm -> {:.1} mm",
+        report.travel_before, report.travel_after
+    );
+    project
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// Read an input file, exiting with status 2 when it cannot be read
+fn read_input(input_path: &str) -> String {
+    match fs::read_to_string(input_path) {
         Ok(content) => content,
         Err(e) => {
             eprintln!("Error reading input file '{}': {}", input_path, e);
             process::exit(2);
         }
-    };
+    }
+}
 
-    let project = match parse_lbrn2(&lbrn2_content) {
-        Ok(p) => p,
+fn print_warnings(input_path: &str, warnings: &[impl Display]) {
+    for w in warnings {
+        eprintln!("Warning: {}: {}", input_path, w);
+    }
+}
+
+/// Read and parse an LBRN2 file, printing its warnings; exits with status 3
+/// when it cannot be parsed
+fn load_project(input_path: &str) -> LightBurnProject {
+    match parse_lbrn2_with_warnings(&read_input(input_path)) {
+        Ok((project, warnings)) => {
+            print_warnings(input_path, &warnings);
+            project
+        }
         Err(e) => {
             eprintln!("Error parsing LBRN2 file: {}", e);
             process::exit(3);
         }
+    }
+}
+
+/// Write a project as SVG when `output_path` ends in `.svg`, as LBRN2
+/// otherwise
+fn write_project(project: &LightBurnProject, output_path: &str) -> io::Result<()> {
+    let output = if has_extension(output_path, "svg") {
+        lbrn2_to_svg(project)
+    } else {
+        write_lbrn2(project)
     };
+    fs::write(output_path, output)
+}
 
-    let svg = lbrn2_to_svg(&project);
+fn run_dxf_import(input_path: &str, output_path: &str, kerf: &KerfArgs, order: &OrderArgs) {
+    let dxf_content = read_input(input_path);
 
-    match fs::write(output_path, &svg) {
+    let project = match dxf_to_lbrn2_with_warnings(&dxf_content, &DxfImportOptions::default()) {
+        Ok((p, warnings)) => {
+            print_warnings(input_path, &warnings);
+            p
+        }
+        Err(e) => {
+            eprintln!("Error converting DXF file: {}", e);
+            process::exit(3);
+        }
+    };
+    let project = apply_kerf(project, kerf, input_path);
+    let project = apply_order(project, order);
+
+    match write_project(&project, output_path) {
+        Ok(_) => {
+            println!(
+                "Successfully converted '{}' to '{}' ({} shapes, {} layers)",
+                input_path,
+                output_path,
+                project.shapes.len(),
+                project.cut_settings.len()
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
+fn read_template(path: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Error reading template file '{}': {}", path, e);
+            process::exit(2);
+        }
+    }
+}
+
+fn run_gcode_generation(
+    input_path: &str,
+    output_path: &str,
+    options: &GcodeOptions,
+    kerf: &KerfArgs,
+    order: &OrderArgs,
+) {
+    let project = load_project(input_path);
+    let project = apply_kerf(project, kerf, input_path);
+    let project = apply_order(project, order);
+
+    let (gcode, warnings) = lbrn2_to_gcode_with_warnings(&project, options);
+    print_warnings(input_path, &warnings);
+
+    match fs::write(output_path, &gcode) {
         Ok(_) => {
             println!(
                 "Successfully converted '{}' to '{}'",
@@ -92,6 +687,182 @@ fn run_lbrn2_conversion(input_path: &str, output_path: &str) {
     }
 }
 
+fn run_weld(input_path: &str, output_path: &str, tolerance: f64) {
+    let project = load_project(input_path);
+
+    let (project, warnings) = weld_layers_with_warnings(&project, tolerance);
+    print_warnings(input_path, &warnings);
+
+    match write_project(&project, output_path) {
+        Ok(_) => {
+            println!(
+                "Successfully welded '{}' to '{}' ({} shapes)",
+                input_path,
+                output_path,
+                project.shapes.len()
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
+fn run_join(input_path: &str, output_path: &str, tolerance: f64, close_only: bool) {
+    let project = load_project(input_path);
+
+    let (project, report, warnings) = if close_only {
+        close_paths(&project, tolerance)
+    } else {
+        join_paths(&project, tolerance)
+    };
+    print_warnings(input_path, &warnings);
+
+    match write_project(&project, output_path) {
+        Ok(_) => {
+            println!(
+                "Successfully joined '{}' to '{}' ({} open contours -> {}, {} closed)",
+                input_path, output_path, report.open_before, report.open_after, report.closed
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
+fn run_dedupe(input_path: &str, output_path: &str, tolerance: f64, report: bool) {
+    let project = load_project(input_path);
+
+    let (project, removals, warnings) = remove_duplicates_with_report(&project, tolerance);
+    print_warnings(input_path, &warnings);
+
+    match write_project(&project, output_path) {
+        Ok(_) => {
+            if report {
+                for removal in &removals {
+                    println!("{}", removal);
+                }
+            }
+            let shapes = removals
+                .iter()
+                .filter(|r| matches!(r, Removal::Shape { .. }))
+                .count();
+            let length = removals.iter().fold(0.0, |sum, r| match r {
+                Removal::Overlap { length, .. } => sum + length,
+                Removal::Shape { .. } => sum,
+            });
+            println!(
+                "Successfully deduplicated '{}' to '{}' ({} duplicate shapes, {} overlaps, {:.2} mm of overlapping lines removed)",
+                input_path,
+                output_path,
+                shapes,
+                removals.len() - shapes,
+                length
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
+fn run_tabs(input_path: &str, output_path: &str, options: &TabOptions, from_layers: bool) {
+    let project = load_project(input_path);
+
+    let (project, report, warnings) = if from_layers {
+        add_layer_tabs(&project, options)
+    } else {
+        add_tabs(&project, options)
+    };
+    print_warnings(input_path, &warnings);
+
+    match write_project(&project, output_path) {
+        Ok(_) => {
+            println!(
+                "Successfully added tabs to '{}' in '{}' ({} tabs on {} contours, {} contours skipped)",
+                input_path, output_path, report.tabs, report.contours, report.skipped
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
+fn run_stats(input_path: &str, json: bool, options: &StatsOptions) {
+    let project = load_project(input_path);
+
+    let stats = project_stats_with_options(&project, options);
+    if json {
+        print!("{}", stats.to_json());
+    } else {
+        print!("{}", stats);
+    }
+}
+
+fn parse_layer_mapping(s: &str) -> Result<(String, i32), String> {
+    let (color, index) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected COLOR=INDEX, got '{}'", s))?;
+    let color = normalize_color(color).ok_or_else(|| format!("invalid color '{}'", color))?;
+    let index = index
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid cut index '{}'", index))?;
+    Ok((color, index))
+}
+
+fn run_svg_import(
+    input_path: &str,
+    output_path: &str,
+    layers: Vec<(String, i32)>,
+    dpi: f32,
+    kerf: &KerfArgs,
+    order: &OrderArgs,
+) {
+    let svg_content = read_input(input_path);
+
+    let options = SvgImportOptions {
+        dpi,
+        color_layers: layers.into_iter().collect(),
+        ..Default::default()
+    };
+
+    let project = match svg_to_lbrn2_with_warnings(&svg_content, &options) {
+        Ok((p, warnings)) => {
+            print_warnings(input_path, &warnings);
+            p
+        }
+        Err(e) => {
+            eprintln!("Error converting SVG file: {}", e);
+            process::exit(3);
+        }
+    };
+    let project = apply_kerf(project, kerf, input_path);
+    let project = apply_order(project, order);
+
+    match fs::write(output_path, write_lbrn2(&project)) {
+        Ok(_) => {
+            println!(
+                "Successfully converted '{}' to '{}' ({} shapes, {} layers)",
+                input_path,
+                output_path,
+                project.shapes.len(),
+                project.cut_settings.len()
+            );
+        }
+        Err(e) => {
+            eprintln!("Error writing output file '{}': {}", output_path, e);
+            process::exit(4);
+        }
+    }
+}
+
 fn run_image_vectorization(
     input_path: &str,
     output_path: &str,
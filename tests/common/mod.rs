@@ -0,0 +1,15 @@
+use laser_tools::lbrn2::bounds::{Bounds, get_transformed_bounds};
+use laser_tools::lbrn2::{LightBurnProject, Shape};
+
+/// Combined SVG-space bounds of all shapes other than bitmaps
+pub fn project_bounds(project: &LightBurnProject) -> Option<Bounds> {
+    project
+        .shapes
+        .iter()
+        .filter(|s| !matches!(s, Shape::Bitmap(_)))
+        .filter_map(get_transformed_bounds)
+        .reduce(|mut acc, b| {
+            acc.expand(&b);
+            acc
+        })
+}
@@ -1,4 +1,9 @@
-use laser_tools::lbrn2::{lbrn2_to_svg, parse_lbrn2};
+use laser_tools::lbrn2::bitmap::encode_bitmap_data;
+use laser_tools::lbrn2::{
+    Bitmap, Group, LightBurnProject, Path as LbPath, Rect, Shape, SvgExportOptions,
+    SvgImportOptions, SvgUnits, XForm, lbrn2_to_svg, lbrn2_to_svg_layered,
+    lbrn2_to_svg_with_assets, lbrn2_to_svg_with_options, parse_lbrn2, svg_to_lbrn2,
+};
 use std::fs;
 use std::path::Path;
 
@@ -135,28 +140,33 @@ fn svg_equal(a: &str, b: &str) -> bool {
 }
 
 fn run_conversion_test(name: &str) {
+    run_conversion_test_with(name, name, lbrn2_to_svg);
+}
+
+/// Convert `<name>.lbrn2` with `convert` and compare against `<expected>.svg`
+fn run_conversion_test_with(name: &str, expected: &str, convert: fn(&LightBurnProject) -> String) {
     let artifacts_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/artifacts");
     let lbrn2_path = artifacts_dir.join(format!("{}.lbrn2", name));
-    let expected_svg_path = artifacts_dir.join(format!("{}.svg", name));
+    let expected_svg_path = artifacts_dir.join(format!("{}.svg", expected));
 
     let lbrn2_content =
         fs::read_to_string(&lbrn2_path).unwrap_or_else(|_| panic!("Failed to read {}.lbrn2", name));
     let expected_svg = fs::read_to_string(&expected_svg_path)
-        .unwrap_or_else(|_| panic!("Failed to read {}.svg", name));
+        .unwrap_or_else(|_| panic!("Failed to read {}.svg", expected));
 
     let project =
         parse_lbrn2(&lbrn2_content).unwrap_or_else(|_| panic!("Failed to parse {}.lbrn2", name));
-    let generated_svg = lbrn2_to_svg(&project);
+    let generated_svg = convert(&project);
 
     // Save generated SVG for debugging
     let temp_dir = artifacts_dir.join("temp");
     let _ = fs::create_dir_all(&temp_dir);
-    let _ = fs::write(temp_dir.join(format!("{}.svg", name)), &generated_svg);
+    let _ = fs::write(temp_dir.join(format!("{}.svg", expected)), &generated_svg);
 
     assert!(
         svg_equal(&generated_svg, &expected_svg),
         "SVG mismatch for {}",
-        name
+        expected
     );
 }
 
@@ -224,3 +234,344 @@ fn test_k() {
 fn test_rings() {
     run_conversion_test("rings");
 }
+
+#[test]
+fn test_cut_settings() {
+    run_conversion_test("cut_settings");
+}
+
+#[test]
+fn test_cut_settings_layered() {
+    run_conversion_test_with("cut_settings", "cut_settings_layered", lbrn2_to_svg_layered);
+}
+
+#[test]
+fn test_rings_layered() {
+    run_conversion_test_with("rings", "rings_layered", lbrn2_to_svg_layered);
+}
+
+#[test]
+fn test_group_matrix_is_not_flipped_twice() {
+    // A group moved 10 right and 20 up holding two 4 x 2 rects, one at its
+    // origin and one 6 to the right
+    let rect = |e: f64| {
+        Shape::Rect(Rect {
+            cut_index: 0,
+            xform: XForm {
+                e,
+                ..XForm::identity()
+            },
+            w: 4.0,
+            h: 2.0,
+            cr: 0.0,
+        })
+    };
+    let project = LightBurnProject {
+        shapes: vec![Shape::Group(Group {
+            cut_index: 0,
+            xform: XForm {
+                e: 10.0,
+                f: 20.0,
+                ..XForm::identity()
+            },
+            children: vec![rect(0.0), rect(6.0)],
+        })],
+        ..Default::default()
+    };
+
+    // The children flip Y themselves, so the group only moves them, upward
+    // in SVG
+    let svg = lbrn2_to_svg(&project);
+    assert!(
+        svg.contains(
+            "<g transform=\"matrix(1.000000 0.000000 0.000000 1.000000 10.000000 -20.000000)\">"
+        ),
+        "{}",
+        svg
+    );
+
+    // Importing it back puts the rects where LightBurn draws them
+    let imported = svg_to_lbrn2(&svg, &SvgImportOptions::default()).unwrap();
+    let verts: Vec<_> = imported
+        .shapes
+        .iter()
+        .flat_map(|shape| match shape {
+            Shape::Path(path) => path.parsed_verts.to_vec(),
+            _ => panic!("expected paths"),
+        })
+        .collect();
+    let min_x = verts.iter().map(|v| v.x).fold(f64::MAX, f64::min);
+    let max_x = verts.iter().map(|v| v.x).fold(f64::MIN, f64::max);
+    let min_y = verts.iter().map(|v| v.y).fold(f64::MAX, f64::min);
+    let max_y = verts.iter().map(|v| v.y).fold(f64::MIN, f64::max);
+    let bounds = [min_x, max_x, min_y, max_y];
+    for (got, want) in bounds.into_iter().zip([8.0, 18.0, 19.0, 21.0]) {
+        assert!((got - want).abs() < 1e-4, "{:?}", bounds);
+    }
+}
+
+#[test]
+fn test_text_without_backup_path_becomes_text_element() {
+    let mut project = load_artifact("word");
+    let Shape::Text(text) = &mut project.shapes[0] else {
+        panic!("Expected Text, got {:?}", project.shapes[0]);
+    };
+    assert_eq!(text.text, "jlucaso");
+    assert_eq!(text.font_family(), "Sans Serif");
+    assert!(text.backup_path.is_some());
+    text.backup_path = None;
+    text.text = "a<b".to_string();
+
+    let svg = lbrn2_to_svg(&project);
+    assert!(
+        svg.contains(r#"<text x="0" y="0" font-family="Sans Serif" font-size="10.000000" text-anchor="middle" dominant-baseline="middle" style="fill:#000000;stroke:none" transform="matrix(1.000000 0.000000 0.000000 1.000000 55.647533 -59.200882)">a&lt;b</text>"#),
+        "{}",
+        svg
+    );
+    let vb = view_box(&svg);
+    assert!(vb[2] > 0.0 && vb[3] > 0.0, "{:?}", vb);
+}
+
+fn load_artifact(name: &str) -> LightBurnProject {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/artifacts")
+        .join(format!("{}.lbrn2", name));
+    parse_lbrn2(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// Read the `viewBox` numbers out of generated SVG
+fn view_box(svg: &str) -> Vec<f64> {
+    let start = svg.find("viewBox=\"").unwrap() + "viewBox=\"".len();
+    let end = start + svg[start..].find('"').unwrap();
+    svg[start..end]
+        .split_whitespace()
+        .map(|n| n.parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_default_options_match_lbrn2_to_svg() {
+    for name in ["cut_settings", "rings", "image", "group_empty"] {
+        let project = load_artifact(name);
+        assert_eq!(
+            lbrn2_to_svg_with_options(&project, &SvgExportOptions::default()),
+            lbrn2_to_svg(&project),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_units_precision_and_margin() {
+    let project = load_artifact("cut_settings");
+    let options = SvgExportOptions {
+        precision: 2,
+        trim_zeros: true,
+        units: SvgUnits::Inches,
+        margin: 2.5,
+        ..Default::default()
+    };
+    let svg = lbrn2_to_svg_with_options(&project, &options);
+
+    // Content spans 35 x 10 mm; the margin adds 2.5 mm on every side
+    assert_eq!(view_box(&svg), vec![7.5, -27.5, 40.0, 15.0]);
+    assert!(
+        svg.contains("width=\"1.57in\" height=\"0.59in\""),
+        "{}",
+        svg
+    );
+    assert!(svg.contains("<rect x=\"-10\" y=\"-5\" width=\"20\" height=\"10\""));
+
+    let px = SvgExportOptions {
+        units: SvgUnits::Pixels { dpi: 96.0 },
+        ..Default::default()
+    };
+    let svg = lbrn2_to_svg_with_options(&project, &px);
+    assert!(svg.contains("width=\"132.283465\""), "{}", svg);
+}
+
+#[test]
+fn test_keep_bed_origin() {
+    let project = load_artifact("cut_settings");
+    let options = SvgExportOptions {
+        keep_bed_origin: true,
+        ..Default::default()
+    };
+    let svg = lbrn2_to_svg_with_options(&project, &options);
+    assert_eq!(view_box(&svg), vec![0.0, -25.0, 45.0, 25.0]);
+
+    let unflipped = SvgExportOptions {
+        keep_bed_origin: true,
+        flip_y: false,
+        ..Default::default()
+    };
+    let svg = lbrn2_to_svg_with_options(&project, &unflipped);
+    assert_eq!(view_box(&svg), vec![0.0, 0.0, 45.0, 25.0]);
+    assert!(svg.contains("matrix(1.000000 0.000000 0.000000 1.000000 20.000000 20.000000)"));
+}
+
+#[test]
+fn test_baked_transforms_keep_geometry() {
+    let options = SvgExportOptions {
+        bake_transforms: true,
+        ..Default::default()
+    };
+
+    for name in ["cut_settings", "rings", "ellipse_stretched", "crucifix"] {
+        let project = load_artifact(name);
+        let baked = lbrn2_to_svg_with_options(&project, &options);
+        assert!(!baked.contains("transform="), "{}: {}", name, baked);
+        assert!(!baked.contains("<rect") && !baked.contains("<ellipse"));
+
+        // Rendering the baked and matrix versions gives the same geometry
+        let from_baked = svg_to_lbrn2(&baked, &SvgImportOptions::default()).unwrap();
+        let from_matrix =
+            svg_to_lbrn2(&lbrn2_to_svg(&project), &SvgImportOptions::default()).unwrap();
+        assert_eq!(
+            from_baked.shapes.len(),
+            from_matrix.shapes.len(),
+            "{}",
+            name
+        );
+        for (a, b) in from_baked.shapes.iter().zip(&from_matrix.shapes) {
+            let (Shape::Path(a), Shape::Path(b)) = (a, b) else {
+                panic!("{}: expected paths", name);
+            };
+            let max = |p: &LbPath| {
+                p.parsed_verts.iter().fold((f64::MIN, f64::MIN), |acc, v| {
+                    (acc.0.max(v.x), acc.1.max(v.y))
+                })
+            };
+            let (ax, ay) = max(a);
+            let (bx, by) = max(b);
+            assert!((ax - bx).abs() < 0.01 && (ay - by).abs() < 0.01, "{}", name);
+        }
+    }
+}
+
+fn bitmap_project(images: &[Vec<u8>]) -> LightBurnProject {
+    LightBurnProject {
+        app_version: String::new(),
+        format_version: "1".to_string(),
+        cut_settings: Vec::new(),
+        shapes: images
+            .iter()
+            .map(|bytes| {
+                Shape::Bitmap(Bitmap {
+                    cut_index: 0,
+                    xform: XForm::identity(),
+                    w: 10.0,
+                    h: 10.0,
+                    data: encode_bitmap_data(bytes),
+                })
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn bmp_bytes() -> Vec<u8> {
+    let img = image::RgbImage::from_pixel(2, 2, image::Rgb([0, 0, 255]));
+    let mut out = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut out, image::ImageFormat::Bmp).unwrap();
+    out.into_inner()
+}
+
+#[test]
+fn test_bitmap_mime_is_sniffed() {
+    let jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+    let project = bitmap_project(&[jpeg, bmp_bytes()]);
+
+    let svg = lbrn2_to_svg(&project);
+    assert!(
+        svg.contains("xlink:href=\"data:image/jpeg;base64,/9j/"),
+        "{}",
+        svg
+    );
+    assert!(
+        svg.contains("xlink:href=\"data:image/bmp;base64,Qk"),
+        "{}",
+        svg
+    );
+
+    let options = SvgExportOptions {
+        reencode_bitmaps: true,
+        ..Default::default()
+    };
+    let svg = lbrn2_to_svg_with_options(&project, &options);
+    assert!(svg.contains("data:image/jpeg;base64,"));
+    assert!(!svg.contains("data:image/bmp"));
+    assert!(svg.contains("xlink:href=\"data:image/png;base64,iVBORw0KGgo"));
+}
+
+#[test]
+fn test_bitmaps_as_sidecar_files() {
+    let project = bitmap_project(&[bmp_bytes(), load_png_artifact()]);
+    let options = SvgExportOptions {
+        reencode_bitmaps: true,
+        bitmap_dir: Some("drawing_files/".to_string()),
+        ..Default::default()
+    };
+    let (svg, assets) = lbrn2_to_svg_with_assets(&project, &options);
+
+    assert!(!svg.contains("base64"));
+    assert!(svg.contains("xlink:href=\"drawing_files/bitmap-1.png\""));
+    assert!(svg.contains("xlink:href=\"drawing_files/bitmap-2.png\""));
+
+    let paths: Vec<&str> = assets.iter().map(|a| a.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec!["drawing_files/bitmap-1.png", "drawing_files/bitmap-2.png"]
+    );
+    assert!(assets.iter().all(|a| a.mime == "image/png"));
+    assert!(assets[0].data.starts_with(b"\x89PNG"));
+}
+
+/// Raw bytes of the PNG embedded in the image artifact
+fn load_png_artifact() -> Vec<u8> {
+    let project = load_artifact("image");
+    let Shape::Bitmap(bitmap) = &project.shapes[0] else {
+        panic!("expected a bitmap");
+    };
+    laser_tools::lbrn2::bitmap::decode_bitmap_data(&bitmap.data).unwrap()
+}
+
+#[test]
+fn test_thumbnail_is_decoded_as_png() {
+    let mut project = load_artifact("word");
+    assert_eq!(project.device_name.as_deref(), Some("JCZFiber (LMC4)"));
+    assert!(project.thumbnail_png().is_none());
+
+    let png = load_png_artifact();
+    project.thumbnail = Some(encode_bitmap_data(&png));
+    assert_eq!(project.thumbnail_png().unwrap().unwrap(), png);
+
+    project.thumbnail = Some(encode_bitmap_data(&bmp_bytes()));
+    let reencoded = project.thumbnail_png().unwrap().unwrap();
+    assert!(reencoded.starts_with(b"\x89PNG"));
+
+    project.thumbnail = Some("not base64!".to_string());
+    assert!(project.thumbnail_png().unwrap().is_err());
+}
+
+#[test]
+fn test_shared_paths_are_written_once_in_defs() {
+    let project = load_artifact("crucifix");
+    let options = SvgExportOptions {
+        reuse_shared_paths: true,
+        ..Default::default()
+    };
+    let svg = lbrn2_to_svg_with_options(&project, &options);
+
+    assert_eq!(svg.matches("<path ").count(), 1, "{}", svg);
+    assert!(svg.contains("<defs>\n        <path id=\"path-1\" d=\"M"));
+    assert_eq!(svg.matches("<use xlink:href=\"#path-1\"").count(), 4);
+    // Baking needs absolute coordinates for every instance
+    let baked = SvgExportOptions {
+        bake_transforms: true,
+        ..options
+    };
+    let svg = lbrn2_to_svg_with_options(&project, &baked);
+    assert!(!svg.contains("<use") && svg.matches("<path ").count() == 4);
+}
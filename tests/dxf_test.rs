@@ -0,0 +1,144 @@
+mod common;
+
+use common::project_bounds;
+use laser_tools::lbrn2::{
+    DxfImportOptions, LightBurnProject, Shape, dxf_to_lbrn2, dxf_to_lbrn2_with_warnings,
+    lbrn2_to_dxf, lbrn2_to_svg, parse_lbrn2,
+};
+use std::fs;
+use std::path::Path;
+
+fn artifacts_dir() -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/artifacts")
+}
+
+#[test]
+fn test_import_fixture() {
+    let dxf = fs::read_to_string(artifacts_dir().join("shapes.dxf")).unwrap();
+    let (project, warnings) = dxf_to_lbrn2_with_warnings(&dxf, &DxfImportOptions::default())
+        .expect("Failed to import shapes.dxf");
+
+    assert_eq!(warnings, ["1 TEXT entity skipped"]);
+
+    let layers: Vec<(&str, Option<&str>)> = project
+        .cut_settings
+        .iter()
+        .map(|cs| (cs.name.as_str(), cs.color.as_deref()))
+        .collect();
+    assert_eq!(
+        layers,
+        [("Cut", Some("#FF0000")), ("Engrave", Some("#0000FF"))]
+    );
+
+    let kinds: Vec<&str> = project
+        .shapes
+        .iter()
+        .map(|s| match s {
+            Shape::Ellipse(_) => "ellipse",
+            Shape::Path(_) => "path",
+            _ => "other",
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "path", "path", "ellipse", "path", "ellipse", "path", "path", "path"
+        ]
+    );
+
+    // The rotated full ellipse keeps its axes
+    let Shape::Ellipse(ellipse) = &project.shapes[4] else {
+        unreachable!()
+    };
+    assert!((ellipse.rx - 10.0).abs() < 1e-6 && (ellipse.ry - 5.0).abs() < 1e-6);
+    assert!((ellipse.xform.a - ellipse.xform.b).abs() < 1e-6);
+
+    // The rounded polyline reaches x = 40 at the bulge apex; every shape lies
+    // inside the drawing (SVG-space bounds, so Y is negated)
+    let bounds = project_bounds(&project).unwrap();
+    assert!((bounds.min_x - 0.0).abs() < 1e-6, "{:?}", bounds);
+    assert!(bounds.max_x <= 112.0, "{:?}", bounds);
+    assert!(bounds.min_y >= -80.0 && bounds.max_y <= 0.0, "{:?}", bounds);
+
+    // Usable as input to the SVG converter
+    let svg = lbrn2_to_svg(&project);
+    assert!(svg.contains("<path"));
+    assert!(svg.contains("<ellipse"));
+}
+
+#[test]
+fn test_artifacts_survive_dxf_round_trip() {
+    let mut count = 0;
+
+    for entry in fs::read_dir(artifacts_dir()).expect("Failed to read artifacts dir") {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("lbrn2") {
+            continue;
+        }
+
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let original = parse_lbrn2(&fs::read_to_string(&path).unwrap()).unwrap();
+        let Some(expected) = project_bounds(&original) else {
+            continue;
+        };
+
+        let dxf = lbrn2_to_dxf(&original);
+        let imported = dxf_to_lbrn2(&dxf, &DxfImportOptions::default())
+            .unwrap_or_else(|e| panic!("{}: {}", name, e));
+        let actual =
+            project_bounds(&imported).unwrap_or_else(|| panic!("{}: nothing was imported", name));
+
+        // Ellipses come back with their axes in a different order, which
+        // shifts the sampled bounds slightly
+        let tolerance = 0.01 + 0.005 * expected.width().max(expected.height());
+        for (what, a, b) in [
+            ("min_x", expected.min_x, actual.min_x),
+            ("min_y", expected.min_y, actual.min_y),
+            ("max_x", expected.max_x, actual.max_x),
+            ("max_y", expected.max_y, actual.max_y),
+        ] {
+            assert!(
+                (a - b).abs() < tolerance,
+                "{}: {} differs after round trip: {} vs {}",
+                name,
+                what,
+                a,
+                b
+            );
+        }
+
+        // One DXF layer per cut index, so the layer structure survives
+        let layers = |p: &LightBurnProject| {
+            let mut indices: Vec<i32> = p
+                .shapes
+                .iter()
+                .filter(|s| !matches!(s, Shape::Bitmap(_)))
+                .map(|s| s.cut_index())
+                .collect();
+            indices.sort_unstable();
+            indices.dedup();
+            indices.len()
+        };
+        if original
+            .shapes
+            .iter()
+            .all(|s| !matches!(s, Shape::Group(_)))
+        {
+            assert_eq!(
+                layers(&imported),
+                layers(&original),
+                "{}: layer count changed",
+                name
+            );
+        }
+
+        count += 1;
+    }
+
+    assert!(count > 0, "No .lbrn2 artifacts found");
+}
+
+#[test]
+fn test_invalid_dxf_is_an_error() {
+    assert!(dxf_to_lbrn2("not a dxf", &DxfImportOptions::default()).is_err());
+}
@@ -0,0 +1,296 @@
+use laser_tools::lbrn2::bounds::get_transformed_bounds;
+use laser_tools::lbrn2::{
+    CutSetting, Ellipse, GcodeOptions, Group, LightBurnProject, Path, PathPrimitive, Shape, Vec2,
+    XForm, lbrn2_to_gcode, parse_lbrn2,
+};
+use std::fs;
+
+const TOLERANCE: f64 = 0.01;
+
+/// A burning polyline recovered from the G-code
+#[derive(Debug)]
+struct Cut {
+    points: Vec<(f64, f64)>,
+    s: f64,
+    feed: f64,
+}
+
+/// Minimal GRBL interpreter: absolute mm coordinates, modal S and F, a new
+/// cut starts at every G0
+fn parse_toolpath(gcode: &str) -> Vec<Cut> {
+    let mut cuts: Vec<Cut> = Vec::new();
+    let (mut x, mut y, mut s, mut feed) = (0.0, 0.0, 0.0, 0.0);
+
+    for line in gcode.lines() {
+        let code = line.split(';').next().unwrap().trim();
+        let mut words = code.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let start = (x, y);
+        for word in words {
+            let value: f64 = word[1..]
+                .parse()
+                .unwrap_or_else(|_| panic!("bad word {}", word));
+            match &word[..1] {
+                "X" => x = value,
+                "Y" => y = value,
+                "S" => s = value,
+                "F" => feed = value,
+                _ => {}
+            }
+        }
+        match command {
+            "G0" => cuts.push(Cut {
+                points: vec![(x, y)],
+                s,
+                feed,
+            }),
+            "G1" => {
+                if cuts.is_empty() {
+                    cuts.push(Cut {
+                        points: vec![start],
+                        s,
+                        feed,
+                    });
+                }
+                let cut = cuts.last_mut().unwrap();
+                cut.points.push((x, y));
+                cut.s = s;
+                cut.feed = feed;
+            }
+            _ => {}
+        }
+    }
+
+    cuts.retain(|c| c.points.len() > 1);
+    cuts
+}
+
+fn project(cut_settings: Vec<CutSetting>, shapes: Vec<Shape>) -> LightBurnProject {
+    LightBurnProject {
+        app_version: String::new(),
+        format_version: String::new(),
+        cut_settings,
+        shapes,
+        ..Default::default()
+    }
+}
+
+/// Largest distance from any point on the toolpath (vertices and segment
+/// midpoints) to the nearest point of `samples`
+fn max_deviation(cuts: &[Cut], samples: &[(f64, f64)]) -> f64 {
+    let nearest = |p: (f64, f64)| {
+        samples
+            .iter()
+            .map(|q| (p.0 - q.0).hypot(p.1 - q.1))
+            .fold(f64::INFINITY, f64::min)
+    };
+    cuts.iter()
+        .flat_map(|c| {
+            c.points.windows(2).flat_map(|w| {
+                let mid = ((w[0].0 + w[1].0) / 2.0, (w[0].1 + w[1].1) / 2.0);
+                [w[0], mid, w[1]]
+            })
+        })
+        .map(nearest)
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn test_ellipse_in_rotated_group() {
+    let (cos, sin) = (30f64.to_radians().cos(), 30f64.to_radians().sin());
+    let rotate = XForm {
+        a: cos,
+        b: sin,
+        c: -sin,
+        d: cos,
+        e: 50.0,
+        f: 40.0,
+    };
+    let ellipse = Shape::Ellipse(Ellipse {
+        cut_index: 0,
+        xform: XForm {
+            e: 5.0,
+            ..XForm::identity()
+        },
+        rx: 20.0,
+        ry: 10.0,
+    });
+    let group = Shape::Group(Group {
+        cut_index: 0,
+        xform: rotate,
+        children: vec![ellipse],
+    });
+
+    let gcode = lbrn2_to_gcode(&project(vec![], vec![group]), &GcodeOptions::default());
+    let cuts = parse_toolpath(&gcode);
+    assert_eq!(cuts.len(), 1, "{}", gcode);
+
+    let samples: Vec<(f64, f64)> = (0..20000)
+        .map(|i| {
+            let t = i as f64 / 20000.0 * std::f64::consts::TAU;
+            rotate.transform_point(5.0 + 20.0 * t.cos(), 10.0 * t.sin())
+        })
+        .collect();
+    let deviation = max_deviation(&cuts, &samples);
+    assert!(deviation < TOLERANCE * 1.5, "deviation {}", deviation);
+
+    // Closed: ends where it started
+    let points = &cuts[0].points;
+    assert_eq!(points.first(), points.last());
+}
+
+#[test]
+fn test_bezier_path_and_layer_settings() {
+    let mut start = Vec2::new(0.0, 0.0);
+    start.c0x = Some(0.0);
+    start.c0y = Some(30.0);
+    let mut mid = Vec2::new(30.0, 0.0);
+    mid.c1x = Some(30.0);
+    mid.c1y = Some(30.0);
+    let path = Shape::Path(Path {
+        cut_index: 3,
+        xform: XForm {
+            e: 10.0,
+            f: 10.0,
+            ..XForm::identity()
+        },
+        vert_list: "".into(),
+        prim_list: "".into(),
+        parsed_verts: vec![start, mid, Vec2::new(30.0, -10.0)].into(),
+        parsed_primitives: vec![
+            PathPrimitive::Bezier {
+                start_idx: 0,
+                end_idx: 1,
+            },
+            PathPrimitive::Line {
+                start_idx: 1,
+                end_idx: 2,
+            },
+        ]
+        .into(),
+        vert_id: None,
+        prim_id: None,
+    });
+    let cs = CutSetting {
+        index: 3,
+        speed: Some(15.0),
+        max_power: Some(55.0),
+        num_passes: Some(3),
+        ..Default::default()
+    };
+
+    let gcode = lbrn2_to_gcode(&project(vec![cs], vec![path]), &GcodeOptions::default());
+    let cuts = parse_toolpath(&gcode);
+    assert_eq!(cuts.len(), 3, "one cut per pass:\n{}", gcode);
+
+    let bezier = |t: f64| {
+        let mt = 1.0 - t;
+        let x = 3.0 * mt * t * t * 30.0 + t * t * t * 30.0;
+        let y = 3.0 * mt * mt * t * 30.0 + 3.0 * mt * t * t * 30.0;
+        (x + 10.0, y + 10.0)
+    };
+    let mut samples: Vec<(f64, f64)> = (0..=20000).map(|i| bezier(i as f64 / 20000.0)).collect();
+    samples.extend((0..=1000).map(|i| (40.0, 10.0 - i as f64 / 100.0)));
+
+    for cut in &cuts {
+        assert_eq!(cut.s, 550.0);
+        assert_eq!(cut.feed, 900.0);
+        assert_eq!(cut.points.first(), Some(&(10.0, 10.0)));
+        assert_eq!(cut.points.last(), Some(&(40.0, 0.0)));
+        assert!(cut.points.contains(&(40.0, 10.0)));
+    }
+    let deviation = max_deviation(&cuts, &samples);
+    assert!(deviation < TOLERANCE * 1.5, "deviation {}", deviation);
+}
+
+#[test]
+fn test_artifact_toolpaths_stay_inside_shape_bounds() {
+    let artifacts_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/artifacts");
+
+    for entry in fs::read_dir(&artifacts_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("lbrn2") {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let project = parse_lbrn2(&fs::read_to_string(&path).unwrap()).unwrap();
+        let gcode = lbrn2_to_gcode(&project, &GcodeOptions::default());
+
+        let bounds = project
+            .shapes
+            .iter()
+            .filter(|s| !matches!(s, Shape::Bitmap(_)))
+            .filter_map(get_transformed_bounds)
+            .reduce(|mut acc, b| {
+                acc.expand(&b);
+                acc
+            });
+        let cuts = parse_toolpath(&gcode);
+        let Some(bounds) = bounds else {
+            assert!(cuts.is_empty(), "{}: unexpected toolpath", name);
+            continue;
+        };
+        assert!(!cuts.is_empty(), "{}: no toolpath", name);
+
+        // Bounds are in SVG space (Y down); the toolpath is Y up
+        let eps = 0.002;
+        for (x, y) in cuts.iter().flat_map(|c| c.points.iter().copied()) {
+            assert!(
+                x >= bounds.min_x - eps
+                    && x <= bounds.max_x + eps
+                    && -y >= bounds.min_y - eps
+                    && -y <= bounds.max_y + eps,
+                "{}: ({}, {}) outside {:?}",
+                name,
+                x,
+                y,
+                bounds
+            );
+        }
+    }
+}
+
+#[test]
+fn test_mirror_flags_flip_about_job_center() {
+    let path = Shape::Path(Path {
+        cut_index: 0,
+        xform: XForm::identity(),
+        vert_list: "".into(),
+        prim_list: "".into(),
+        parsed_verts: vec![
+            Vec2::new(10.0, 10.0),
+            Vec2::new(30.0, 10.0),
+            Vec2::new(10.0, 20.0),
+        ]
+        .into(),
+        parsed_primitives: vec![
+            PathPrimitive::Line {
+                start_idx: 0,
+                end_idx: 1,
+            },
+            PathPrimitive::Line {
+                start_idx: 1,
+                end_idx: 2,
+            },
+        ]
+        .into(),
+        vert_id: None,
+        prim_id: None,
+    });
+    let mut mirrored = project(vec![], vec![path]);
+    mirrored.mirror_x = true;
+    mirrored.mirror_y = true;
+
+    let cuts = parse_toolpath(&lbrn2_to_gcode(&mirrored, &GcodeOptions::default()));
+    assert_eq!(cuts.len(), 1);
+    let expected = [(30.0, 20.0), (10.0, 20.0), (30.0, 10.0)];
+    for (p, e) in cuts[0].points.iter().zip(expected) {
+        assert!(
+            (p.0 - e.0).abs() < 1e-9 && (p.1 - e.1).abs() < 1e-9,
+            "{:?}",
+            cuts[0].points
+        );
+    }
+}
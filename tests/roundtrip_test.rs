@@ -0,0 +1,227 @@
+use laser_tools::lbrn2::writer::{format_prim_list, format_vert_list};
+use laser_tools::lbrn2::{
+    Bitmap, CutSetting, CutType, Group, LightBurnProject, Notes, PathPrimitive, Rect, Shape, Text,
+    Vec2, XForm, parse_lbrn2_with_warnings, write_lbrn2,
+};
+use std::fs;
+use std::path::Path;
+
+/// The parser keeps the raw VertList/PrimList text next to the parsed data,
+/// while the writer re-encodes from the parsed data. Only the parsed form is
+/// expected to survive a round trip, so re-derive the text from it.
+fn normalize_shape(shape: &mut Shape) {
+    match shape {
+        Shape::Path(p) => {
+            p.vert_list = format_vert_list(&p.parsed_verts).into();
+            if !p.is_line_closed() {
+                p.prim_list = format_prim_list(&p.parsed_primitives).into();
+            }
+        }
+        Shape::Group(g) => g.children.iter_mut().for_each(normalize_shape),
+        _ => {}
+    }
+}
+
+fn normalize(mut project: LightBurnProject) -> LightBurnProject {
+    project.shapes.iter_mut().for_each(normalize_shape);
+    project
+}
+
+fn assert_round_trip(name: &str, content: &str) {
+    let (original, original_warnings) =
+        parse_lbrn2_with_warnings(content).unwrap_or_else(|e| panic!("{}: {}", name, e));
+
+    let written = write_lbrn2(&original);
+    let (reparsed, reparsed_warnings) = parse_lbrn2_with_warnings(&written)
+        .unwrap_or_else(|e| panic!("{}: written output failed to parse: {}", name, e));
+
+    assert!(
+        reparsed_warnings.len() <= original_warnings.len(),
+        "{}: writer introduced warnings: {:?}",
+        name,
+        reparsed_warnings
+    );
+    assert_eq!(
+        normalize(original),
+        normalize(reparsed.clone()),
+        "{}: model changed after parse -> write -> parse",
+        name
+    );
+
+    // Writing is stable once the raw text has been re-encoded
+    assert_eq!(
+        write_lbrn2(&reparsed),
+        written,
+        "{}: writer is not stable",
+        name
+    );
+}
+
+#[test]
+fn test_artifacts_round_trip() {
+    let artifacts_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/artifacts");
+    let mut count = 0;
+
+    for entry in fs::read_dir(&artifacts_dir).expect("Failed to read artifacts dir") {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("lbrn2") {
+            continue;
+        }
+
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_round_trip(&name, &content);
+        count += 1;
+    }
+
+    assert!(count > 0, "No .lbrn2 artifacts found");
+}
+
+#[test]
+fn test_synthetic_project_round_trip() {
+    let mut outline = CutSetting::new(0);
+    outline.name = "Outline <\"quoted\"> & more".to_string();
+    outline.speed = Some(12.5);
+    outline.num_passes = Some(3);
+    outline.tabs.enabled = Some(true);
+    outline.tabs.size = Some(0.4);
+    outline
+        .params
+        .insert("runBlower".to_string(), "1".to_string());
+
+    let mut photo = CutSetting::new(2);
+    photo.cut_type = CutType::Image;
+    photo.params.insert("dpi".to_string(), "318".to_string());
+
+    let project = LightBurnProject {
+        app_version: "1.7.08".to_string(),
+        format_version: "1".to_string(),
+        device_name: Some("GRBL & co".to_string()),
+        material_height: Some(3.2),
+        mirror_x: true,
+        mirror_y: false,
+        notes: Some(Notes {
+            text: "Line one\nLine \"two\"".to_string(),
+            show_on_load: true,
+        }),
+        thumbnail: Some("iVBORw0KGgo=".to_string()),
+        ui_prefs: [("Optimize_ByLayer".to_string(), "0".to_string())].into(),
+        cut_settings: vec![outline, photo],
+        shapes: vec![
+            Shape::Group(Group {
+                cut_index: 0,
+                xform: XForm {
+                    a: 0.5,
+                    b: 0.25,
+                    c: -0.25,
+                    d: 0.5,
+                    e: 10.0,
+                    f: -3.125,
+                },
+                children: vec![Shape::Rect(Rect {
+                    cut_index: 0,
+                    xform: XForm::identity(),
+                    w: 10.0,
+                    h: 4.0,
+                    cr: 1.5,
+                })],
+            }),
+            Shape::Bitmap(Bitmap {
+                cut_index: 2,
+                xform: XForm::identity(),
+                w: 20.0,
+                h: 20.0,
+                data: "iVBORw0KGgo=".to_string(),
+            }),
+            Shape::Text(Text {
+                cut_index: 0,
+                xform: XForm::identity(),
+                text: "Two \"lines\"\n& more".to_string(),
+                font: "Arial,-1,100,5,50,0,0,0,0,0".to_string(),
+                h: 6.5,
+                letter_spacing: 0.0,
+                line_spacing: 1.25,
+                align_h: 2,
+                align_v: 0,
+                weld: false,
+                backup_path: None,
+            }),
+        ],
+        ..Default::default()
+    };
+
+    let written = write_lbrn2(&project);
+    assert!(written.contains("<CutSetting_Img type=\"Image\">"));
+
+    let (reparsed, warnings) = parse_lbrn2_with_warnings(&written).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+
+    // Layer colors are derived from the index when reading
+    let mut expected = project.clone();
+    for cs in &mut expected.cut_settings {
+        cs.color = reparsed
+            .cut_settings
+            .iter()
+            .find(|r| r.index == cs.index)
+            .and_then(|r| r.color.clone());
+    }
+    assert_eq!(reparsed, expected);
+}
+
+#[test]
+fn test_shared_lists_stay_shared() {
+    let content = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/artifacts/crucifix.lbrn2"),
+    )
+    .unwrap();
+    let mut project = parse_lbrn2_with_warnings(&content).unwrap().0;
+    assert_eq!(project.shared_verts.len(), 1);
+    assert_eq!(project.shared_prims.len(), 1);
+
+    // The lists are written once and referenced by ID afterwards
+    let written = write_lbrn2(&project);
+    assert_eq!(written.matches("<VertList>").count(), 1);
+    assert_eq!(written.matches("<PrimList>").count(), 1);
+    assert_eq!(written.matches("VertID=\"0\" PrimID=\"0\"").count(), 4);
+
+    // Editing the shared outline changes every instance
+    let square = vec![
+        Vec2::new(0.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(1.0, 1.0),
+        Vec2::new(0.0, 1.0),
+    ];
+    let edges = (0..4)
+        .map(|i| PathPrimitive::Line {
+            start_idx: i,
+            end_idx: (i + 1) % 4,
+        })
+        .collect();
+    assert_eq!(project.set_shared_verts(0, square.clone()), 4);
+    assert_eq!(project.set_shared_prims(0, edges), 4);
+    let (reparsed, warnings) = parse_lbrn2_with_warnings(&write_lbrn2(&project)).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    for shape in &reparsed.shapes {
+        let Shape::Path(path) = shape else {
+            panic!("expected a path, got {:?}", shape);
+        };
+        assert_eq!(*path.parsed_verts, square);
+        assert_eq!(path.vert_id, Some(0));
+    }
+
+    // A path edited on its own redefines the ID where it is written
+    let Shape::Path(path) = &mut project.shapes[2] else {
+        unreachable!()
+    };
+    std::sync::Arc::make_mut(&mut path.parsed_verts).push(Vec2::new(0.5, 2.0));
+    let (reparsed, _) = parse_lbrn2_with_warnings(&write_lbrn2(&project)).unwrap();
+    let counts: Vec<usize> = reparsed
+        .shapes
+        .iter()
+        .map(|shape| match shape {
+            Shape::Path(path) => path.parsed_verts.len(),
+            _ => 0,
+        })
+        .collect();
+    assert_eq!(counts, vec![4, 4, 5, 4]);
+}
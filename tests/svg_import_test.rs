@@ -0,0 +1,98 @@
+mod common;
+
+use common::project_bounds;
+use laser_tools::lbrn2::{
+    Shape, SvgImportOptions, lbrn2_to_svg, parse_lbrn2, svg_to_lbrn2, svg_to_lbrn2_with_warnings,
+};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+fn leaf_cut_indices(shapes: &[Shape], out: &mut BTreeSet<i32>) {
+    for shape in shapes {
+        match shape {
+            Shape::Group(g) => leaf_cut_indices(&g.children, out),
+            Shape::Bitmap(_) => {}
+            _ => {
+                out.insert(shape.cut_index());
+            }
+        }
+    }
+}
+
+fn assert_close(name: &str, what: &str, a: f64, b: f64) {
+    assert!(
+        (a - b).abs() < 0.01,
+        "{}: {} differs after round trip: {} vs {}",
+        name,
+        what,
+        a,
+        b
+    );
+}
+
+#[test]
+fn test_artifacts_survive_svg_round_trip() {
+    let artifacts_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/artifacts");
+    let mut count = 0;
+
+    for entry in fs::read_dir(&artifacts_dir).expect("Failed to read artifacts dir") {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("lbrn2") {
+            continue;
+        }
+
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let original = parse_lbrn2(&fs::read_to_string(&path).unwrap()).unwrap();
+        let Some(expected) = project_bounds(&original) else {
+            continue;
+        };
+
+        let svg = lbrn2_to_svg(&original);
+        let imported = svg_to_lbrn2(&svg, &SvgImportOptions::default())
+            .unwrap_or_else(|e| panic!("{}: {}", name, e));
+
+        let actual =
+            project_bounds(&imported).unwrap_or_else(|| panic!("{}: nothing was imported", name));
+        assert_close(&name, "min_x", expected.min_x, actual.min_x);
+        assert_close(&name, "min_y", expected.min_y, actual.min_y);
+        assert_close(&name, "max_x", expected.max_x, actual.max_x);
+        assert_close(&name, "max_y", expected.max_y, actual.max_y);
+
+        // Layer colors written by the exporter map back to the same layers
+        if !original.cut_settings.is_empty() {
+            let mut before = BTreeSet::new();
+            let mut after = BTreeSet::new();
+            leaf_cut_indices(&original.shapes, &mut before);
+            leaf_cut_indices(&imported.shapes, &mut after);
+            assert_eq!(after, before, "{}: layers changed", name);
+        }
+
+        count += 1;
+    }
+
+    assert!(count > 0, "No .lbrn2 artifacts found");
+}
+
+#[test]
+fn test_import_reports_unsupported_content() {
+    let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+        <text x="1" y="5">Hello</text>
+        <rect x="1" y="1" width="8" height="8" stroke="#FF0000" fill="none"/>
+    </svg>"##;
+
+    let (project, warnings) =
+        svg_to_lbrn2_with_warnings(svg, &SvgImportOptions::default()).unwrap();
+    assert_eq!(project.shapes.len(), 1);
+    assert_eq!(project.cut_settings.len(), 1);
+    assert!(
+        warnings.iter().any(|w| w.contains("text")),
+        "{:?}",
+        warnings
+    );
+}
+
+#[test]
+fn test_invalid_svg_is_an_error() {
+    assert!(svg_to_lbrn2("<svg", &SvgImportOptions::default()).is_err());
+}